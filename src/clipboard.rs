@@ -0,0 +1,36 @@
+//! System clipboard access, compiled in with the `clipboard` cargo feature.
+
+use anyhow::Result;
+
+#[cfg(feature = "clipboard")]
+pub fn read_text() -> Result<String> {
+    use anyhow::Context;
+
+    let mut clipboard = arboard::Clipboard::new().context("clipboard unavailable")?;
+    clipboard.get_text().context("clipboard has no text")
+}
+
+#[cfg(feature = "clipboard")]
+pub fn write_text(text: &str) -> Result<()> {
+    use anyhow::Context;
+
+    let mut clipboard = arboard::Clipboard::new().context("clipboard unavailable")?;
+    clipboard
+        .set_text(text)
+        .context("could not write to the clipboard")
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn read_text() -> Result<String> {
+    Err(not_built_in())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn write_text(_text: &str) -> Result<()> {
+    Err(not_built_in())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn not_built_in() -> anyhow::Error {
+    anyhow::anyhow!("clipboard support not built in (install with --features clipboard)")
+}
@@ -0,0 +1,305 @@
+use crate::models::SafeToSpendBasis;
+use serde::{Deserialize, Serialize};
+
+/// User preferences read from `config.json` next to the ledger. Every field has a default so
+/// a missing or partial file behaves like a fresh install.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Soft minimum for auto-budget suggestions. Categories spending well below it are only
+    /// nudged up proportionally, so tiny categories stay close to their real average.
+    pub suggestion_floor: f64,
+    /// Days of history auto-budget suggestions average over; adjustable on the Budgets tab.
+    pub suggestion_window_days: u32,
+    /// Headroom added to suggestions (0.1 = 10%); adjustable on the Budgets tab.
+    pub suggestion_buffer: f64,
+    /// Suggestions are rounded up to a multiple of this (10.0 turns $321.47 into $330) so
+    /// applied budgets are easy to remember. 0 keeps them to the cent.
+    pub suggestion_round_step: f64,
+    /// Month (1-12) the fiscal year starts in. January keeps the yearly view on calendar years.
+    pub fiscal_year_start_month: u32,
+    /// Categories that move money around rather than spend it (savings, transfers). They are
+    /// left out of totals such as the search summary.
+    pub excluded_categories: Vec<String>,
+    /// Categories that are always income, whatever the sign of their amounts (e.g. a
+    /// "Refunds" category with mixed signs). Subcategories are included.
+    pub income_categories: Vec<String>,
+    /// Spending that can't easily change month to month (rent, insurance, loan payments).
+    /// Subcategories are included.
+    pub fixed_categories: Vec<String>,
+    /// Spending that could be cut back (dining out, entertainment). Categories in neither
+    /// list are reported as unclassified.
+    pub discretionary_categories: Vec<String>,
+    /// Whether the overview's safe-to-spend figure subtracts budgets or actual spending.
+    pub safe_to_spend_basis: SafeToSpendBasis,
+    /// Savings rate (0.2 = 20%) the overview compares the month against.
+    pub savings_rate_target: f64,
+    /// Append every change to `actions.log` (JSON lines) next to the ledger.
+    pub audit_log: bool,
+    /// Capitalize each word of entered categories ("eating out" becomes "Eating Out").
+    /// Whitespace is always trimmed and collapsed.
+    pub title_case_categories: bool,
+    /// How many days ahead the overview lists upcoming bills.
+    pub upcoming_bill_days: u32,
+    pub currency: CurrencyFormat,
+    /// Spread transactions marked for amortization across their months in the overview and
+    /// charts (accrual) instead of counting them when paid (cash).
+    pub accrual: bool,
+    /// Days without any transactions after which a budget is flagged as possibly stale.
+    pub stale_budget_days: u32,
+    /// Show a warning when the month's net, or its month-end projection, drops below this.
+    /// `None` turns the warning off.
+    pub low_balance_threshold: Option<f64>,
+    /// Flag the week when its daily spending runs this many times the month's daily average
+    /// (1.5 = 50% faster). `0` turns the alert off.
+    pub velocity_alert_factor: f64,
+    /// Column mappings for bank CSV exports, used alongside the built-in profiles. A profile
+    /// here with a built-in's name replaces it.
+    pub import_profiles: Vec<ImportProfile>,
+    /// Panels and charts shown on the overview, in order. Panels stack in the left column
+    /// and charts in the right; a column with nothing enabled gives its width to the other.
+    pub overview_widgets: Vec<OverviewWidget>,
+    /// Notice when the ledger file is changed by something else (an editor, a sync client)
+    /// and ask before reloading it or writing over it.
+    pub watch_ledger: bool,
+}
+
+/// One piece of the overview, as listed in `Config::overview_widgets`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverviewWidget {
+    /// Safe to spend, the month's totals and the budget lines.
+    Stats,
+    TopExpenses,
+    IncomeSources,
+    /// The month's daily spending heatmap.
+    Calendar,
+    UpcomingBills,
+    CategoryBar,
+    CashflowLine,
+    /// The month's daily spending as a sparkline.
+    Sparkline,
+}
+
+impl OverviewWidget {
+    /// The layout before widgets were configurable; the sparkline is opt-in.
+    pub fn defaults() -> Vec<OverviewWidget> {
+        vec![
+            OverviewWidget::Stats,
+            OverviewWidget::TopExpenses,
+            OverviewWidget::IncomeSources,
+            OverviewWidget::Calendar,
+            OverviewWidget::UpcomingBills,
+            OverviewWidget::CategoryBar,
+            OverviewWidget::CashflowLine,
+        ]
+    }
+}
+
+/// How to read one bank's CSV export: which header holds each field, how dates are written,
+/// and how amounts are signed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportProfile {
+    pub name: String,
+    pub date_column: String,
+    /// A chrono format string such as `%Y-%m-%d` or `%m/%d/%Y`.
+    pub date_format: String,
+    pub description_column: String,
+    /// Missing or blank categories become "General".
+    pub category_column: Option<String>,
+    /// A single signed amount column. Leave unset when the bank uses debit/credit columns.
+    pub amount_column: Option<String>,
+    /// The single amount column shows spending as negative (most banks); it is flipped to
+    /// centsh's positive-is-spending convention.
+    pub negative_is_spending: bool,
+    /// Money out, as a positive number, when the bank splits amounts into two columns.
+    pub debit_column: Option<String>,
+    /// Money in, as a positive number, when the bank splits amounts into two columns.
+    pub credit_column: Option<String>,
+}
+
+impl Default for ImportProfile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            date_column: "date".to_string(),
+            date_format: "%Y-%m-%d".to_string(),
+            description_column: "description".to_string(),
+            category_column: None,
+            amount_column: Some("amount".to_string()),
+            negative_is_spending: false,
+            debit_column: None,
+            credit_column: None,
+        }
+    }
+}
+
+impl ImportProfile {
+    /// Profiles that work without any configuration:
+    /// - `centsh`: the app's own CSV export.
+    /// - `signed`: `Date,Description,Amount` with spending negative and US dates.
+    /// - `debit-credit`: `Date,Description,Debit,Credit` with ISO dates.
+    pub fn built_in() -> Vec<ImportProfile> {
+        vec![
+            ImportProfile {
+                name: "centsh".to_string(),
+                category_column: Some("category".to_string()),
+                ..ImportProfile::default()
+            },
+            ImportProfile {
+                name: "signed".to_string(),
+                date_column: "Date".to_string(),
+                date_format: "%m/%d/%Y".to_string(),
+                description_column: "Description".to_string(),
+                amount_column: Some("Amount".to_string()),
+                negative_is_spending: true,
+                ..ImportProfile::default()
+            },
+            ImportProfile {
+                name: "debit-credit".to_string(),
+                date_column: "Date".to_string(),
+                description_column: "Description".to_string(),
+                amount_column: None,
+                debit_column: Some("Debit".to_string()),
+                credit_column: Some("Credit".to_string()),
+                ..ImportProfile::default()
+            },
+        ]
+    }
+}
+
+/// How amounts are displayed. Purely cosmetic: amounts are stored without a currency.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CurrencyFormat {
+    pub symbol: String,
+    /// Put the symbol after the number (`12.50 €`) instead of before it (`$12.50`).
+    pub symbol_after: bool,
+    /// Separate the symbol and the number with a space.
+    pub space: bool,
+    /// Inserted between groups of three digits; empty for no grouping.
+    pub thousands_separator: String,
+    pub decimal_separator: String,
+}
+
+impl Default for CurrencyFormat {
+    fn default() -> Self {
+        Self {
+            symbol: "$".to_string(),
+            symbol_after: false,
+            space: false,
+            thousands_separator: String::new(),
+            decimal_separator: ".".to_string(),
+        }
+    }
+}
+
+impl CurrencyFormat {
+    /// Format for a currency code or symbol typed in the setup wizard: USD, EUR, GBP, JPY,
+    /// CHF and their symbols get their usual layout; anything else is used as a prefix
+    /// symbol. Blank keeps the default dollar format.
+    pub fn for_currency(input: &str) -> Self {
+        let input = input.trim();
+        let euro_style = |symbol: &str| Self {
+            symbol: symbol.to_string(),
+            symbol_after: true,
+            space: true,
+            thousands_separator: ".".to_string(),
+            decimal_separator: ",".to_string(),
+        };
+        match input.to_ascii_uppercase().as_str() {
+            "" | "USD" | "$" => Self::default(),
+            "EUR" | "€" => euro_style("€"),
+            "GBP" | "£" => Self {
+                symbol: "£".to_string(),
+                ..Self::default()
+            },
+            "JPY" | "¥" => Self {
+                symbol: "¥".to_string(),
+                ..Self::default()
+            },
+            "CHF" => Self {
+                symbol: "CHF".to_string(),
+                space: true,
+                thousands_separator: "'".to_string(),
+                ..Self::default()
+            },
+            _ => Self {
+                symbol: input.to_string(),
+                ..Self::default()
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Looks up an import profile by name (case-insensitive), preferring the user's own.
+    pub fn import_profile(&self, name: &str) -> Option<ImportProfile> {
+        let name = name.trim();
+        self.import_profiles
+            .iter()
+            .cloned()
+            .chain(ImportProfile::built_in())
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            suggestion_floor: 0.0,
+            suggestion_window_days: 90,
+            suggestion_buffer: 0.1,
+            suggestion_round_step: 10.0,
+            fiscal_year_start_month: 1,
+            excluded_categories: Vec::new(),
+            income_categories: Vec::new(),
+            fixed_categories: Vec::new(),
+            discretionary_categories: Vec::new(),
+            safe_to_spend_basis: SafeToSpendBasis::default(),
+            savings_rate_target: 0.2,
+            audit_log: false,
+            title_case_categories: false,
+            upcoming_bill_days: 14,
+            currency: CurrencyFormat::default(),
+            accrual: false,
+            stale_budget_days: 90,
+            low_balance_threshold: None,
+            velocity_alert_factor: 1.5,
+            import_profiles: Vec::new(),
+            overview_widgets: OverviewWidget::defaults(),
+            watch_ledger: false,
+        }
+    }
+}
+
+/// View toggles remembered between sessions in `ui_state.json` next to the ledger. Unlike
+/// `Config` the app writes this file itself whenever a toggle changes; a missing or older file
+/// falls back to the defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    /// Auto-budget hints on the Budgets tab (`g`).
+    pub show_suggestions: bool,
+    /// Overview budget lines show what is left instead of what has been used (`v`).
+    pub show_remaining: bool,
+    /// The overview's category chart shows subcategories instead of parent totals (`e`).
+    pub expand_subcategories: bool,
+    /// The overview leaves out budgets with nothing spent this month (`z`).
+    pub hide_zero_budgets: bool,
+    /// The overview's category chart leaves out spending no budget covers (`Z`).
+    pub hide_unbudgeted: bool,
+}
+
+impl Default for UiState {
+    fn default() -> Self {
+        Self {
+            show_suggestions: true,
+            show_remaining: false,
+            expand_subcategories: false,
+            hide_zero_budgets: false,
+            hide_unbudgeted: false,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Errors returned by the ledger and storage layers. Callers can match on the kind of
+//! failure (a corrupt ledger versus an unreadable one, a rejected value versus a missing id)
+//! instead of on message text; the binary wraps them in `anyhow` for display.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub type Result<T, E = CentshError> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum CentshError {
+    /// Reading or writing a file failed; `action` says which, e.g. "reading".
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// JSON that could not be parsed or written, e.g. a corrupt ledger file. `what` names the
+    /// operation, e.g. "parsing ledger data".
+    Json {
+        what: &'static str,
+        source: serde_json::Error,
+    },
+    /// Imported text that could not be read as transactions, e.g. a CSV line with a bad date.
+    Parse(String),
+    /// A value that was rejected, e.g. a duplicate account name.
+    Validation(String),
+    /// The transaction or budget asked for does not exist.
+    NotFound(String),
+    /// Saving is not possible right now: the data directory is read-only or the disk is full.
+    NotSaved(String),
+}
+
+impl CentshError {
+    /// For `map_err` on file operations: `action` on `path` failed.
+    pub fn io(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> Self {
+        let path = path.to_path_buf();
+        move |source| CentshError::Io {
+            action,
+            path,
+            source,
+        }
+    }
+
+    /// For `map_err` on serde_json calls.
+    pub fn json(what: &'static str) -> impl FnOnce(serde_json::Error) -> Self {
+        move |source| CentshError::Json { what, source }
+    }
+}
+
+/// Like `anyhow` context, the message describes what failed and `source` holds the cause, so
+/// `{:#}` on a wrapped error prints both.
+impl fmt::Display for CentshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CentshError::Io { action, path, .. } => write!(f, "{action} {path:?}"),
+            CentshError::Json { what, .. } => write!(f, "{what} failed"),
+            CentshError::Parse(message)
+            | CentshError::Validation(message)
+            | CentshError::NotFound(message)
+            | CentshError::NotSaved(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for CentshError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CentshError::Io { source, .. } => Some(source),
+            CentshError::Json { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,509 @@
+//! Parsing of free-form text typed or pasted by the user.
+
+use crate::models::NewTransaction;
+use anyhow::{Context, Result, anyhow};
+use chrono::{Local, NaiveDate};
+
+/// Parses the first non-empty line of pasted text into a transaction.
+pub fn parse_pasted_text(text: &str) -> Result<NewTransaction> {
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .ok_or_else(|| anyhow!("clipboard is empty"))?;
+    parse_pasted_line(line)
+}
+
+/// Several pasted lines, split into the ones that parsed and the ones that didn't.
+pub struct PastedBlock {
+    pub parsed: Vec<NewTransaction>,
+    pub failed: Vec<PasteFailure>,
+}
+
+pub struct PasteFailure {
+    /// 1-based line number in the pasted text.
+    pub line: usize,
+    pub text: String,
+    pub error: String,
+}
+
+/// Parses every non-empty line of pasted text on its own, like `parse_pasted_text`, so one
+/// messy line doesn't stop the rest.
+pub fn parse_pasted_block(text: &str) -> PastedBlock {
+    let mut block = PastedBlock {
+        parsed: Vec::new(),
+        failed: Vec::new(),
+    };
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match parse_pasted_line(line) {
+            Ok(tx) => block.parsed.push(tx),
+            Err(err) => block.failed.push(PasteFailure {
+                line: index + 1,
+                text: line.to_string(),
+                error: err.to_string(),
+            }),
+        }
+    }
+    block
+}
+
+/// A CSV line when it has commas, quick-add text otherwise.
+fn parse_pasted_line(line: &str) -> Result<NewTransaction> {
+    if line.contains(',') {
+        parse_csv_line(line)
+    } else {
+        parse_quick_add(line)
+    }
+}
+
+/// Quick-add text: `[YYYY-MM-DD] description amount [category]`. The last number on the line
+/// is the amount; the date defaults to today and the category to "General".
+pub fn parse_quick_add(line: &str) -> Result<NewTransaction> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let (date, rest) = match tokens.split_first() {
+        Some((first, rest)) => match NaiveDate::parse_from_str(first, "%Y-%m-%d") {
+            Ok(date) => (date, rest),
+            Err(_) => (today(), tokens.as_slice()),
+        },
+        None => return Err(anyhow!("nothing to parse")),
+    };
+
+    let amount_idx = rest
+        .iter()
+        .rposition(|t| parse_money(t).is_ok())
+        .ok_or_else(|| anyhow!("no amount found in {line:?}"))?;
+    let description = rest[..amount_idx].join(" ");
+    if description.is_empty() {
+        return Err(anyhow!("expected a description before the amount"));
+    }
+    let amount = parse_money(rest[amount_idx])?;
+
+    Ok(NewTransaction {
+        description,
+        amount,
+        category: category_or_default(&rest[amount_idx + 1..].join(" ")),
+        date,
+        note: String::new(),
+    })
+}
+
+/// CSV line in export column order: `date,description,amount[,category]`.
+pub fn parse_csv_line(line: &str) -> Result<NewTransaction> {
+    let fields = split_csv_line(line);
+    if fields.len() < 3 {
+        return Err(anyhow!(
+            "expected date,description,amount[,category] but found {} fields",
+            fields.len()
+        ));
+    }
+    let date = NaiveDate::parse_from_str(fields[0].trim(), "%Y-%m-%d")
+        .with_context(|| format!("invalid date {:?} (use YYYY-MM-DD)", fields[0].trim()))?;
+    let description = fields[1].trim().to_string();
+    if description.is_empty() {
+        return Err(anyhow!("description is empty"));
+    }
+    let amount = parse_money(fields[2].trim())?;
+
+    Ok(NewTransaction {
+        description,
+        amount,
+        category: category_or_default(fields.get(3).map(|c| c.trim()).unwrap_or_default()),
+        date,
+        note: String::new(),
+    })
+}
+
+/// Splits one CSV line, honoring double-quoted fields (with `""` as an escaped quote).
+pub fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+/// Currency symbols allowed before or after an amount.
+const CURRENCY_SYMBOLS: &[char] = &['$', '€', '£', '¥', '₹', '₩', '₽', '¢'];
+
+/// Parses an amount as people write it: `12.5`, `-4`, `$1,234.56`, `-$20`, `12 €`, and
+/// accounting-style `($50.00)` for a negative. Commas are thousands separators, except a
+/// last comma followed by one or two digits, which is a decimal comma: `12,50` and
+/// `1.234,56 €` as written with euros.
+pub fn parse_money(input: &str) -> Result<f64> {
+    let invalid = || {
+        anyhow!(
+            "{:?} is not an amount (e.g. 12.50, $1,234.56 or (50.00))",
+            input.trim()
+        )
+    };
+    let mut text = input.trim();
+    let mut negative = false;
+    if let Some(inner) = text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        negative = true;
+        text = inner.trim();
+    }
+    // A sign may come before or after the symbol: `-$5` and `$-5` are both accepted.
+    for _ in 0..2 {
+        if let Some(rest) = text.strip_prefix('-') {
+            negative = !negative;
+            text = rest;
+        } else if let Some(rest) = text.strip_prefix('+') {
+            text = rest;
+        }
+        text = text.trim_matches(|c: char| CURRENCY_SYMBOLS.contains(&c) || c.is_whitespace());
+    }
+    let digits = match text.rsplit_once(',') {
+        Some((whole, fraction))
+            if (1..=2).contains(&fraction.len())
+                && fraction.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            format!("{}.{fraction}", whole.replace(['.', ','], ""))
+        }
+        _ => text.replace(',', ""),
+    };
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return Err(invalid());
+    }
+    let value: f64 = digits.parse().map_err(|_| invalid())?;
+    if !value.is_finite() {
+        return Err(invalid());
+    }
+    Ok(if negative { -value } else { value })
+}
+
+/// An amount optionally followed by a percentage adjustment, e.g. `120 +10%` (132) or
+/// `$80-15%` (68). Without a trailing `%` token this is `parse_money`.
+pub fn parse_adjusted_money(input: &str) -> Result<f64> {
+    let text = input.trim();
+    let Some(without_percent) = text.strip_suffix('%') else {
+        return parse_money(text);
+    };
+    let sign_at = without_percent
+        .rfind(['+', '-'])
+        .ok_or_else(|| anyhow!("write a percentage adjustment as +10% or -15%"))?;
+    let (base, percent) = without_percent.split_at(sign_at);
+    if base.trim().is_empty() {
+        return Err(anyhow!(
+            "put the amount to adjust before {text:?}, e.g. 120 {text}"
+        ));
+    }
+    let percent: f64 = percent
+        .replace(' ', "")
+        .parse()
+        .map_err(|_| anyhow!("{:?} is not a percentage", &percent[1..]))?;
+    Ok(parse_money(base)? * (1.0 + percent / 100.0))
+}
+
+/// Budgets to try out, written `Category=limit` and separated by semicolons, e.g.
+/// `Food=400; Rent=$1,200`.
+pub fn parse_budget_list(input: &str) -> Result<Vec<(String, f64)>> {
+    input
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (category, limit) = entry
+                .rsplit_once('=')
+                .ok_or_else(|| anyhow!("write each budget as Category=limit, not {entry:?}"))?;
+            let category = category.trim();
+            if category.is_empty() {
+                return Err(anyhow!("missing category in {entry:?}"));
+            }
+            Ok((category.to_string(), parse_money(limit)?))
+        })
+        .collect()
+}
+
+/// The "my share" percentage of a shared expense, e.g. `60` or `60%`. Blank means the whole
+/// amount is mine.
+pub fn parse_share_pct(input: &str) -> Result<Option<f64>> {
+    let text = input.trim().trim_end_matches('%').trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let pct: f64 = text
+        .parse()
+        .map_err(|_| anyhow!("{input:?} is not a percentage"))?;
+    check_share_pct(pct)?;
+    Ok(Some(pct))
+}
+
+/// My part of a shared `amount` when I pay `pct` percent of it. Fails unless `pct` is more
+/// than 0 and at most 100.
+pub fn apply_share(amount: f64, pct: f64) -> Result<f64> {
+    check_share_pct(pct)?;
+    Ok(amount * pct / 100.0)
+}
+
+fn check_share_pct(pct: f64) -> Result<()> {
+    if !(pct > 0.0 && pct <= 100.0) {
+        return Err(anyhow!("share must be more than 0% and at most 100%"));
+    }
+    Ok(())
+}
+
+/// A date as typed in forms: `YYYY-MM-DD`, blank for today, or an offset from today such as
+/// `-3d` (three days ago), `-2w` (two weeks ago) or `+10d` (ten days from now).
+pub fn parse_date_flexible(input: &str) -> Result<NaiveDate> {
+    let text = input.trim();
+    if text.is_empty() {
+        return Ok(today());
+    }
+    if let Some(date) = parse_date_offset(text) {
+        return date;
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .with_context(|| format!("invalid date {text:?} (use YYYY-MM-DD, -3d, -2w or +10d)"))
+}
+
+/// `±N` followed by `d` or `w`; `None` when `text` is not shaped like an offset.
+fn parse_date_offset(text: &str) -> Option<Result<NaiveDate>> {
+    let (sign, rest) = match text.as_bytes().first()? {
+        b'-' => (-1, &text[1..]),
+        b'+' => (1, &text[1..]),
+        _ => return None,
+    };
+    let (count, unit_days) = if let Some(n) = rest.strip_suffix(['d', 'D']) {
+        (n, 1)
+    } else if let Some(n) = rest.strip_suffix(['w', 'W']) {
+        (n, 7)
+    } else {
+        return None;
+    };
+    let count: i64 = count.trim().parse().ok()?;
+    let date = count
+        .checked_mul(unit_days * sign)
+        .and_then(chrono::Duration::try_days)
+        .and_then(|offset| today().checked_add_signed(offset))
+        .ok_or_else(|| anyhow!("{text:?} is too far from today"));
+    Some(date)
+}
+
+/// Evaluates arithmetic such as `12.50 + 3*4 - (20 / 4)`: `+ - * /`, parentheses and unary
+/// minus with the usual precedence. `$` signs and spaces are ignored.
+pub fn evaluate_expression(input: &str) -> Result<f64> {
+    let tokens: Vec<char> = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '$')
+        .collect();
+    if tokens.is_empty() {
+        return Err(anyhow!("nothing to calculate"));
+    }
+    let mut calc = Calc { tokens, pos: 0 };
+    let value = calc.sum()?;
+    match calc.tokens.get(calc.pos) {
+        Some(c) => Err(anyhow!("unexpected {c:?}")),
+        None if value.is_finite() => Ok(value),
+        None => Err(anyhow!("result is not a number")),
+    }
+}
+
+/// Recursive-descent state for `evaluate_expression`.
+struct Calc {
+    tokens: Vec<char>,
+    pos: usize,
+}
+
+impl Calc {
+    fn peek(&self) -> Option<char> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    /// Terms joined by `+` and `-`.
+    fn sum(&mut self) -> Result<f64> {
+        let mut value = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.product()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    /// Factors joined by `*` and `/`.
+    fn product(&mut self) -> Result<f64> {
+        let mut value = self.factor()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.factor()?;
+            if op == '/' && rhs == 0.0 {
+                return Err(anyhow!("division by zero"));
+            }
+            value = if op == '*' { value * rhs } else { value / rhs };
+        }
+        Ok(value)
+    }
+
+    /// A number, a negated factor or a parenthesized sum.
+    fn factor(&mut self) -> Result<f64> {
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                Ok(-self.factor()?)
+            }
+            Some('(') => {
+                self.pos += 1;
+                let value = self.sum()?;
+                if self.peek() != Some(')') {
+                    return Err(anyhow!("missing )"));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.pos += 1;
+                }
+                let number: String = self.tokens[start..self.pos].iter().collect();
+                number
+                    .parse()
+                    .map_err(|_| anyhow!("{number:?} is not a number"))
+            }
+            Some(c) => Err(anyhow!("unexpected {c:?}")),
+            None => Err(anyhow!("expression ends early")),
+        }
+    }
+}
+
+pub fn category_or_default(category: &str) -> String {
+    if category.is_empty() {
+        "General".to_string()
+    } else {
+        category.to_string()
+    }
+}
+
+fn today() -> NaiveDate {
+    Local::now().naive_local().date()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn parse_money_accepts_plain_numbers() {
+        assert_eq!(parse_money("12.5").unwrap(), 12.5);
+        assert_eq!(parse_money("  -4 ").unwrap(), -4.0);
+        assert_eq!(parse_money("+7").unwrap(), 7.0);
+        assert_eq!(parse_money(".99").unwrap(), 0.99);
+    }
+
+    #[test]
+    fn parse_money_strips_symbols_and_thousands_separators() {
+        assert_eq!(parse_money("$1,234.56").unwrap(), 1234.56);
+        assert_eq!(parse_money("-$20").unwrap(), -20.0);
+        assert_eq!(parse_money("$-20").unwrap(), -20.0);
+        assert_eq!(parse_money("12 €").unwrap(), 12.0);
+        assert_eq!(parse_money("£1,000,000").unwrap(), 1_000_000.0);
+    }
+
+    #[test]
+    fn parse_money_reads_accounting_negatives() {
+        assert_eq!(parse_money("($50.00)").unwrap(), -50.0);
+        assert_eq!(parse_money("(1,234.50)").unwrap(), -1234.5);
+    }
+
+    #[test]
+    fn parse_money_reads_decimal_commas() {
+        assert_eq!(parse_money("12,50").unwrap(), 12.5);
+        assert_eq!(parse_money("1.234,56 €").unwrap(), 1234.56);
+        assert_eq!(parse_money("1,234").unwrap(), 1234.0);
+    }
+
+    #[test]
+    fn parse_money_rejects_text() {
+        for input in ["", "abc", "$", "12abc", "twelve", "--"] {
+            let err = parse_money(input).unwrap_err().to_string();
+            assert!(err.contains("is not an amount"), "{input:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn apply_share_takes_a_percentage_of_the_amount() {
+        assert_eq!(apply_share(200.0, 60.0).unwrap(), 120.0);
+        assert_eq!(apply_share(200.0, 100.0).unwrap(), 200.0);
+        assert_eq!(apply_share(-50.0, 50.0).unwrap(), -25.0);
+        assert_eq!(apply_share(0.0, 0.5).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn apply_share_rejects_percentages_outside_0_to_100() {
+        for pct in [0.0, -10.0, 100.01, 250.0, f64::NAN, f64::INFINITY] {
+            let err = apply_share(200.0, pct).unwrap_err().to_string();
+            assert!(err.contains("at most 100%"), "{pct}: {err}");
+        }
+        assert!(parse_share_pct("250%").is_err());
+        assert_eq!(parse_share_pct(" 60% ").unwrap(), Some(60.0));
+        assert_eq!(parse_share_pct("").unwrap(), None);
+    }
+
+    #[test]
+    fn parse_date_flexible_reads_day_and_week_offsets() {
+        let now = today();
+        assert_eq!(parse_date_flexible("-3d").unwrap(), now - Duration::days(3));
+        assert_eq!(
+            parse_date_flexible("-2w").unwrap(),
+            now - Duration::days(14)
+        );
+        assert_eq!(
+            parse_date_flexible(" +10D ").unwrap(),
+            now + Duration::days(10)
+        );
+        assert_eq!(parse_date_flexible("+1W").unwrap(), now + Duration::days(7));
+    }
+
+    #[test]
+    fn parse_date_flexible_treats_zero_offsets_and_blank_as_today() {
+        let now = today();
+        for input in ["", "-0d", "+0d", "-0w", "+0w"] {
+            assert_eq!(parse_date_flexible(input).unwrap(), now, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn parse_date_flexible_handles_large_offsets() {
+        let now = today();
+        assert_eq!(
+            parse_date_flexible("-3650d").unwrap(),
+            now - Duration::days(3650)
+        );
+        assert_eq!(
+            parse_date_flexible("+520w").unwrap(),
+            now + Duration::days(3640)
+        );
+        for input in ["-99999999999d", "+9999999999999w", "-9223372036854775807w"] {
+            let err = parse_date_flexible(input).unwrap_err().to_string();
+            assert!(err.contains("too far from today"), "{input:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn parse_date_flexible_rejects_malformed_offsets() {
+        assert_eq!(
+            parse_date_flexible("2024-03-05").unwrap().to_string(),
+            "2024-03-05"
+        );
+        for input in ["-d", "3d", "-3x", "-3.5d", "tomorrow"] {
+            assert!(parse_date_flexible(input).is_err(), "{input:?}");
+        }
+    }
+}
@@ -1,14 +1,24 @@
+mod clipboard;
+mod config;
+mod error;
+mod input;
 mod models;
 mod storage;
 
-use crate::models::Ledger;
-use crate::storage::Storage;
+use crate::config::{Config, CurrencyFormat, OverviewWidget, UiState};
+use crate::input::PastedBlock;
+use crate::models::{
+    ARCHIVED_READ_ONLY, Account, Annotation, Budget, BudgetDirection, BudgetPeriod,
+    BudgetSuggestion, DateRange, Ledger, MonthBudgetResult, NewTransaction, Reconciliation,
+    SpendingSplit, Transaction, TxKind, category_parent, fiscal_year_start, normalize_category,
+};
+use crate::storage::{DataSource, ExportFormat, ExportPeriod, ExportScope, LEDGER_ENV, Storage};
 use anyhow::{Context, Result, anyhow};
-use chrono::{Local, NaiveDate};
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
 use crossterm::ExecutableCommand;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use crossterm::terminal::{
-    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+    self, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
 use ratatui::Terminal;
 use ratatui::backend::CrosstermBackend;
@@ -17,13 +27,135 @@ use ratatui::style::{Color, Modifier, Style};
 use ratatui::symbols;
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{
-    BarChart, Block, Borders, Cell, Chart, Dataset, Paragraph, Row, Table, Tabs, Wrap,
+    Bar, BarChart, BarGroup, Block, Borders, Cell, Chart, Clear, Dataset, Gauge, GraphType,
+    LineGauge, Paragraph, Row, Sparkline, Table, TableState, Tabs, Wrap,
 };
+use serde_json::json;
 use std::io::{Stdout, stdout};
+use std::path::Path;
 use std::time::{Duration, Instant};
 
+const TAB_TITLES: [&str; 9] = [
+    "Overview",
+    "Transactions",
+    "Budgets",
+    "Year",
+    "Trash",
+    "Templates",
+    "Compare",
+    "Envelopes",
+    "Trends",
+];
+
+/// Recent categories offered first when cycling the category field.
+const RECENT_CATEGORIES: usize = 5;
+
+/// Changes Ctrl+Z can take back.
+const UNDO_LEVELS: usize = 20;
+
+/// Complete months behind the overview's average-spend baseline.
+const AVERAGE_SPEND_MONTHS: usize = 6;
+/// Largest expenses listed on the overview.
+const TOP_EXPENSES: usize = 5;
+
+/// Recent days compared with the month's daily average for the spending velocity alert.
+const VELOCITY_WINDOW_DAYS: u32 = 7;
+
+/// Line numbers an import summary lists after the first skipped line, before "and N more".
+const SKIPPED_LINES_LISTED: usize = 5;
+
+/// Share of a spending cap used from which it is shown as close to its limit (yellow).
+const NEAR_LIMIT: f64 = 0.8;
+
+/// Keys available on every tab, as shown in the footer.
+const GLOBAL_KEYS: &[(&str, &str)] = &[
+    ("q", "quit"),
+    ("a", "add txn"),
+    ("i", "add income"),
+    ("p", "paste txn"),
+    ("b", "add budget"),
+    ("h/l 1-9", "tabs"),
+    ("/", "search"),
+    ("o", "account"),
+    ("N", "new account"),
+    ("x", "export"),
+    ("L", "account register"),
+    ("T", "business export"),
+    ("Y/y", "archive year/load archives"),
+    ("m", "import"),
+    ("W", "profile"),
+    ("H", "report"),
+    ("B", "balance"),
+    ("C", "reconcile"),
+    ("n", "annotate"),
+    ("s", "save"),
+    ("^Z/^Y", "undo/redo"),
+    ("g", "auto-budget hints"),
+    ("=", "calculator"),
+    ("r", "reload"),
+];
+
+/// Keys that only act on one tab, indexed like `TAB_TITLES`.
+const TAB_KEYS: [&[(&str, &str)]; 9] = [
+    &[
+        ("Tab", "focus panel"),
+        ("j/k", "select"),
+        ("Enter", "open"),
+        ("v", "used/left"),
+        ("e", "subcategories"),
+        ("z/Z", "hide idle budgets/unbudgeted"),
+        ("D", "dates"),
+    ],
+    &[
+        ("j/k", "select"),
+        ("I/E", "income/expenses only"),
+        ("e", "edit"),
+        ("d", "delete"),
+        ("t", "save template"),
+        ("R", "link refund"),
+        ("A", "amortize"),
+        ("c", "cleared"),
+        ("w", "business"),
+        ("f", "copy row"),
+    ],
+    &[
+        ("j/k", "select"),
+        ("Enter", "edit"),
+        ("d", "delete"),
+        ("J/K", "move"),
+        ("P", "pin"),
+        ("A", "apply suggestions"),
+        ("O", "apply + overwrite"),
+        ("w", "what-if"),
+        ("[ ]", "window"),
+        ("- +", "buffer"),
+    ],
+    &[],
+    &[("j/k", "select"), ("u", "restore"), ("X", "empty trash")],
+    &[
+        ("j/k", "select"),
+        ("Enter", "use"),
+        ("e", "edit"),
+        ("d", "delete"),
+    ],
+    &[("[ ]", "month A"), ("{ }", "month B")],
+    &[("j/k", "scroll")],
+    &[("S", "sort")],
+];
+
+/// Lines of detail a confirmation can show between its question and the y/n hint.
+const CONFIRM_DETAIL_LINES: usize = 3;
+
+/// Default number of past months a what-if budget run is checked against.
+const SIMULATION_MONTHS: usize = 6;
+
+/// Size of one budget card on the Envelopes tab, borders included.
+const ENVELOPE_WIDTH: u16 = 30;
+const ENVELOPE_HEIGHT: u16 = 6;
+
 fn main() -> Result<()> {
-    let mut app = App::new()?;
+    let profile = profile_arg()?;
+    let mut app = App::new(profile.as_deref())?;
     let res = run(&mut app);
     if let Err(err) = res {
         eprintln!("Application error: {err:?}");
@@ -32,6 +164,24 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// The only command-line option: `--profile NAME` (or `--profile=NAME`) picks a named ledger.
+fn profile_arg() -> Result<Option<String>> {
+    let mut args = std::env::args().skip(1);
+    let mut profile = None;
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            profile = Some(args.next().context("--profile needs a name")?);
+        } else if let Some(name) = arg.strip_prefix("--profile=") {
+            profile = Some(name.to_string());
+        } else {
+            return Err(anyhow!(
+                "unknown argument {arg:?} (usage: centsh [--profile NAME])"
+            ));
+        }
+    }
+    Ok(profile)
+}
+
 fn run(app: &mut App) -> Result<()> {
     enable_raw_mode()?;
     let mut stdout = stdout();
@@ -56,616 +206,4814 @@ fn run(app: &mut App) -> Result<()> {
 struct App {
     ledger: Ledger,
     storage: Storage,
+    config: Config,
     active_tab: usize,
     form: ActiveForm,
     show_suggestions: bool,
+    filter: String,
+    /// Income-only or expense-only view of the Transactions table, combined with `filter`.
+    kind_filter: Option<TxKind>,
+    /// Selected row of the Transactions table and the first row on screen, moved together
+    /// by `select_tx` so the table only scrolls when the selection would leave the window.
+    tx_table: TableState,
+    selected_budget: usize,
+    selected_archived: usize,
+    selected_template: usize,
+    /// Refund waiting for its original purchase to be picked in the Transactions table.
+    pending_refund: Option<u64>,
+    /// Auto-budget window and buffer, starting from the config and tweaked live.
+    suggestion_window_days: u32,
+    suggestion_buffer: f64,
+    /// Statement balance and date being reconciled against, while reconciling.
+    reconcile: Option<(f64, NaiveDate)>,
+    /// Categories entered this session, most recent first.
+    recent_categories: Vec<String>,
+    /// First days of the two months shown side by side on the Compare tab.
+    compare_a: NaiveDate,
+    compare_b: NaiveDate,
+    /// Column the Trends table is sorted by; `S` cycles it.
+    trend_sort: TrendSort,
+    /// Overview panel taking j/k and Enter (Tab cycles), with the selected row in it.
+    overview_focus: Option<OverviewFocus>,
+    overview_index: usize,
+    /// Account new transactions are recorded against; `o` cycles through them.
+    current_account: Option<u64>,
+    /// Expression in the calculator popup while it is open.
+    calculator: Option<String>,
+    /// Transactions from a multi-line paste waiting for confirmation.
+    pasted: Option<PastedBlock>,
+    /// Result of the last what-if run, shown on the Budgets tab until Esc.
+    simulation: Option<Vec<MonthBudgetResult>>,
+    /// Overview display filters: budget lines with nothing spent this month, and chart
+    /// categories without a budget.
+    hide_zero_budgets: bool,
+    hide_unbudgeted: bool,
+    /// Days the overview's totals and category chart cover; `None` follows the current month.
+    overview_range: Option<DateRange>,
+    /// Category chart splits parent categories into their subcategories.
+    expand_subcategories: bool,
+    /// First row of cards shown on the Envelopes tab.
+    envelope_scroll: usize,
+    /// Overview budget lines show what is left instead of what has been used.
+    show_remaining: bool,
     last_message: String,
     last_save: Option<Instant>,
+    /// In-memory changes a save held back because the file changed on disk.
+    unsaved: bool,
+    /// Ledgers from before each saved change, newest last, named by the recorded action;
+    /// Ctrl+Z restores them and Ctrl+Y moves forward again through `redo`.
+    undo: Vec<(String, Ledger)>,
+    redo: Vec<(String, Ledger)>,
+    /// The ledger as of the last save: what a change saved next is undone to.
+    checkpoint: Ledger,
+    /// First action recorded since the last save, naming the next undo step.
+    pending_action: Option<String>,
 }
 
 impl App {
-    fn new() -> Result<Self> {
-        let storage = Storage::new()?;
-        let ledger = storage.load()?;
+    fn new(profile: Option<&str>) -> Result<Self> {
+        Self::with_storage(Storage::new(profile)?)
+    }
+
+    /// Loads the ledger from `storage`, or starts the setup wizard when there is none yet.
+    fn with_storage(storage: Storage) -> Result<Self> {
+        let first_run = !storage.ledger_exists();
+        let config = storage.load_config()?;
+        let ui_state = storage.load_ui_state();
+        let (mut ledger, form) = if first_run {
+            (Ledger::empty(), ActiveForm::Wizard(Box::new(Wizard::new())))
+        } else {
+            (storage.load()?, ActiveForm::None)
+        };
+        let cleanup = ledger.normalize_categories(config.title_case_categories);
+        apply_config(&mut ledger, &config);
+        let mut last_message = match storage.source() {
+            _ if first_run => "Welcome to centsh".to_string(),
+            DataSource::DataDir => "Loaded data".to_string(),
+            source => format!("Loaded {} ({source})", storage.path().display()),
+        };
+        if cleanup.changed > 0 {
+            last_message.push_str(&format!(", tidied {} categories", cleanup.changed));
+        }
+        if !cleanup.merged_budgets.is_empty() {
+            last_message.push_str(&format!(
+                ", merged duplicate budgets for {}",
+                cleanup.merged_budgets.join(", ")
+            ));
+        }
+        if !storage.writable() {
+            last_message = format!(
+                "Read-only: data dir not writable — changes won't persist; set {LEDGER_ENV} to a writable file"
+            );
+        }
+        let this_month = Local::now()
+            .naive_local()
+            .date()
+            .with_day(1)
+            .unwrap_or_default();
         Ok(Self {
+            checkpoint: ledger.clone(),
             ledger,
             storage,
+            suggestion_window_days: config.suggestion_window_days,
+            suggestion_buffer: config.suggestion_buffer,
+            config,
+            reconcile: None,
+            recent_categories: Vec::new(),
+            compare_a: this_month - Months::new(1),
+            compare_b: this_month,
+            trend_sort: TrendSort::Total,
+            overview_focus: None,
+            overview_index: 0,
+            current_account: None,
+            show_remaining: ui_state.show_remaining,
+            overview_range: None,
+            expand_subcategories: ui_state.expand_subcategories,
+            hide_zero_budgets: ui_state.hide_zero_budgets,
+            hide_unbudgeted: ui_state.hide_unbudgeted,
+            simulation: None,
+            pasted: None,
+            calculator: None,
+            envelope_scroll: 0,
             active_tab: 0,
-            form: ActiveForm::None,
-            show_suggestions: true,
-            last_message: "Loaded data".to_string(),
+            form,
+            show_suggestions: ui_state.show_suggestions,
+            filter: String::new(),
+            kind_filter: None,
+            tx_table: TableState::default().with_selected(Some(0)),
+            selected_budget: 0,
+            selected_archived: 0,
+            selected_template: 0,
+            pending_refund: None,
+            last_message,
             last_save: None,
+            unsaved: false,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            pending_action: None,
         })
     }
 
+    /// Remembers the view toggles for the next session. A failure only costs the preference,
+    /// so it is reported in the footer rather than returned.
+    fn save_ui_state(&mut self) {
+        let state = UiState {
+            show_suggestions: self.show_suggestions,
+            show_remaining: self.show_remaining,
+            expand_subcategories: self.expand_subcategories,
+            hide_zero_budgets: self.hide_zero_budgets,
+            hide_unbudgeted: self.hide_unbudgeted,
+        };
+        if let Err(err) = self
+            .storage
+            .save_ui_state(&state)
+            .map_err(anyhow::Error::from)
+        {
+            self.last_message = format!("View settings not saved: {err:#}");
+        }
+    }
+
+    /// Saves the ledger. A failure is also shown in the footer, so callers may ignore it.
+    /// With `watch_ledger`, a file changed by something else is not overwritten; the reload
+    /// question decides which version wins.
     fn save(&mut self) -> Result<()> {
-        self.storage
-            .save(&self.ledger)
-            .context("saving ledger failed")?;
+        let before = std::mem::replace(&mut self.checkpoint, self.ledger.clone());
+        if let Some(action) = self.pending_action.take() {
+            self.undo.push((action, before));
+            if self.undo.len() > UNDO_LEVELS {
+                self.undo.remove(0);
+            }
+            self.redo.clear();
+        }
+        if self.config.watch_ledger && self.storage.file_changed_since_load() {
+            self.unsaved = true;
+            self.last_message = "Not saved: the ledger changed on disk".into();
+            return Err(anyhow!("ledger changed on disk since it was loaded"));
+        }
+        if let Err(err) = self.storage.save(&self.ledger) {
+            let err = anyhow::Error::from(err);
+            self.last_message = format!("Not saved: {err:#}");
+            return Err(err.context("saving ledger failed"));
+        }
         self.last_save = Some(Instant::now());
+        self.unsaved = false;
         self.last_message = format!("Saved to {}", self.storage.path().display());
         Ok(())
     }
-}
-
-enum ActiveForm {
-    None,
-    Transaction(TxForm),
-    Budget(BudgetForm),
-}
-
-#[derive(Clone)]
-struct Field {
-    label: &'static str,
-    value: String,
-}
 
-struct TxForm {
-    fields: Vec<Field>,
-    index: usize,
-}
+    /// Replaces the in-memory ledger with the file's contents, dropping unsaved changes.
+    fn reload(&mut self) -> Result<()> {
+        self.ledger = self.storage.load()?;
+        self.ledger
+            .normalize_categories(self.config.title_case_categories);
+        apply_config(&mut self.ledger, &self.config);
+        self.unsaved = false;
+        self.forget_undo();
+        Ok(())
+    }
 
-impl TxForm {
-    fn new() -> Self {
-        let today = Local::now().naive_local().date();
-        Self {
-            fields: vec![
-                Field {
-                    label: "Description",
-                    value: String::new(),
-                },
-                Field {
-                    label: "Amount (+out / -in)",
-                    value: String::new(),
-                },
-                Field {
-                    label: "Category",
-                    value: String::from("General"),
-                },
-                Field {
-                    label: "Date (YYYY-MM-DD)",
-                    value: today.to_string(),
-                },
-            ],
-            index: 0,
+    /// Swaps to another named ledger (`None` for the default one), starting a new profile
+    /// empty. Refused while a held-back save would be lost. Views, selections and undo start
+    /// over; the config is shared between profiles.
+    fn switch_profile(&mut self, profile: Option<&str>) -> Result<String> {
+        if self.unsaved {
+            return Err(anyhow!(
+                "Unsaved changes: save (s) or reload (r) before switching profiles"
+            ));
         }
+        let storage = Storage::new(profile)?;
+        let existed = storage.ledger_exists();
+        let mut ledger = if existed {
+            storage.load()?
+        } else {
+            Ledger::empty()
+        };
+        ledger.normalize_categories(self.config.title_case_categories);
+        apply_config(&mut ledger, &self.config);
+        self.storage = storage;
+        self.ledger = ledger;
+        self.forget_undo();
+        self.active_tab = 0;
+        self.filter.clear();
+        self.kind_filter = None;
+        self.tx_table = TableState::default().with_selected(Some(0));
+        self.selected_budget = 0;
+        self.selected_archived = 0;
+        self.selected_template = 0;
+        self.pending_refund = None;
+        self.reconcile = None;
+        self.overview_focus = None;
+        self.overview_index = 0;
+        self.overview_range = None;
+        self.current_account = None;
+        self.calculator = None;
+        self.pasted = None;
+        self.simulation = None;
+        self.envelope_scroll = 0;
+        let name = self.storage.profile().unwrap_or("default");
+        Ok(if existed {
+            format!("Switched to the {name} profile")
+        } else {
+            format!(
+                "New {name} profile; {} is created on the first save",
+                self.storage.path().display()
+            )
+        })
     }
 
-    fn current_mut(&mut self) -> &mut Field {
-        &mut self.fields[self.index]
+    /// Starts undo over from the ledger as it is now, for changes that can't be taken back
+    /// by swapping the ledger (a reload, an archived year already written to its own file).
+    fn forget_undo(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.checkpoint = self.ledger.clone();
+        self.pending_action = None;
     }
 
-    fn next(&mut self) {
-        if self.index + 1 < self.fields.len() {
-            self.index += 1;
+    /// Ctrl+Z (or with `redo`, Ctrl+Y): swaps in the ledger from before the last saved
+    /// change, keeping the current one for the other direction, and saves.
+    fn undo_step(&mut self, redo: bool) {
+        let (from, to) = if redo {
+            (&mut self.redo, &mut self.undo)
+        } else {
+            (&mut self.undo, &mut self.redo)
+        };
+        let Some((action, ledger)) = from.pop() else {
+            self.last_message = if redo {
+                "Nothing to redo".into()
+            } else {
+                "Nothing to undo".into()
+            };
+            return;
+        };
+        to.push((action.clone(), std::mem::replace(&mut self.ledger, ledger)));
+        self.checkpoint = self.ledger.clone();
+        self.pending_action = None;
+        let last = self.ledger.budgets.len().saturating_sub(1);
+        self.selected_budget = self.selected_budget.min(last);
+        let last = self.ledger.archived.len().saturating_sub(1);
+        self.selected_archived = self.selected_archived.min(last);
+        let last = self.ledger.templates.len().saturating_sub(1);
+        self.selected_template = self.selected_template.min(last);
+        self.log_action(
+            if redo { "redo" } else { "undo" },
+            json!({ "action": action }),
+        );
+        let saved = self.save().is_ok();
+        let verb = if redo { "Redid" } else { "Undid" };
+        let action = action.replace('_', " ");
+        if saved {
+            self.last_message = format!("{verb} {action}");
+        } else {
+            self.last_message = format!("{verb} {action} ({})", self.last_message);
         }
     }
 
-    fn prev(&mut self) {
-        if self.index > 0 {
-            self.index -= 1;
+    /// Answers "no" to reloading a changed file: this version wins, and is written out now
+    /// if it has changes the file lacks.
+    fn keep_in_memory_ledger(&mut self) -> String {
+        self.storage.mark_in_sync();
+        if !self.unsaved {
+            return "Kept this version; the next save overwrites the file".into();
+        }
+        match self.save() {
+            Ok(()) => "Kept this version and saved it over the file".into(),
+            Err(_) => self.last_message.clone(),
         }
     }
 
-    fn push_char(&mut self, c: char) {
-        self.current_mut().value.push(c);
+    /// With `watch_ledger`, asks about reloading once the file changes on disk. Waits while
+    /// a form is open so typing is not interrupted.
+    fn check_external_change(&mut self) {
+        if self.config.watch_ledger
+            && matches!(self.form, ActiveForm::None)
+            && self.calculator.is_none()
+            && self.storage.file_changed_since_load()
+        {
+            self.form = ActiveForm::Confirm(ConfirmAction::ReloadChanged);
+        }
     }
 
-    fn backspace(&mut self) {
-        self.current_mut().value.pop();
+    /// Transactions visible in the table, after the search and income/expense filters.
+    fn visible_transactions(&self) -> Vec<&Transaction> {
+        self.ledger
+            .transactions_by_kind(&self.filter, self.kind_filter)
     }
 
-    fn try_submit(&self) -> Result<NewTransaction> {
-        let description = self.fields[0].value.trim();
-        let amount_str = self.fields[1].value.trim();
-        let category = self.fields[2].value.trim();
-        let date_str = self.fields[3].value.trim();
+    fn selected_transaction(&self) -> Option<&Transaction> {
+        self.visible_transactions().get(self.selected_tx()).copied()
+    }
 
-        if description.is_empty() {
-            return Err(anyhow!("Description is required"));
+    fn selected_tx(&self) -> usize {
+        self.tx_table.selected().unwrap_or(0)
+    }
+
+    /// Selects row `index` of the Transactions table (clamped to the rows there are) and
+    /// scrolls just enough to keep it on screen.
+    fn select_tx(&mut self, index: usize) {
+        let index = index.min(self.visible_transactions().len().saturating_sub(1));
+        let page = transactions_page(self);
+        let mut offset = self.tx_table.offset();
+        if index < offset {
+            offset = index;
+        } else if index >= offset + page {
+            offset = index + 1 - page;
         }
-        if amount_str.is_empty() {
-            return Err(anyhow!("Amount is required"));
+        *self.tx_table.offset_mut() = offset;
+        self.tx_table.select(Some(index));
+    }
+
+    /// Starts linking the selected transaction as a refund, or finishes the link when a
+    /// refund is already pending and the original purchase is selected.
+    fn link_refund_step(&mut self) {
+        let Some(selected) = self.selected_transaction().map(|t| t.id) else {
+            return;
+        };
+        match self.pending_refund.take() {
+            None if self.ledger.is_from_archive(selected) => {
+                self.last_message = ARCHIVED_READ_ONLY.into();
+            }
+            None => {
+                self.pending_refund = Some(selected);
+                self.last_message =
+                    "Select the original purchase and press R to link (Esc cancels)".into();
+            }
+            Some(refund_id) => match self.ledger.link_refund(refund_id, selected) {
+                Ok(()) => {
+                    self.record(
+                        "link_refund",
+                        json!({ "refund_id": refund_id, "original_id": selected }),
+                    );
+                    self.last_message = "Refund linked".into();
+                    self.save().ok();
+                }
+                Err(err) => self.last_message = err.to_string(),
+            },
         }
-        let amount: f64 = amount_str
-            .parse()
-            .context("Amount must be a number (use negative for income)")?;
-        let date = if date_str.is_empty() {
-            Local::now().naive_local().date()
-        } else {
-            NaiveDate::parse_from_str(date_str, "%Y-%m-%d").context("Date must be YYYY-MM-DD")?
+    }
+
+    /// Steps the current account through none and then each account in order.
+    fn cycle_account(&mut self) {
+        let ids: Vec<u64> = self.ledger.accounts.iter().map(|a| a.id).collect();
+        let next = match self.current_account {
+            None => ids.first().copied(),
+            Some(id) => ids
+                .iter()
+                .position(|a| *a == id)
+                .and_then(|i| ids.get(i + 1).copied()),
         };
+        self.current_account = next;
+        self.last_message = match next.and_then(|id| self.ledger.account(id)) {
+            Some(account) => format!("Recording to {}", account.name),
+            None if ids.is_empty() => "No accounts yet. Press N to add one.".into(),
+            None => "Recording without an account".into(),
+        };
+    }
 
-        Ok(NewTransaction {
-            description: description.to_string(),
-            amount,
-            category: if category.is_empty() {
-                "General".to_string()
-            } else {
-                category.to_string()
-            },
-            date,
-        })
+    /// Budgets listed on the overview: all of them, or with `hide_zero_budgets` only those
+    /// with something spent (or saved) this month.
+    fn overview_budgets(&self) -> Vec<&Budget> {
+        let spending = self.ledger.category_spending_current_month();
+        // Spending on the budget's category or one of its subcategories, as budgets count it.
+        let spent = |budget: &Budget| {
+            spending.iter().any(|(category, amount)| {
+                *amount != 0.0
+                    && (*category == budget.category
+                        || category_parent(category) == budget.category)
+            })
+        };
+        self.ledger
+            .budgets
+            .iter()
+            .filter(|b| !self.hide_zero_budgets || spent(b))
+            .collect()
     }
-}
 
-struct BudgetForm {
-    fields: Vec<Field>,
-    index: usize,
-}
+    /// Rows in the focused overview panel.
+    fn overview_rows(&self) -> usize {
+        match self.overview_focus {
+            None => 0,
+            Some(OverviewFocus::Budgets) => self.overview_budgets().len(),
+            Some(OverviewFocus::TopExpenses) => self
+                .ledger
+                .top_expenses(Local::now().naive_local().date(), TOP_EXPENSES)
+                .len(),
+        }
+    }
 
-impl BudgetForm {
-    fn new() -> Self {
-        Self {
-            fields: vec![
-                Field {
-                    label: "Category",
-                    value: "General".to_string(),
-                },
-                Field {
-                    label: "Monthly limit",
-                    value: String::new(),
-                },
-            ],
-            index: 0,
+    /// Enter on the overview: a budget opens the Transactions tab searched to its category,
+    /// a top expense opens it with that transaction selected.
+    fn open_overview_selection(&mut self) {
+        match self.overview_focus {
+            None => {}
+            Some(OverviewFocus::Budgets) => {
+                let Some(category) = self
+                    .overview_budgets()
+                    .get(self.overview_index)
+                    .map(|b| b.category.clone())
+                else {
+                    return;
+                };
+                self.filter = category_search(&category);
+                self.kind_filter = None;
+                self.select_tx(0);
+                self.active_tab = 1;
+            }
+            Some(OverviewFocus::TopExpenses) => {
+                let today = Local::now().naive_local().date();
+                let Some(tx) = self
+                    .ledger
+                    .top_expenses(today, TOP_EXPENSES)
+                    .get(self.overview_index)
+                    .map(|tx| (tx.id, tx.description.clone()))
+                else {
+                    return;
+                };
+                self.kind_filter = None;
+                self.filter.clear();
+                let index = self
+                    .visible_transactions()
+                    .iter()
+                    .position(|t| t.id == tx.0)
+                    .unwrap_or(0);
+                self.select_tx(index);
+                self.active_tab = 1;
+            }
         }
     }
 
-    fn current_mut(&mut self) -> &mut Field {
-        &mut self.fields[self.index]
+    /// Auto-budget suggestions with the current window and buffer.
+    fn current_suggestions(&self) -> Vec<BudgetSuggestion> {
+        self.ledger.suggested_budgets(
+            self.config.suggestion_floor,
+            self.suggestion_window_days,
+            self.suggestion_buffer,
+            self.config.suggestion_round_step,
+        )
     }
 
-    fn next(&mut self) {
-        if self.index + 1 < self.fields.len() {
-            self.index += 1;
+    /// Takes over the ledger built by the setup wizard, and the currency picked in it.
+    fn finish_onboarding(
+        &mut self,
+        ledger: Ledger,
+        currency: Option<CurrencyFormat>,
+    ) -> Transition {
+        if let Some(currency) = currency {
+            self.config.currency = currency;
+            if let Err(err) = self.storage.save_config(&self.config) {
+                self.last_message = format!("Currency not saved: {err}");
+            }
+        }
+        self.current_account = ledger.accounts.first().map(|a| a.id);
+        self.ledger = ledger;
+        apply_config(&mut self.ledger, &self.config);
+        self.forget_undo();
+        self.log_action(
+            "onboarding",
+            json!({
+                "budgets": self.ledger.budgets.len(),
+                "transactions": self.ledger.transactions.len(),
+            }),
+        );
+        if self.save().is_ok() {
+            self.last_message = "Setup complete. Press a to add a transaction.".into();
         }
+        Transition::Close
     }
 
-    fn prev(&mut self) {
-        if self.index > 0 {
-            self.index -= 1;
+    /// Notes a change to the ledger: it becomes the next undo step once saved, and goes to
+    /// the audit log.
+    fn record(&mut self, action: &str, details: serde_json::Value) {
+        self.pending_action
+            .get_or_insert_with(|| action.to_string());
+        self.log_action(action, details);
+    }
+
+    /// Appends to the audit log when enabled. Logging failures never interrupt the user.
+    fn log_action(&self, action: &str, details: serde_json::Value) {
+        if self.config.audit_log {
+            self.storage.append_action(action, details).ok();
         }
     }
+}
 
-    fn push_char(&mut self, c: char) {
-        self.current_mut().value.push(c);
+/// Reads the overview date prompt: a preset in `from`, or two dates with `to` defaulting
+/// to today. `None` is the current month, which moves on by itself when the month turns.
+fn parse_overview_range(from: &str, to: &str, today: NaiveDate) -> Result<Option<DateRange>> {
+    let range = match from.trim().to_ascii_lowercase().as_str() {
+        "" | "month" => return Ok(None),
+        "last-month" => DateRange::month(
+            today
+                .checked_sub_months(Months::new(1))
+                .ok_or_else(|| anyhow!("No month before {today}"))?,
+        ),
+        "30d" => DateRange {
+            start: today.checked_sub_days(Days::new(29)).unwrap_or(today),
+            end: today,
+        },
+        "ytd" => DateRange {
+            start: NaiveDate::from_ymd_opt(today.year(), 1, 1).unwrap_or(today),
+            end: today,
+        },
+        from => {
+            let start = input::parse_date_flexible(from)?;
+            let end = match to.trim() {
+                "" => today,
+                to => input::parse_date_flexible(to)?,
+            };
+            if end < start {
+                return Err(anyhow!("The range ends ({end}) before it starts ({start})"));
+            }
+            DateRange { start, end }
+        }
+    };
+    Ok(Some(range))
+}
+
+/// "March 2024" for a calendar month, otherwise both dates.
+fn range_label(range: DateRange) -> String {
+    if range.is_month() {
+        range.start.format("%B %Y").to_string()
+    } else {
+        format!("{} to {}", range.start, range.end)
     }
+}
 
-    fn backspace(&mut self) {
-        self.current_mut().value.pop();
+/// Hands the config's reporting settings to a freshly loaded or built ledger.
+fn apply_config(ledger: &mut Ledger, config: &Config) {
+    ledger.set_accrual(config.accrual);
+    ledger.set_income_categories(config.income_categories.clone());
+    ledger.set_spending_classes(
+        config.fixed_categories.clone(),
+        config.discretionary_categories.clone(),
+    );
+}
+
+/// Sort orders for the Trends table, in the order `S` cycles through them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrendSort {
+    Total,
+    Average,
+    Category,
+}
+
+impl TrendSort {
+    fn next(self) -> Self {
+        match self {
+            TrendSort::Total => TrendSort::Average,
+            TrendSort::Average => TrendSort::Category,
+            TrendSort::Category => TrendSort::Total,
+        }
     }
 
-    fn try_submit(&self) -> Result<NewBudget> {
-        let category = self.fields[0].value.trim();
-        let limit = self.fields[1].value.trim();
-        if category.is_empty() {
-            return Err(anyhow!("Category is required"));
+    fn label(self) -> &'static str {
+        match self {
+            TrendSort::Total => "12-month total",
+            TrendSort::Average => "monthly average",
+            TrendSort::Category => "category",
         }
-        let monthly_limit: f64 = limit
-            .parse()
-            .context("Monthly limit must be a number (no $ sign)")?;
-        Ok(NewBudget {
-            category: category.to_string(),
-            monthly_limit,
-        })
     }
 }
 
-struct NewTransaction {
-    description: String,
-    amount: f64,
-    category: String,
-    date: NaiveDate,
+/// Overview panels that can take keyboard focus, in Tab order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OverviewFocus {
+    Budgets,
+    TopExpenses,
 }
 
-struct NewBudget {
-    category: String,
-    monthly_limit: f64,
+enum ActiveForm {
+    None,
+    Transaction(TxForm),
+    Budget(BudgetForm),
+    /// Typing edits `App::filter` live; Enter keeps the filter, Esc clears it.
+    Search,
+    Prompt(PromptForm),
+    /// A yes/no question guarding an irreversible action.
+    Confirm(ConfirmAction),
+    /// First-run setup, shown instead of the UI until a ledger exists.
+    Wizard(Box<Wizard>),
 }
 
-fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App) -> Result<()> {
-    loop {
-        terminal.draw(|f| draw(f, app))?;
-        if event::poll(Duration::from_millis(200))?
-            && let Event::Key(key) = event::read()?
-            && handle_key(app, key)?
-        {
-            return Ok(());
+/// What a mode's key handler asks for next. Handlers own their mode's keys and return this
+/// instead of assigning `App::form`, so overlays can open other modes without nesting.
+enum Transition {
+    /// Keep the current mode.
+    Stay,
+    /// Back to browsing the tabs.
+    Close,
+    /// Switch to another mode.
+    To(ActiveForm),
+    Quit,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum WizardStep {
+    Welcome,
+    Currency,
+    Account,
+    OpeningBalance,
+    Budgets,
+    Done,
+}
+
+/// Guided setup that builds a fresh ledger; it only replaces `App::ledger` when finished.
+struct Wizard {
+    step: WizardStep,
+    ledger: Ledger,
+    /// Currency code or symbol typed on the currency step, and the format picked from it;
+    /// the format goes into the config when setup finishes.
+    currency_input: String,
+    currency: Option<CurrencyFormat>,
+    /// Name of the first account, typed on the account step, and its id once added.
+    account_name: String,
+    account: Option<u64>,
+    /// What the user has today, typed on the opening balance step.
+    opening_balance: String,
+    budget: BudgetForm,
+    error: String,
+}
+
+impl Wizard {
+    fn new() -> Self {
+        Self {
+            step: WizardStep::Welcome,
+            ledger: Ledger::empty(),
+            currency_input: String::new(),
+            currency: None,
+            account_name: String::new(),
+            account: None,
+            opening_balance: String::new(),
+            budget: BudgetForm::new(),
+            error: String::new(),
         }
     }
-}
 
-fn handle_key(app: &mut App, key: KeyEvent) -> Result<bool> {
-    match &mut app.form {
-        ActiveForm::Transaction(form) => match key.code {
-            KeyCode::Esc => {
-                app.form = ActiveForm::None;
-                app.last_message = "Cancelled transaction".into();
-            }
-            KeyCode::Tab => form.next(),
-            KeyCode::BackTab => form.prev(),
-            KeyCode::Enter => {
-                if form.index + 1 < form.fields.len() {
-                    form.next();
-                } else {
-                    match form.try_submit() {
-                        Ok(tx) => {
-                            app.ledger.add_transaction(
-                                tx.description,
-                                tx.amount,
-                                tx.category,
-                                tx.date,
-                            );
-                            app.form = ActiveForm::None;
-                            app.last_message = "Transaction added".into();
-                            app.save().ok(); // best effort
-                        }
-                        Err(err) => app.last_message = err.to_string(),
+    /// Advances the wizard and returns the finished ledger once the user is done.
+    fn handle_key(&mut self, key: KeyEvent, title_case: bool) -> Option<Ledger> {
+        match self.step {
+            WizardStep::Welcome => match key.code {
+                KeyCode::Enter => self.step = WizardStep::Currency,
+                KeyCode::Char('s') => return Some(Ledger::with_sample_data()),
+                KeyCode::Char('e') => return Some(Ledger::empty()),
+                _ => {}
+            },
+            WizardStep::Currency => match key.code {
+                KeyCode::Esc => self.step = WizardStep::Account,
+                KeyCode::Enter => {
+                    self.currency = Some(CurrencyFormat::for_currency(&self.currency_input));
+                    self.step = WizardStep::Account;
+                }
+                KeyCode::Backspace => {
+                    self.currency_input.pop();
+                }
+                KeyCode::Char(c) => self.currency_input.push(c),
+                _ => {}
+            },
+            WizardStep::Account => match key.code {
+                KeyCode::Esc => {
+                    self.error.clear();
+                    self.step = WizardStep::OpeningBalance;
+                }
+                KeyCode::Enter if self.account_name.trim().is_empty() => {
+                    self.step = WizardStep::OpeningBalance
+                }
+                // Going back and forth keeps the account added the first time.
+                KeyCode::Enter if self.account.is_some() => self.step = WizardStep::OpeningBalance,
+                KeyCode::Enter => match self.ledger.add_account(&self.account_name, "") {
+                    Ok(id) => {
+                        self.account = Some(id);
+                        self.error.clear();
+                        self.step = WizardStep::OpeningBalance;
                     }
+                    Err(err) => self.error = err.to_string(),
+                },
+                KeyCode::Backspace => {
+                    self.account_name.pop();
                 }
-            }
-            KeyCode::Backspace => form.backspace(),
-            KeyCode::Left => form.prev(),
-            KeyCode::Right => form.next(),
-            KeyCode::Char(c) => form.push_char(c),
-            _ => {}
-        },
-        ActiveForm::Budget(form) => match key.code {
-            KeyCode::Esc => {
-                app.form = ActiveForm::None;
-                app.last_message = "Cancelled budget edit".into();
-            }
-            KeyCode::Tab => form.next(),
-            KeyCode::BackTab => form.prev(),
-            KeyCode::Enter => {
-                if form.index + 1 < form.fields.len() {
-                    form.next();
-                } else {
-                    match form.try_submit() {
-                        Ok(budget) => {
-                            app.ledger
-                                .add_or_update_budget(budget.category, budget.monthly_limit);
-                            app.form = ActiveForm::None;
-                            app.last_message = "Budget saved".into();
-                            app.save().ok();
-                        }
-                        Err(err) => app.last_message = err.to_string(),
+                KeyCode::Char(c) => self.account_name.push(c),
+                _ => {}
+            },
+            WizardStep::OpeningBalance => match key.code {
+                KeyCode::Esc => {
+                    self.error.clear();
+                    self.step = WizardStep::Budgets;
+                }
+                KeyCode::Enter if self.opening_balance.trim().is_empty() => {
+                    self.step = WizardStep::Budgets
+                }
+                KeyCode::Enter => match input::parse_money(&self.opening_balance) {
+                    Ok(balance) => {
+                        let today = Local::now().naive_local().date();
+                        self.ledger
+                            .set_opening_balance(self.account, balance, today);
+                        self.error.clear();
+                        self.step = WizardStep::Budgets;
                     }
+                    Err(err) => self.error = err.to_string(),
+                },
+                KeyCode::Backspace => {
+                    self.opening_balance.pop();
                 }
-            }
-            KeyCode::Backspace => form.backspace(),
-            KeyCode::Left => form.prev(),
-            KeyCode::Right => form.next(),
-            KeyCode::Char(c) => form.push_char(c),
-            _ => {}
-        },
-        ActiveForm::None => match key.code {
-            KeyCode::Char('q') => return Ok(true),
-            KeyCode::Char('h') => {
-                if app.active_tab > 0 {
-                    app.active_tab -= 1;
+                KeyCode::Char(c) => self.opening_balance.push(c),
+                _ => {}
+            },
+            WizardStep::Budgets => match key.code {
+                KeyCode::Esc => self.step = WizardStep::Done,
+                KeyCode::Tab | KeyCode::Right => self.budget.next(),
+                KeyCode::BackTab | KeyCode::Left => self.budget.prev(),
+                KeyCode::Enter if self.budget.index + 1 < self.budget.fields.len() => {
+                    self.budget.next()
                 }
-            }
-            KeyCode::Char('l') => {
-                if app.active_tab < 2 {
-                    app.active_tab += 1;
+                KeyCode::Enter => match self.budget.try_submit(title_case) {
+                    Ok(budget) => {
+                        self.ledger.add_or_update_budget(
+                            budget.category.clone(),
+                            budget.monthly_limit,
+                            budget.note,
+                        );
+                        self.ledger.set_budget_options(
+                            &budget.category,
+                            &budget.icon,
+                            budget.direction,
+                        );
+                        self.ledger.set_budget_period(
+                            &budget.category,
+                            budget.period,
+                            budget.limit,
+                        );
+                        self.budget = BudgetForm::new();
+                        self.error.clear();
+                    }
+                    Err(err) => self.error = err.to_string(),
+                },
+                KeyCode::Backspace => self.budget.backspace(),
+                KeyCode::Char(c) => self.budget.push_char(c),
+                _ => {}
+            },
+            WizardStep::Done => match key.code {
+                KeyCode::Enter => {
+                    return Some(std::mem::replace(&mut self.ledger, Ledger::empty()));
                 }
+                KeyCode::Esc => self.step = WizardStep::Budgets,
+                _ => {}
+            },
+        }
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ConfirmAction {
+    PurgeArchive,
+    /// Add the transactions parsed from a multi-line paste, kept in `App::pasted`.
+    ImportPasted,
+    /// Remove the budget with this id.
+    DeleteBudget(u64),
+    /// Move the transaction with this id to the trash.
+    DeleteTransaction(u64),
+    /// Create budgets from the auto-budget suggestions, also replacing existing limits when
+    /// `overwrite` is set.
+    ApplySuggestions {
+        overwrite: bool,
+    },
+    /// The ledger file was changed by something else (an editor, a sync client): load it,
+    /// or keep the version in memory and write it over the file.
+    ReloadChanged,
+}
+
+impl ConfirmAction {
+    fn question(self, app: &App) -> String {
+        match self {
+            ConfirmAction::PurgeArchive => format!(
+                "Permanently delete {} archived transactions?",
+                app.ledger.archived.len()
+            ),
+            ConfirmAction::ImportPasted => {
+                let Some(block) = &app.pasted else {
+                    return "Nothing pasted".into();
+                };
+                match block.failed.len() {
+                    0 => format!("Import {} pasted transactions?", block.parsed.len()),
+                    failed => format!(
+                        "Import {} pasted transactions? {failed} lines failed and will be skipped:",
+                        block.parsed.len()
+                    ),
+                }
+            }
+            ConfirmAction::ApplySuggestions { overwrite } => {
+                let suggestions = app.current_suggestions();
+                let (new, existing): (Vec<_>, Vec<_>) = suggestions
+                    .iter()
+                    .filter(|s| s.suggested_limit > 0.0)
+                    .partition(|s| !app.ledger.budgets.iter().any(|b| b.category == s.category));
+                if overwrite {
+                    format!(
+                        "Create {} and update {} budgets from the suggestions?",
+                        new.len(),
+                        existing.len()
+                    )
+                } else {
+                    format!(
+                        "Create {} budgets from the suggestions? ({} existing kept; O overwrites them too)",
+                        new.len(),
+                        existing.len()
+                    )
+                }
+            }
+            ConfirmAction::ReloadChanged => {
+                "The ledger file changed on disk. Reload it? (n keeps this version)".into()
+            }
+            ConfirmAction::DeleteTransaction(id) => {
+                match app.ledger.transactions.iter().find(|t| t.id == id) {
+                    Some(tx) => format!(
+                        "Delete \"{}\" ({}, {})? It goes to the trash.",
+                        tx.description,
+                        tx.date,
+                        format_currency(tx.amount, &app.config.currency)
+                    ),
+                    None => "Delete the selected transaction?".into(),
+                }
+            }
+            ConfirmAction::DeleteBudget(id) => format!(
+                "Delete the {} budget?",
+                app.ledger
+                    .budgets
+                    .iter()
+                    .find(|b| b.id == id)
+                    .map(|b| b.category.as_str())
+                    .unwrap_or("selected")
+            ),
+        }
+    }
+
+    /// Lines shown under the question, at most `CONFIRM_DETAIL_LINES`.
+    fn details(self, app: &App) -> Vec<String> {
+        if self == ConfirmAction::ReloadChanged {
+            return if app.unsaved {
+                vec![
+                    "Changes made here since then are not saved and will be lost on reload.".into(),
+                    "n writes them over the file instead, dropping the outside changes.".into(),
+                ]
+            } else {
+                vec!["n keeps this version; the next save overwrites the file.".into()]
+            };
+        }
+        let ConfirmAction::ImportPasted = self else {
+            return Vec::new();
+        };
+        let Some(block) = &app.pasted else {
+            return Vec::new();
+        };
+        let mut lines: Vec<String> = block
+            .failed
+            .iter()
+            .map(|f| format!("line {}: {:?} ({})", f.line, f.text, f.error))
+            .collect();
+        if lines.len() > CONFIRM_DETAIL_LINES {
+            let more = lines.len() - (CONFIRM_DETAIL_LINES - 1);
+            lines.truncate(CONFIRM_DETAIL_LINES - 1);
+            lines.push(format!("…and {more} more"));
+        }
+        lines
+    }
+
+    fn handle_key(self, app: &mut App, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Enter => {
+                app.last_message = self.run(app);
+                Transition::Close
+            }
+            KeyCode::Char('n') | KeyCode::Esc if self == ConfirmAction::ReloadChanged => {
+                app.last_message = app.keep_in_memory_ledger();
+                Transition::Close
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                app.pasted = None;
+                app.last_message = "Cancelled".into();
+                Transition::Close
+            }
+            _ => Transition::Stay,
+        }
+    }
+
+    /// Performs the action and returns the status message.
+    fn run(self, app: &mut App) -> String {
+        match self {
+            ConfirmAction::ImportPasted => {
+                let Some(block) = app.pasted.take() else {
+                    return "Nothing pasted".into();
+                };
+                for tx in &block.parsed {
+                    let category =
+                        normalize_category(&tx.category, app.config.title_case_categories);
+                    let id = app.ledger.add_transaction(
+                        tx.description.clone(),
+                        tx.amount,
+                        category,
+                        tx.date,
+                    );
+                    app.ledger.set_transaction_account(id, app.current_account);
+                }
+                app.record(
+                    "import_paste",
+                    json!({
+                        "count": block.parsed.len(),
+                        "failed": block.failed.len(),
+                        "account": app.current_account,
+                    }),
+                );
+                app.save().ok();
+                format!(
+                    "Imported {}, {} lines failed",
+                    block.parsed.len(),
+                    block.failed.len()
+                )
+            }
+            ConfirmAction::ReloadChanged => match app.reload() {
+                Ok(()) => "Reloaded the changed ledger".into(),
+                Err(err) => format!("Reload failed: {err:#}"),
+            },
+            ConfirmAction::PurgeArchive => {
+                let count = app.ledger.purge_archive();
+                app.record("purge_archive", json!({ "count": count }));
+                app.selected_archived = 0;
+                app.save().ok();
+                format!("Purged {count} archived transactions")
             }
-            KeyCode::Char('a') => app.form = ActiveForm::Transaction(TxForm::new()),
-            KeyCode::Char('b') => app.form = ActiveForm::Budget(BudgetForm::new()),
-            KeyCode::Char('s') => {
-                app.save()?;
+            ConfirmAction::ApplySuggestions { overwrite } => {
+                let suggestions = app.current_suggestions();
+                let (created, updated) = app.ledger.apply_suggestions(&suggestions, overwrite);
+                app.record(
+                    "apply_suggestions",
+                    json!({ "created": created, "updated": updated }),
+                );
+                app.save().ok();
+                format!("Created {created} and updated {updated} budgets")
             }
-            KeyCode::Char('g') => app.show_suggestions = !app.show_suggestions,
-            KeyCode::Char('r') => {
-                app.ledger = app.storage.load()?;
-                app.last_message = "Reloaded data".into();
+            ConfirmAction::DeleteTransaction(id) => {
+                if !app.ledger.archive_transaction(id) {
+                    return "Transaction already removed".into();
+                }
+                app.record("archive_transaction", json!({ "id": id }));
+                app.select_tx(app.selected_tx());
+                app.save().ok();
+                "Deleted transaction (u on the Trash tab restores it)".into()
+            }
+            ConfirmAction::DeleteBudget(id) => {
+                let category = app.ledger.budgets.iter().find(|b| b.id == id);
+                let Some(category) = category.map(|b| b.category.clone()) else {
+                    return "Budget already removed".into();
+                };
+                app.ledger.remove_budget(&category);
+                app.record("remove_budget", json!({ "category": category }));
+                let last = app.ledger.budgets.len().saturating_sub(1);
+                app.selected_budget = app.selected_budget.min(last);
+                app.save().ok();
+                format!("Deleted the {category} budget")
+            }
+        }
+    }
+}
+
+/// What a `PromptForm` does with its values when submitted.
+#[derive(Clone, Copy)]
+enum PromptAction {
+    Export,
+    BalanceAsOf,
+    /// Spread the transaction with this id over a number of months.
+    Amortize(u64),
+    AddAnnotation,
+    Reconcile,
+    Report,
+    Import,
+    AddAccount,
+    SimulateBudgets,
+    ExportRegister,
+    ExportBusiness,
+    ArchiveYear,
+    OverviewRange,
+    SwitchProfile,
+}
+
+/// A small free-text form for one-off actions; `submit_prompt` interprets the values.
+struct PromptForm {
+    action: PromptAction,
+    title: &'static str,
+    fields: Vec<Field>,
+    index: usize,
+}
+
+impl PromptForm {
+    fn new(action: PromptAction, title: &'static str, fields: Vec<Field>) -> Self {
+        Self {
+            action,
+            title,
+            fields,
+            index: 0,
+        }
+    }
+
+    fn export(query: &str) -> Self {
+        Self::new(
+            PromptAction::Export,
+            "Export transactions",
+            vec![
+                Field {
+                    label: "Period (YYYY, YYYY-MM, blank for all)",
+                    value: String::new(),
+                },
+                Field {
+                    label: "Search (blank for all)",
+                    value: query.to_string(),
+                },
+                Field {
+                    label: "Format (csv or jsonl)",
+                    value: "csv".to_string(),
+                },
+            ],
+        )
+    }
+
+    fn export_register(account: Option<&Account>) -> Self {
+        Self::new(
+            PromptAction::ExportRegister,
+            "Export account register",
+            vec![Field {
+                label: "Account",
+                value: account.map(|a| a.name.clone()).unwrap_or_default(),
+            }],
+        )
+    }
+
+    fn export_business() -> Self {
+        Self::new(
+            PromptAction::ExportBusiness,
+            "Export business expenses",
+            vec![Field {
+                label: "Period (YYYY, YYYY-MM, blank for all)",
+                value: Local::now().year().to_string(),
+            }],
+        )
+    }
+
+    fn add_account() -> Self {
+        Self::new(
+            PromptAction::AddAccount,
+            "Add account",
+            vec![
+                Field {
+                    label: "Name (e.g. Checking, Credit Card)",
+                    value: String::new(),
+                },
+                Field {
+                    label: "Default category (optional)",
+                    value: String::new(),
+                },
+                Field {
+                    label: "Current balance (optional, negative for a card owed)",
+                    value: String::new(),
+                },
+            ],
+        )
+    }
+
+    /// Starts from the current spending limits so the user only changes what they want to try.
+    fn simulate_budgets(ledger: &Ledger) -> Self {
+        let current: Vec<String> = ledger
+            .budgets
+            .iter()
+            .filter(|b| b.direction == BudgetDirection::SpendCap)
+            .map(|b| format!("{}={}", b.category, b.monthly_limit))
+            .collect();
+        Self::new(
+            PromptAction::SimulateBudgets,
+            "What-if budgets",
+            vec![
+                Field {
+                    label: "Budgets (Category=limit; ...)",
+                    value: current.join("; "),
+                },
+                Field {
+                    label: "Months of history",
+                    value: SIMULATION_MONTHS.to_string(),
+                },
+            ],
+        )
+    }
+
+    fn import() -> Self {
+        Self::new(
+            PromptAction::Import,
+            "Import bank CSV",
+            vec![
+                Field {
+                    label: "CSV file path",
+                    value: String::new(),
+                },
+                Field {
+                    label: "Profile (blank for centsh's own CSV; signed, debit-credit or from config)",
+                    value: String::new(),
+                },
+                Field {
+                    label: "Flip amount signs? (y/N, when spending comes in as income)",
+                    value: String::new(),
+                },
+            ],
+        )
+    }
+
+    fn report() -> Self {
+        Self::new(
+            PromptAction::Report,
+            "HTML report",
+            vec![Field {
+                label: "Month (YYYY-MM)",
+                value: Local::now().format("%Y-%m").to_string(),
+            }],
+        )
+    }
+
+    fn archive_year() -> Self {
+        Self::new(
+            PromptAction::ArchiveYear,
+            "Archive a year",
+            vec![Field {
+                label: "Year (YYYY, must be over)",
+                value: (Local::now().year() - 1).to_string(),
+            }],
+        )
+    }
+
+    /// Prefilled with the range shown now, or the `month` preset when following the calendar.
+    fn overview_range(current: Option<DateRange>) -> Self {
+        let (from, to) = match current {
+            Some(range) => (range.start.to_string(), range.end.to_string()),
+            None => ("month".to_string(), String::new()),
+        };
+        Self::new(
+            PromptAction::OverviewRange,
+            "Overview dates",
+            vec![
+                Field {
+                    label: "From (YYYY-MM-DD, or month, last-month, 30d, ytd)",
+                    value: from,
+                },
+                Field {
+                    label: "To (YYYY-MM-DD, blank for today; ignored for presets)",
+                    value: to,
+                },
+            ],
+        )
+    }
+
+    fn switch_profile(current: Option<&str>) -> Self {
+        Self::new(
+            PromptAction::SwitchProfile,
+            "Switch profile",
+            vec![Field {
+                label: "Profile (e.g. business; blank for the default ledger)",
+                value: current.unwrap_or_default().to_string(),
+            }],
+        )
+    }
+
+    fn balance_as_of() -> Self {
+        Self::new(
+            PromptAction::BalanceAsOf,
+            "Balance as of",
+            vec![Field {
+                label: "Date (YYYY-MM-DD)",
+                value: Local::now().naive_local().date().to_string(),
+            }],
+        )
+    }
+
+    fn amortize(tx: &Transaction) -> Self {
+        Self::new(
+            PromptAction::Amortize(tx.id),
+            "Amortize transaction",
+            vec![Field {
+                label: "Spread over months (blank or 1 to count once)",
+                value: tx
+                    .amortize_months
+                    .map(|m| m.to_string())
+                    .unwrap_or_default(),
+            }],
+        )
+    }
+
+    fn reconcile() -> Self {
+        Self::new(
+            PromptAction::Reconcile,
+            "Reconcile with a statement",
+            vec![
+                Field {
+                    label: "Statement ending balance",
+                    value: String::new(),
+                },
+                Field {
+                    label: "Statement date (YYYY-MM-DD)",
+                    value: Local::now().naive_local().date().to_string(),
+                },
+            ],
+        )
+    }
+
+    fn add_annotation() -> Self {
+        Self::new(
+            PromptAction::AddAnnotation,
+            "Annotate cashflow",
+            vec![
+                Field {
+                    label: "Date (YYYY-MM-DD)",
+                    value: Local::now().naive_local().date().to_string(),
+                },
+                Field {
+                    label: "Event",
+                    value: String::new(),
+                },
+            ],
+        )
+    }
+
+    fn current_mut(&mut self) -> &mut Field {
+        &mut self.fields[self.index]
+    }
+
+    fn next(&mut self) {
+        if self.index + 1 < self.fields.len() {
+            self.index += 1;
+        }
+    }
+
+    fn prev(&mut self) {
+        if self.index > 0 {
+            self.index -= 1;
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.current_mut().value.push(c);
+    }
+
+    fn backspace(&mut self) {
+        self.current_mut().value.pop();
+    }
+
+    fn handle_key(&mut self, app: &mut App, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Esc => {
+                app.last_message = "Cancelled".into();
+                return Transition::Close;
             }
-            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(true),
+            KeyCode::Tab | KeyCode::Right => self.next(),
+            KeyCode::BackTab | KeyCode::Left => self.prev(),
+            KeyCode::Enter if self.index + 1 < self.fields.len() => self.next(),
+            KeyCode::Enter => match submit_prompt(app, self.action, &self.values()) {
+                Ok(message) => {
+                    app.last_message = message;
+                    return Transition::Close;
+                }
+                Err(err) => app.last_message = err.to_string(),
+            },
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Char(c) => self.push_char(c),
             _ => {}
-        },
+        }
+        Transition::Stay
+    }
+
+    fn values(&self) -> Vec<String> {
+        self.fields
+            .iter()
+            .map(|f| f.value.trim().to_string())
+            .collect()
+    }
+}
+
+/// Runs a submitted prompt and returns the status message to show.
+fn submit_prompt(app: &mut App, action: PromptAction, values: &[String]) -> Result<String> {
+    match action {
+        PromptAction::ExportRegister => {
+            let account = app
+                .ledger
+                .account_by_name(&values[0])
+                .ok_or_else(|| anyhow!("No account named {:?}", values[0]))?;
+            let (path, count) = app.storage.export_register(&app.ledger, account.id)?;
+            Ok(format!(
+                "Exported the {} register ({count} transactions) to {}",
+                account.name,
+                path.display()
+            ))
+        }
+        PromptAction::OverviewRange => {
+            let today = Local::now().naive_local().date();
+            app.overview_range = parse_overview_range(&values[0], &values[1], today)?;
+            app.overview_index = 0;
+            Ok(match app.overview_range {
+                Some(range) => format!("Overview shows {}", range_label(range)),
+                None => "Overview shows this month".to_string(),
+            })
+        }
+        PromptAction::SwitchProfile => {
+            let profile = Some(values[0].as_str()).filter(|p| !p.is_empty());
+            app.switch_profile(profile)
+        }
+        PromptAction::ArchiveYear => {
+            let year: i32 = values[0]
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Year must be YYYY"))?;
+            let before = app.ledger.transactions.len();
+            let path = app.storage.archive_year(&mut app.ledger, year)?;
+            let moved = before - app.ledger.transactions.len();
+            app.select_tx(0);
+            app.log_action(
+                "archive_year",
+                json!({ "year": year, "transactions": moved }),
+            );
+            app.forget_undo();
+            Ok(format!(
+                "Archived {moved} transactions from {year} to {}",
+                path.display()
+            ))
+        }
+        PromptAction::ExportBusiness => {
+            let period = ExportPeriod::parse(&values[0])?;
+            let (path, count) = app.storage.export_csv_business_only(&app.ledger, period)?;
+            Ok(format!(
+                "Exported {count} business transactions to {}",
+                path.display()
+            ))
+        }
+        PromptAction::Export => {
+            let scope = ExportScope {
+                period: ExportPeriod::parse(&values[0])?,
+                query: values[1].clone(),
+                format: ExportFormat::parse(&values[2])?,
+            };
+            let (path, count) = app.storage.export_filtered(&app.ledger, &scope)?;
+            Ok(format!(
+                "Exported {count} transactions to {}",
+                path.display()
+            ))
+        }
+        PromptAction::Amortize(id) => {
+            let months = if values[0].is_empty() {
+                None
+            } else {
+                Some(
+                    values[0]
+                        .parse::<u32>()
+                        .context("Months must be a whole number")?,
+                )
+            };
+            if !app.ledger.set_amortization(id, months) {
+                return Err(anyhow!(ARCHIVED_READ_ONLY));
+            }
+            app.record(
+                "amortize_transaction",
+                json!({ "id": id, "months": months }),
+            );
+            app.save()?;
+            Ok(match months.filter(|m| *m > 1) {
+                Some(m) if app.config.accrual => format!("Spread over {m} months"),
+                Some(m) => format!("Spread over {m} months (enable accrual in config to see it)"),
+                None => "Counted once when paid".to_string(),
+            })
+        }
+        PromptAction::SimulateBudgets => {
+            let proposed: Vec<(String, f64)> = input::parse_budget_list(&values[0])?
+                .into_iter()
+                .map(|(category, limit)| {
+                    (
+                        normalize_category(&category, app.config.title_case_categories),
+                        limit,
+                    )
+                })
+                .collect();
+            if proposed.is_empty() {
+                return Err(anyhow!("Enter at least one budget, e.g. Food=400"));
+            }
+            let months = match values[1].as_str() {
+                "" => SIMULATION_MONTHS,
+                text => text
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|m| (1..=24).contains(m))
+                    .ok_or_else(|| anyhow!("Months must be a whole number from 1 to 24"))?,
+            };
+            let results = app.ledger.simulate_budgets(&proposed, months);
+            let blown = results
+                .iter()
+                .flat_map(|month| &month.budgets)
+                .filter(|b| !b.met())
+                .count();
+            app.simulation = Some(results);
+            app.active_tab = 2;
+            Ok(format!(
+                "What-if: {blown} budget-months over the limit in the last {months} months (Esc closes)"
+            ))
+        }
+        PromptAction::AddAccount => {
+            let category = if values[1].is_empty() {
+                String::new()
+            } else {
+                normalize_category(&values[1], app.config.title_case_categories)
+            };
+            let opening = match values[2].trim() {
+                "" => None,
+                balance => Some(input::parse_money(balance)?),
+            };
+            let id = app.ledger.add_account(&values[0], &category)?;
+            app.current_account = Some(id);
+            if let Some(balance) = opening {
+                let today = Local::now().naive_local().date();
+                app.ledger.set_opening_balance(Some(id), balance, today);
+            }
+            app.record(
+                "add_account",
+                json!({
+                    "id": id,
+                    "name": values[0],
+                    "default_category": category,
+                    "opening_balance": opening,
+                }),
+            );
+            app.save()?;
+            Ok(format!(
+                "Added account {}; new transactions go to it",
+                values[0]
+            ))
+        }
+        PromptAction::Import => {
+            let flip = match values[2].trim().to_ascii_lowercase().as_str() {
+                "" | "n" | "no" => false,
+                "y" | "yes" => true,
+                other => return Err(anyhow!("Flip amount signs must be y or n, not {other:?}")),
+            };
+            let path = Path::new(&values[0]);
+            let (count, failed, profile) = if values[1].trim().is_empty() {
+                let (count, failed) = app.storage.import_csv(path, &mut app.ledger, flip)?;
+                (count, failed, "centsh".to_string())
+            } else {
+                let mut profile = app
+                    .config
+                    .import_profile(&values[1])
+                    .ok_or_else(|| anyhow!("No import profile named {:?}", values[1]))?;
+                profile.negative_is_spending ^= flip;
+                let (count, failed) =
+                    app.storage
+                        .import_csv_with_profile(path, &mut app.ledger, &profile)?;
+                (count, failed, profile.name)
+            };
+            // Imported categories get the same tidying as typed ones.
+            app.ledger
+                .normalize_categories(app.config.title_case_categories);
+            app.record(
+                "import_csv",
+                json!({
+                    "file": values[0],
+                    "profile": profile,
+                    "flipped": flip,
+                    "count": count,
+                    "failed": failed.len(),
+                }),
+            );
+            app.save()?;
+            let imported = format!("Imported {count} transactions with the {profile} profile");
+            Ok(match failed.as_slice() {
+                [] => imported,
+                [only] => format!("{imported}; skipped line {}: {}", only.line, only.error),
+                [first, rest @ ..] => {
+                    let mut others = rest
+                        .iter()
+                        .take(SKIPPED_LINES_LISTED)
+                        .map(|f| f.line.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    if rest.len() > SKIPPED_LINES_LISTED {
+                        others
+                            .push_str(&format!(" and {} more", rest.len() - SKIPPED_LINES_LISTED));
+                    }
+                    format!(
+                        "{imported}; skipped {} lines, first line {}: {} (and lines {others})",
+                        rest.len() + 1,
+                        first.line,
+                        first.error,
+                    )
+                }
+            })
+        }
+        PromptAction::Report => {
+            let ExportPeriod::Month(year, month) = ExportPeriod::parse(&values[0])? else {
+                return Err(anyhow!("Month must be YYYY-MM"));
+            };
+            let html = app.ledger.monthly_report_html(year, month);
+            let path = app
+                .storage
+                .write_report(&format!("report-{year}-{month:02}.html"), &html)?;
+            Ok(format!("Wrote report to {}", path.display()))
+        }
+        PromptAction::Reconcile => {
+            let target = input::parse_money(&values[0])?;
+            let date = input::parse_date_flexible(&values[1])?;
+            app.reconcile = Some((target, date));
+            app.active_tab = 1;
+            Ok("Reconciling: press c to mark transactions on the statement, Esc to finish".into())
+        }
+        PromptAction::AddAnnotation => {
+            let date = input::parse_date_flexible(&values[0])?;
+            if values[1].is_empty() {
+                return Err(anyhow!("Event is required"));
+            }
+            app.ledger.add_annotation(date, values[1].clone());
+            app.record(
+                "add_annotation",
+                json!({ "date": date, "label": values[1] }),
+            );
+            app.save()?;
+            Ok(format!("Marked \"{}\" on the cashflow chart", values[1]))
+        }
+        PromptAction::BalanceAsOf => {
+            let date = input::parse_date_flexible(&values[0])?;
+            let balance = app.ledger.balance_as_of(date, app.current_account);
+            let balance = format_currency(balance, &app.config.currency);
+            Ok(
+                match app.current_account.and_then(|id| app.ledger.account(id)) {
+                    Some(account) => format!("{} balance on {date}: {balance}", account.name),
+                    None => format!("Balance on {date}: {balance}"),
+                },
+            )
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Field {
+    label: &'static str,
+    value: String,
+}
+
+struct TxForm {
+    title: &'static str,
+    fields: Vec<Field>,
+    index: usize,
+    /// Income entry: the user types a positive amount and it is stored negated.
+    income: bool,
+    /// Editing the template at this index instead of adding a transaction.
+    template_index: Option<usize>,
+    /// Editing the transaction with this id instead of adding one.
+    editing_id: Option<u64>,
+    /// Existing transaction this one looks like a repeat of; set after warning once.
+    duplicate_of: Option<u64>,
+}
+
+impl TxForm {
+    /// An empty form whose category starts as `account`'s default, or "General".
+    fn new(account: Option<&Account>) -> Self {
+        let today = Local::now().naive_local().date();
+        let category = account
+            .map(|a| a.default_category.clone())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| "General".to_string());
+        Self {
+            title: "Add transaction",
+            fields: vec![
+                Field {
+                    label: "Description",
+                    value: String::new(),
+                },
+                Field {
+                    label: "Amount (+out / -in)",
+                    value: String::new(),
+                },
+                Field {
+                    label: "Category (Up/Down: recent)",
+                    value: category,
+                },
+                Field {
+                    label: "Date (YYYY-MM-DD or -3d/-2w/+1w, Up/Down: day, Shift: month)",
+                    value: today.to_string(),
+                },
+                Field {
+                    label: "My share % (optional, for split bills)",
+                    value: String::new(),
+                },
+            ],
+            index: 0,
+            income: false,
+            template_index: None,
+            editing_id: None,
+            duplicate_of: None,
+        }
+    }
+
+    fn income() -> Self {
+        let mut form = Self::new(None);
+        form.title = "Add income";
+        form.income = true;
+        form.fields[1].label = "Amount received";
+        form.fields[2].value = "Income".to_string();
+        form
+    }
+
+    /// A form filled from an already-parsed transaction, focused on the last field so a
+    /// single Enter confirms it.
+    fn prefilled(title: &'static str, tx: &NewTransaction) -> Self {
+        let mut form = Self::new(None);
+        form.title = title;
+        form.fields[0].value = tx.description.clone();
+        form.fields[1].value = tx.amount.to_string();
+        form.fields[2].value = tx.category.clone();
+        form.fields[3].value = tx.date.to_string();
+        form.index = form.fields.len() - 1;
+        form
+    }
+
+    /// A form for changing `tx` in place, starting on the description.
+    fn edit(tx: &Transaction) -> Self {
+        let mut form = Self::prefilled(
+            "Edit transaction",
+            &NewTransaction {
+                description: tx.description.clone(),
+                amount: tx.amount,
+                category: tx.category.clone(),
+                date: tx.date,
+                note: tx.note.clone(),
+            },
+        );
+        form.editing_id = Some(tx.id);
+        form.index = 0;
+        form
+    }
+
+    fn current_mut(&mut self) -> &mut Field {
+        &mut self.fields[self.index]
+    }
+
+    fn next(&mut self) {
+        if self.index + 1 < self.fields.len() {
+            self.index += 1;
+        }
+    }
+
+    fn prev(&mut self) {
+        if self.index > 0 {
+            self.index -= 1;
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.current_mut().value.push(c);
+        self.duplicate_of = None;
+    }
+
+    fn backspace(&mut self) {
+        self.current_mut().value.pop();
+        self.duplicate_of = None;
+    }
+
+    /// Title, with the duplicate warning while one is pending.
+    fn heading(&self) -> String {
+        match self.duplicate_of {
+            Some(_) => format!("{} — possible duplicate, Enter again to add", self.title),
+            None => self.title.to_string(),
+        }
+    }
+
+    /// Moves the date field a day (or with `by_month`, a month) forward or back, resolving
+    /// offsets like `-3d` first. Left alone while the field is not a valid date.
+    fn step_date(&mut self, forward: bool, by_month: bool) {
+        let field = &mut self.fields[3];
+        let Ok(date) = input::parse_date_flexible(&field.value) else {
+            return;
+        };
+        let stepped = match (by_month, forward) {
+            (false, true) => date.checked_add_days(Days::new(1)),
+            (false, false) => date.checked_sub_days(Days::new(1)),
+            (true, true) => date.checked_add_months(Months::new(1)),
+            (true, false) => date.checked_sub_months(Months::new(1)),
+        };
+        if let Some(stepped) = stepped {
+            field.value = stepped.to_string();
+            self.duplicate_of = None;
+        }
+    }
+
+    /// Replaces the category with the next (or previous) entry in `choices`.
+    fn cycle_category(&mut self, choices: &[String], forward: bool) {
+        if choices.is_empty() {
+            return;
+        }
+        let current = choices
+            .iter()
+            .position(|c| c.eq_ignore_ascii_case(self.fields[2].value.trim()));
+        let next = match (current, forward) {
+            (None, _) => 0,
+            (Some(i), true) => (i + 1) % choices.len(),
+            (Some(i), false) => (i + choices.len() - 1) % choices.len(),
+        };
+        self.fields[2].value = choices[next].clone();
+        self.duplicate_of = None;
+    }
+
+    fn handle_key(&mut self, app: &mut App, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Esc => {
+                app.last_message = "Cancelled transaction".into();
+                return Transition::Close;
+            }
+            KeyCode::Char('=') if self.index == 1 => {
+                app.calculator = Some(self.fields[1].value.clone());
+            }
+            KeyCode::Up | KeyCode::Down if self.index == 2 => {
+                let choices = category_choices(&app.ledger, &app.recent_categories);
+                self.cycle_category(&choices, key.code == KeyCode::Down);
+            }
+            KeyCode::Up | KeyCode::Down if self.index == 3 => {
+                let by_month = key
+                    .modifiers
+                    .intersects(KeyModifiers::SHIFT | KeyModifiers::CONTROL);
+                self.step_date(key.code == KeyCode::Up, by_month);
+            }
+            KeyCode::Tab => self.next(),
+            KeyCode::BackTab => self.prev(),
+            KeyCode::Enter if self.index + 1 < self.fields.len() => self.next(),
+            KeyCode::Enter => return self.submit(app, key),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Left => self.prev(),
+            KeyCode::Right => self.next(),
+            KeyCode::Char(c) => self.push_char(c),
+            _ => {}
+        }
+        Transition::Stay
+    }
+
+    fn submit(&mut self, app: &mut App, key: KeyEvent) -> Transition {
+        match self.try_submit(app.config.title_case_categories) {
+            Ok(tx) if let Some(index) = self.template_index => {
+                app.record("update_template", json!({ "index": index, "template": tx }));
+                app.ledger.update_template(index, tx);
+                app.last_message = "Template updated".into();
+                app.save().ok();
+                Transition::Close
+            }
+            Ok(tx) if let Some(id) = self.editing_id => {
+                let details = serde_json::to_value(&tx).unwrap_or_default();
+                match app.ledger.update_transaction(id, tx) {
+                    Ok(()) => {
+                        app.record(
+                            "update_transaction",
+                            json!({ "id": id, "transaction": details }),
+                        );
+                        // Follow the row if its new date moved it.
+                        if let Some(index) =
+                            app.visible_transactions().iter().position(|t| t.id == id)
+                        {
+                            app.select_tx(index);
+                        }
+                        app.last_message = "Transaction updated".into();
+                        app.save().ok();
+                        Transition::Close
+                    }
+                    Err(err) => {
+                        app.last_message = err.to_string();
+                        Transition::Stay
+                    }
+                }
+            }
+            // A likely double submit; a second Enter (or Alt+Enter straight away) adds it anyway.
+            Ok(tx)
+                if self.duplicate_of.is_none()
+                    && !key.modifiers.contains(KeyModifiers::ALT)
+                    && let Some(original) = app.ledger.find_duplicate(&tx) =>
+            {
+                self.duplicate_of = Some(original.id);
+                app.last_message = format!(
+                    "Looks like a duplicate of {:?} on {} — Enter to add anyway, Esc to cancel",
+                    original.description, original.date
+                );
+                Transition::Stay
+            }
+            Ok(tx) => {
+                app.recent_categories.retain(|c| *c != tx.category);
+                app.recent_categories.insert(0, tx.category.clone());
+                let details = serde_json::to_value(&tx).unwrap_or_default();
+                let id =
+                    app.ledger
+                        .add_transaction(tx.description, tx.amount, tx.category, tx.date);
+                app.ledger.set_transaction_account(id, app.current_account);
+                app.ledger.set_transaction_note(id, tx.note);
+                app.record(
+                    "add_transaction",
+                    json!({
+                        "id": id,
+                        "transaction": details,
+                        "account": app.current_account,
+                    }),
+                );
+                app.last_message = if self.income {
+                    "Income added".into()
+                } else {
+                    "Transaction added".into()
+                };
+                app.save().ok(); // best effort
+                Transition::Close
+            }
+            Err(err) => {
+                app.last_message = err.to_string();
+                Transition::Stay
+            }
+        }
+    }
+
+    fn try_submit(&self, title_case: bool) -> Result<NewTransaction> {
+        let description = self.fields[0].value.trim();
+        let amount_str = self.fields[1].value.trim();
+        let category = normalize_category(&self.fields[2].value, title_case);
+        let date_str = self.fields[3].value.trim();
+        let share = input::parse_share_pct(&self.fields[4].value)?;
+
+        if description.is_empty() {
+            return Err(anyhow!("Description is required"));
+        }
+        if amount_str.is_empty() {
+            return Err(anyhow!("Amount is required"));
+        }
+        let amount = input::parse_adjusted_money(amount_str)?;
+        let amount = if self.income { -amount.abs() } else { amount };
+        let date = input::parse_date_flexible(date_str)?;
+        let (amount, note) = match share {
+            Some(pct) if pct < 100.0 => (
+                input::apply_share(amount, pct)?,
+                format!("My {pct:.1}% share of {:.2}", amount.abs()),
+            ),
+            _ => (amount, String::new()),
+        };
+
+        Ok(NewTransaction {
+            description: description.to_string(),
+            amount,
+            category: if category.is_empty() {
+                "General".to_string()
+            } else {
+                category
+            },
+            date,
+            note,
+        })
+    }
+}
+
+struct BudgetForm {
+    fields: Vec<Field>,
+    index: usize,
+    /// Category of the budget being edited; `None` adds or updates by category.
+    editing: Option<String>,
+}
+
+impl BudgetForm {
+    fn new() -> Self {
+        Self {
+            fields: vec![
+                Field {
+                    label: "Category",
+                    value: "General".to_string(),
+                },
+                Field {
+                    label: "Limit (per period)",
+                    value: String::new(),
+                },
+                Field {
+                    label: "Period (weekly, biweekly, monthly, yearly)",
+                    value: BudgetPeriod::Monthly.label().to_string(),
+                },
+                Field {
+                    label: "Note (optional)",
+                    value: String::new(),
+                },
+                Field {
+                    label: "Icon (optional emoji)",
+                    value: String::new(),
+                },
+                Field {
+                    label: "Direction (cap: stay under, save: reach)",
+                    value: BudgetDirection::SpendCap.label().to_string(),
+                },
+            ],
+            index: 0,
+            editing: None,
+        }
+    }
+
+    fn edit(budget: &Budget) -> Self {
+        let mut form = Self::new();
+        form.fields[0].value = budget.category.clone();
+        form.fields[1].value = budget.period_limit().to_string();
+        form.fields[2].value = budget.period.label().to_string();
+        form.fields[3].value = budget.note.clone();
+        form.fields[4].value = budget.icon.clone();
+        form.fields[5].value = budget.direction.label().to_string();
+        form.editing = Some(budget.category.clone());
+        form
+    }
+
+    fn title(&self) -> &'static str {
+        if self.editing.is_some() {
+            "Edit budget"
+        } else {
+            "Add budget"
+        }
+    }
+
+    fn current_mut(&mut self) -> &mut Field {
+        &mut self.fields[self.index]
+    }
+
+    fn next(&mut self) {
+        if self.index + 1 < self.fields.len() {
+            self.index += 1;
+        }
+    }
+
+    fn prev(&mut self) {
+        if self.index > 0 {
+            self.index -= 1;
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.current_mut().value.push(c);
+    }
+
+    fn backspace(&mut self) {
+        self.current_mut().value.pop();
+    }
+
+    fn handle_key(&mut self, app: &mut App, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Esc => {
+                app.last_message = "Cancelled budget edit".into();
+                return Transition::Close;
+            }
+            KeyCode::Char('=') if self.index == 1 => {
+                app.calculator = Some(self.fields[1].value.clone());
+            }
+            KeyCode::Tab => self.next(),
+            KeyCode::BackTab => self.prev(),
+            KeyCode::Enter if self.index + 1 < self.fields.len() => self.next(),
+            KeyCode::Enter => return self.submit(app),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Left => self.prev(),
+            KeyCode::Right => self.next(),
+            KeyCode::Char(c) => self.push_char(c),
+            _ => {}
+        }
+        Transition::Stay
+    }
+
+    fn submit(&self, app: &mut App) -> Transition {
+        let saved = self
+            .try_submit(app.config.title_case_categories)
+            .and_then(|budget| {
+                let details = json!({
+                    "category": budget.category,
+                    "monthly_limit": budget.monthly_limit,
+                    "note": budget.note,
+                    "icon": budget.icon,
+                    "direction": budget.direction,
+                    "period": budget.period,
+                    "limit": budget.limit,
+                    "previous_category": self.editing,
+                });
+                match &self.editing {
+                    Some(original) => app.ledger.edit_budget(
+                        original,
+                        budget.category.clone(),
+                        budget.monthly_limit,
+                        budget.note,
+                    )?,
+                    None => app.ledger.add_or_update_budget(
+                        budget.category.clone(),
+                        budget.monthly_limit,
+                        budget.note,
+                    ),
+                }
+                app.ledger
+                    .set_budget_options(&budget.category, &budget.icon, budget.direction);
+                app.ledger
+                    .set_budget_period(&budget.category, budget.period, budget.limit);
+                Ok(details)
+            });
+        match saved {
+            Ok(details) => {
+                app.record("set_budget", details);
+                app.last_message = "Budget saved".into();
+                app.save().ok();
+                Transition::Close
+            }
+            Err(err) => {
+                app.last_message = err.to_string();
+                Transition::Stay
+            }
+        }
+    }
+
+    fn try_submit(&self, title_case: bool) -> Result<NewBudget> {
+        let category = normalize_category(&self.fields[0].value, title_case);
+        let limit = self.fields[1].value.trim();
+        let note = self.fields[3].value.trim();
+        let icon = self.fields[4].value.trim();
+        if category.is_empty() {
+            return Err(anyhow!("Category is required"));
+        }
+        // Wider icons would push the category out of its column.
+        if Span::raw(icon).width() > 2 {
+            return Err(anyhow!("Icon must be a single emoji or character"));
+        }
+        let period = BudgetPeriod::parse(&self.fields[2].value)?;
+        let limit = input::parse_money(limit)?;
+        let direction = BudgetDirection::parse(&self.fields[5].value)?;
+        Ok(NewBudget {
+            category,
+            monthly_limit: limit * period.per_month(),
+            limit,
+            note: note.to_string(),
+            icon: icon.to_string(),
+            direction,
+            period,
+        })
+    }
+}
+
+struct NewBudget {
+    category: String,
+    monthly_limit: f64,
+    /// The limit per `period`, as typed.
+    limit: f64,
+    note: String,
+    icon: String,
+    direction: BudgetDirection,
+    period: BudgetPeriod,
+}
+
+/// Categories in the order the category field cycles through them: this session's entries,
+/// then the ledger's most recent, then everything else alphabetically.
+fn category_choices(ledger: &Ledger, session: &[String]) -> Vec<String> {
+    let mut choices: Vec<String> = Vec::new();
+    for category in session
+        .iter()
+        .cloned()
+        .chain(ledger.recent_categories(RECENT_CATEGORIES))
+        .chain(ledger.categories())
+    {
+        if !choices.contains(&category) {
+            choices.push(category);
+        }
+    }
+    choices
+}
+
+/// A Transactions tab search for just `category` and its subcategories, quoting names with
+/// spaces.
+fn category_search(category: &str) -> String {
+    if category.contains(char::is_whitespace) {
+        format!("category:\"{category}\"")
+    } else {
+        format!("category:{category}")
+    }
+}
+
+fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App) -> Result<()> {
+    loop {
+        app.check_external_change();
+        terminal.draw(|f| draw(f, app))?;
+        if !event::poll(Duration::from_millis(200))? {
+            continue;
+        }
+        // Handle everything already queued (paste bursts, held keys) before redrawing.
+        // Releases are ignored so terminals that report them do not fire keys twice.
+        loop {
+            if let Event::Key(key) = event::read()?
+                && key.kind != KeyEventKind::Release
+                && handle_key(app, key)?
+            {
+                return Ok(());
+            }
+            if !event::poll(Duration::ZERO)? {
+                break;
+            }
+        }
+    }
+}
+
+fn handle_key(app: &mut App, key: KeyEvent) -> Result<bool> {
+    if app.calculator.is_some() {
+        handle_calculator_key(app, key);
+        return Ok(false);
+    }
+    // The mode is taken out of the app while its handler runs, so the handler can borrow both;
+    // `Stay` puts it back.
+    let mut mode = std::mem::replace(&mut app.form, ActiveForm::None);
+    let transition = match &mut mode {
+        ActiveForm::None => handle_browse_key(app, key)?,
+        ActiveForm::Wizard(_)
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            Transition::Quit
+        }
+        ActiveForm::Wizard(wizard) => {
+            match wizard.handle_key(key, app.config.title_case_categories) {
+                Some(ledger) => app.finish_onboarding(ledger, wizard.currency.take()),
+                None => Transition::Stay,
+            }
+        }
+        ActiveForm::Transaction(form) => form.handle_key(app, key),
+        ActiveForm::Budget(form) => form.handle_key(app, key),
+        ActiveForm::Prompt(form) => form.handle_key(app, key),
+        ActiveForm::Confirm(action) => action.handle_key(app, key),
+        ActiveForm::Search => handle_search_key(app, key),
+    };
+    match transition {
+        Transition::Stay => app.form = mode,
+        Transition::Close => app.form = ActiveForm::None,
+        Transition::To(next) => app.form = next,
+        Transition::Quit => return Ok(true),
+    }
+    Ok(false)
+}
+
+/// Keys while browsing the tabs with no form open.
+fn handle_browse_key(app: &mut App, key: KeyEvent) -> Result<Transition> {
+    match key.code {
+        KeyCode::Char('q') => return Ok(Transition::Quit),
+        KeyCode::Char(c @ ('z' | 'y')) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.undo_step(c == 'y');
+        }
+        KeyCode::Char('h') if app.active_tab > 0 => app.active_tab -= 1,
+        KeyCode::Char('l') if app.active_tab + 1 < TAB_TITLES.len() => app.active_tab += 1,
+        KeyCode::Char(c @ '1'..='9') => {
+            let tab = c as usize - '1' as usize;
+            if tab < TAB_TITLES.len() {
+                app.active_tab = tab;
+            }
+        }
+        KeyCode::Char('a') => {
+            let account = app.current_account.and_then(|id| app.ledger.account(id));
+            return Ok(Transition::To(ActiveForm::Transaction(TxForm::new(
+                account,
+            ))));
+        }
+        KeyCode::Char('o') => app.cycle_account(),
+        KeyCode::Char('N') => {
+            return Ok(Transition::To(
+                ActiveForm::Prompt(PromptForm::add_account()),
+            ));
+        }
+        KeyCode::Char('i') => return Ok(Transition::To(ActiveForm::Transaction(TxForm::income()))),
+        KeyCode::Char('b') => return Ok(Transition::To(ActiveForm::Budget(BudgetForm::new()))),
+        KeyCode::Char('j') | KeyCode::Down if app.active_tab == 1 => {
+            app.select_tx(app.selected_tx() + 1);
+        }
+        KeyCode::Char('k') | KeyCode::Up if app.active_tab == 1 => {
+            app.select_tx(app.selected_tx().saturating_sub(1));
+        }
+        KeyCode::Char('R') if app.active_tab == 1 => app.link_refund_step(),
+        KeyCode::Char('d') if app.active_tab == 1 => {
+            if let Some(id) = app.selected_transaction().map(|t| t.id) {
+                if app.ledger.is_from_archive(id) {
+                    app.last_message = ARCHIVED_READ_ONLY.into();
+                    return Ok(Transition::Stay);
+                }
+                return Ok(Transition::To(ActiveForm::Confirm(
+                    ConfirmAction::DeleteTransaction(id),
+                )));
+            }
+        }
+        KeyCode::Char('c')
+            if app.active_tab == 1 && !key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            if let Some(id) = app.selected_transaction().map(|t| t.id) {
+                match app.ledger.toggle_cleared(id) {
+                    Some(cleared) => {
+                        app.record("set_cleared", json!({ "id": id, "cleared": cleared }));
+                        app.save().ok();
+                    }
+                    None => app.last_message = ARCHIVED_READ_ONLY.into(),
+                }
+            }
+        }
+        KeyCode::Char('S') if app.active_tab == 8 => app.trend_sort = app.trend_sort.next(),
+        KeyCode::Char('j') | KeyCode::Down if app.active_tab == 7 => {
+            // The same card area `draw` lays out: the tab body inside its border.
+            let (width, height) = terminal::size().unwrap_or((80, 24));
+            let inner = Rect::new(
+                0,
+                0,
+                width.saturating_sub(2),
+                height.saturating_sub(4 + footer_height(app, width) + 2),
+            );
+            let last = envelope_max_scroll(app.ledger.budgets.len(), inner);
+            app.envelope_scroll = (app.envelope_scroll + 1).min(last);
+        }
+        KeyCode::Char('k') | KeyCode::Up if app.active_tab == 7 => {
+            app.envelope_scroll = app.envelope_scroll.saturating_sub(1);
+        }
+        KeyCode::Tab if app.active_tab == 0 => {
+            // Panels left out of `overview_widgets` are skipped.
+            let shown = |focus: OverviewFocus| {
+                let widget = match focus {
+                    OverviewFocus::Budgets => OverviewWidget::Stats,
+                    OverviewFocus::TopExpenses => OverviewWidget::TopExpenses,
+                };
+                app.config.overview_widgets.contains(&widget)
+            };
+            app.overview_focus = match app.overview_focus {
+                None => Some(OverviewFocus::Budgets),
+                Some(OverviewFocus::Budgets) => Some(OverviewFocus::TopExpenses),
+                Some(OverviewFocus::TopExpenses) => None,
+            };
+            if let Some(focus) = app.overview_focus.filter(|f| !shown(*f)) {
+                app.overview_focus = match focus {
+                    OverviewFocus::Budgets => Some(OverviewFocus::TopExpenses),
+                    OverviewFocus::TopExpenses => None,
+                }
+                .filter(|f| shown(*f));
+            }
+            app.overview_index = 0;
+        }
+        KeyCode::Esc if app.active_tab == 0 && app.overview_focus.is_some() => {
+            app.overview_focus = None
+        }
+        KeyCode::Char('j') | KeyCode::Down if app.active_tab == 0 => {
+            let last = app.overview_rows().saturating_sub(1);
+            app.overview_index = (app.overview_index + 1).min(last);
+        }
+        KeyCode::Char('k') | KeyCode::Up if app.active_tab == 0 => {
+            app.overview_index = app.overview_index.saturating_sub(1);
+        }
+        KeyCode::Enter if app.active_tab == 0 => app.open_overview_selection(),
+        KeyCode::Char('z') if app.active_tab == 0 => {
+            app.hide_zero_budgets = !app.hide_zero_budgets;
+            app.overview_index = 0;
+            app.save_ui_state();
+        }
+        KeyCode::Char('Z') if app.active_tab == 0 => {
+            app.hide_unbudgeted = !app.hide_unbudgeted;
+            app.save_ui_state();
+        }
+        KeyCode::Char('e') if app.active_tab == 0 => {
+            app.expand_subcategories = !app.expand_subcategories;
+            app.save_ui_state();
+        }
+        KeyCode::Char('D') if app.active_tab == 0 => {
+            return Ok(Transition::To(ActiveForm::Prompt(
+                PromptForm::overview_range(app.overview_range),
+            )));
+        }
+        KeyCode::Char('v') if app.active_tab == 0 => {
+            app.show_remaining = !app.show_remaining;
+            app.save_ui_state();
+        }
+        KeyCode::Esc if app.reconcile.is_some() => {
+            app.reconcile = None;
+            app.last_message = "Finished reconciling".into();
+        }
+        KeyCode::Char('C') => {
+            return Ok(Transition::To(ActiveForm::Prompt(PromptForm::reconcile())));
+        }
+        KeyCode::Char('f') if app.active_tab == 1 => {
+            if let Some(tx) = app.selected_transaction() {
+                // Tab-separated so it pastes into separate spreadsheet cells.
+                let line = format!(
+                    "{}\t{}\t{}\t{:.2}",
+                    tx.date, tx.description, tx.category, tx.amount
+                );
+                app.last_message = match clipboard::write_text(&line) {
+                    Ok(()) => "Copied to clipboard".into(),
+                    Err(err) => format!("Copy failed: {err}"),
+                };
+            }
+        }
+        KeyCode::Char('e') if app.active_tab == 1 => {
+            if let Some(tx) = app.selected_transaction() {
+                if app.ledger.is_from_archive(tx.id) {
+                    app.last_message = ARCHIVED_READ_ONLY.into();
+                    return Ok(Transition::Stay);
+                }
+                return Ok(Transition::To(ActiveForm::Transaction(TxForm::edit(tx))));
+            }
+        }
+        KeyCode::Char('w') if app.active_tab == 1 => {
+            if let Some(id) = app.selected_transaction().map(|t| t.id) {
+                match app.ledger.toggle_business(id) {
+                    Some(business) => {
+                        app.record("set_business", json!({ "id": id, "business": business }));
+                        app.last_message = if business {
+                            "Marked as business".into()
+                        } else {
+                            "Marked as personal".into()
+                        };
+                        app.save().ok();
+                    }
+                    None => app.last_message = ARCHIVED_READ_ONLY.into(),
+                }
+            }
+        }
+        KeyCode::Char(c @ ('I' | 'E')) if app.active_tab == 1 => {
+            let kind = if c == 'I' {
+                TxKind::Income
+            } else {
+                TxKind::Expense
+            };
+            app.kind_filter = (app.kind_filter != Some(kind)).then_some(kind);
+            app.select_tx(0);
+        }
+        KeyCode::Char('A') if app.active_tab == 1 => {
+            if let Some(tx) = app.selected_transaction() {
+                if app.ledger.is_from_archive(tx.id) {
+                    app.last_message = ARCHIVED_READ_ONLY.into();
+                    return Ok(Transition::Stay);
+                }
+                return Ok(Transition::To(ActiveForm::Prompt(PromptForm::amortize(tx))));
+            }
+        }
+        KeyCode::Char('t') if app.active_tab == 1 => {
+            if let Some(tx) = app.selected_transaction() {
+                let template = NewTransaction {
+                    description: tx.description.clone(),
+                    amount: tx.amount,
+                    category: tx.category.clone(),
+                    date: tx.date,
+                    note: String::new(),
+                };
+                app.record("save_template", json!({ "template": template }));
+                app.last_message = format!("Saved \"{}\" as a template", template.description);
+                app.ledger.save_template(template);
+                app.save().ok();
+            }
+        }
+        KeyCode::Char('j') | KeyCode::Down if app.active_tab == 5 => {
+            let last = app.ledger.templates.len().saturating_sub(1);
+            app.selected_template = (app.selected_template + 1).min(last);
+        }
+        KeyCode::Char('k') | KeyCode::Up if app.active_tab == 5 => {
+            app.selected_template = app.selected_template.saturating_sub(1);
+        }
+        KeyCode::Enter if app.active_tab == 5 => {
+            if let Some(template) = app.ledger.templates.get(app.selected_template) {
+                let mut tx = template.clone();
+                tx.date = Local::now().naive_local().date();
+                return Ok(Transition::To(ActiveForm::Transaction(TxForm::prefilled(
+                    "Add from template",
+                    &tx,
+                ))));
+            }
+        }
+        KeyCode::Char('e') if app.active_tab == 5 => {
+            if let Some(template) = app.ledger.templates.get(app.selected_template) {
+                let mut form = TxForm::prefilled("Edit template", template);
+                form.template_index = Some(app.selected_template);
+                form.index = 0;
+                return Ok(Transition::To(ActiveForm::Transaction(form)));
+            }
+        }
+        KeyCode::Char('d') if app.active_tab == 5 => {
+            if let Some(template) = app.ledger.remove_template(app.selected_template) {
+                app.record("remove_template", json!({ "template": template }));
+                let last = app.ledger.templates.len().saturating_sub(1);
+                app.selected_template = app.selected_template.min(last);
+                app.last_message = format!("Deleted template \"{}\"", template.description);
+                app.save().ok();
+            }
+        }
+        KeyCode::Char(c @ ('[' | ']' | '{' | '}')) if app.active_tab == 6 => {
+            let month = if matches!(c, '[' | ']') {
+                &mut app.compare_a
+            } else {
+                &mut app.compare_b
+            };
+            *month = if matches!(c, '[' | '{') {
+                *month - Months::new(1)
+            } else {
+                *month + Months::new(1)
+            };
+        }
+        KeyCode::Char('j') | KeyCode::Down if app.active_tab == 4 => {
+            let last = app.ledger.archived.len().saturating_sub(1);
+            app.selected_archived = (app.selected_archived + 1).min(last);
+        }
+        KeyCode::Char('k') | KeyCode::Up if app.active_tab == 4 => {
+            app.selected_archived = app.selected_archived.saturating_sub(1);
+        }
+        KeyCode::Char('u') if app.active_tab == 4 => {
+            if let Some(id) = app.ledger.archived.get(app.selected_archived).map(|t| t.id) {
+                app.ledger.restore_archived(id);
+                app.record("restore_transaction", json!({ "id": id }));
+                let last = app.ledger.archived.len().saturating_sub(1);
+                app.selected_archived = app.selected_archived.min(last);
+                app.last_message = "Restored transaction".into();
+                app.save().ok();
+            }
+        }
+        KeyCode::Char('X') if app.active_tab == 4 && !app.ledger.archived.is_empty() => {
+            return Ok(Transition::To(ActiveForm::Confirm(
+                ConfirmAction::PurgeArchive,
+            )));
+        }
+        KeyCode::Esc if app.pending_refund.is_some() => {
+            app.pending_refund = None;
+            app.last_message = "Cancelled refund link".into();
+        }
+        KeyCode::Esc if app.active_tab == 1 && !app.filter.is_empty() => {
+            app.filter.clear();
+            app.select_tx(0);
+            app.last_message = "Cleared search".into();
+        }
+        KeyCode::Char('j') | KeyCode::Down if app.active_tab == 2 => {
+            let last = app.ledger.budgets.len().saturating_sub(1);
+            app.selected_budget = (app.selected_budget + 1).min(last);
+        }
+        KeyCode::Char('k') | KeyCode::Up if app.active_tab == 2 => {
+            app.selected_budget = app.selected_budget.saturating_sub(1);
+        }
+        KeyCode::Char('J') | KeyCode::Char('K') if app.active_tab == 2 => {
+            let up = key.code == KeyCode::Char('K');
+            app.selected_budget = app.ledger.move_budget(app.selected_budget, up);
+            if let Some(budget) = app.ledger.budgets.get(app.selected_budget) {
+                app.record(
+                    "move_budget",
+                    json!({ "category": budget.category, "position": app.selected_budget }),
+                );
+            }
+            app.save().ok();
+        }
+        KeyCode::Enter if app.active_tab == 2 => {
+            if let Some(budget) = app.ledger.budgets.get(app.selected_budget) {
+                return Ok(Transition::To(ActiveForm::Budget(BudgetForm::edit(budget))));
+            }
+        }
+        KeyCode::Char('d') if app.active_tab == 2 => {
+            if let Some(budget) = app.ledger.budgets.get(app.selected_budget) {
+                return Ok(Transition::To(ActiveForm::Confirm(
+                    ConfirmAction::DeleteBudget(budget.id),
+                )));
+            }
+        }
+        KeyCode::Char('w') if app.active_tab == 2 => {
+            return Ok(Transition::To(ActiveForm::Prompt(
+                PromptForm::simulate_budgets(&app.ledger),
+            )));
+        }
+        KeyCode::Esc if app.active_tab == 2 && app.simulation.is_some() => {
+            app.simulation = None;
+        }
+        KeyCode::Char(c @ ('A' | 'O')) if app.active_tab == 2 => {
+            let overwrite = c == 'O';
+            return Ok(Transition::To(ActiveForm::Confirm(
+                ConfirmAction::ApplySuggestions { overwrite },
+            )));
+        }
+        KeyCode::Char('[') if app.active_tab == 2 => {
+            app.suggestion_window_days = app.suggestion_window_days.saturating_sub(30).max(30);
+        }
+        KeyCode::Char(']') if app.active_tab == 2 => {
+            app.suggestion_window_days = (app.suggestion_window_days + 30).min(365);
+        }
+        KeyCode::Char('-') if app.active_tab == 2 => {
+            app.suggestion_buffer = (app.suggestion_buffer - 0.05).max(0.0);
+        }
+        KeyCode::Char('+') if app.active_tab == 2 => {
+            app.suggestion_buffer = (app.suggestion_buffer + 0.05).min(1.0);
+        }
+        KeyCode::Char('=') => app.calculator = Some(String::new()),
+        KeyCode::Char('P') if app.active_tab == 2 => {
+            app.selected_budget = app.ledger.toggle_budget_pin(app.selected_budget);
+            if let Some(budget) = app.ledger.budgets.get(app.selected_budget) {
+                app.record(
+                    "pin_budget",
+                    json!({ "category": budget.category, "pinned": budget.pinned }),
+                );
+            }
+            app.save().ok();
+        }
+        KeyCode::Char('p') => {
+            let text = match clipboard::read_text() {
+                Ok(text) => text,
+                Err(err) => {
+                    app.last_message = format!("Paste failed: {err}");
+                    return Ok(Transition::Stay);
+                }
+            };
+            let lines = text.lines().filter(|l| !l.trim().is_empty()).count();
+            if lines > 1 {
+                // A block of lines is imported in bulk after a summary instead of one form.
+                let block = input::parse_pasted_block(&text);
+                match block.failed.first() {
+                    Some(first) if block.parsed.is_empty() => {
+                        app.last_message = format!(
+                            "Paste failed: none of the {lines} lines parsed (line {}: {})",
+                            first.line, first.error
+                        );
+                    }
+                    _ => {
+                        app.pasted = Some(block);
+                        return Ok(Transition::To(ActiveForm::Confirm(
+                            ConfirmAction::ImportPasted,
+                        )));
+                    }
+                }
+            } else {
+                match input::parse_pasted_text(&text) {
+                    Ok(tx) => {
+                        app.last_message = "Review the pasted transaction, Enter to add".into();
+                        return Ok(Transition::To(ActiveForm::Transaction(TxForm::prefilled(
+                            "Confirm pasted transaction",
+                            &tx,
+                        ))));
+                    }
+                    Err(err) => app.last_message = format!("Paste failed: {err}"),
+                }
+            }
+        }
+        KeyCode::Char('s') => {
+            app.save().ok();
+        }
+        KeyCode::Char('g') => {
+            app.show_suggestions = !app.show_suggestions;
+            app.save_ui_state();
+        }
+        KeyCode::Char('x') => {
+            return Ok(Transition::To(ActiveForm::Prompt(PromptForm::export(
+                &app.filter,
+            ))));
+        }
+        KeyCode::Char('m') => return Ok(Transition::To(ActiveForm::Prompt(PromptForm::import()))),
+        KeyCode::Char('W') => {
+            return Ok(Transition::To(ActiveForm::Prompt(
+                PromptForm::switch_profile(app.storage.profile()),
+            )));
+        }
+        KeyCode::Char('L') => {
+            let account = app.current_account.and_then(|id| app.ledger.account(id));
+            return Ok(Transition::To(ActiveForm::Prompt(
+                PromptForm::export_register(account),
+            )));
+        }
+        KeyCode::Char('Y') => {
+            return Ok(Transition::To(ActiveForm::Prompt(
+                PromptForm::archive_year(),
+            )));
+        }
+        KeyCode::Char('y') if app.ledger.history_loaded() => {
+            app.ledger.drop_history();
+            app.forget_undo();
+            app.select_tx(0);
+            app.last_message = "Archived years unloaded".into();
+        }
+        KeyCode::Char('y') => {
+            let history = app.storage.load_history()?;
+            let added = app.ledger.merge_history(history);
+            app.forget_undo();
+            app.last_message = if added == 0 {
+                "No archived years to load (Y archives a finished year)".into()
+            } else {
+                format!("Loaded {added} archived transactions for reports (y to unload)")
+            };
+        }
+        KeyCode::Char('T') => {
+            return Ok(Transition::To(ActiveForm::Prompt(
+                PromptForm::export_business(),
+            )));
+        }
+        KeyCode::Char('H') => return Ok(Transition::To(ActiveForm::Prompt(PromptForm::report()))),
+        KeyCode::Char('B') => {
+            return Ok(Transition::To(ActiveForm::Prompt(
+                PromptForm::balance_as_of(),
+            )));
+        }
+        KeyCode::Char('n') => {
+            return Ok(Transition::To(ActiveForm::Prompt(
+                PromptForm::add_annotation(),
+            )));
+        }
+        KeyCode::Char('/') => {
+            app.active_tab = 1;
+            return Ok(Transition::To(ActiveForm::Search));
+        }
+        KeyCode::Char('r') => {
+            app.reload()?;
+            app.last_message = "Reloaded data".into();
+        }
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            return Ok(Transition::Quit);
+        }
+        _ => {}
+    }
+    Ok(Transition::Stay)
+}
+
+/// Typing edits `App::filter` live; Enter keeps the filter, Esc clears it.
+fn handle_search_key(app: &mut App, key: KeyEvent) -> Transition {
+    match key.code {
+        KeyCode::Esc => {
+            app.filter.clear();
+            app.select_tx(0);
+            app.last_message = "Cleared search".into();
+            return Transition::Close;
+        }
+        KeyCode::Enter => return Transition::Close,
+        KeyCode::Backspace => {
+            app.filter.pop();
+        }
+        KeyCode::Char(c) => app.filter.push(c),
+        _ => return Transition::Stay,
+    }
+    // The matches change with every keystroke; start again from the newest one.
+    app.select_tx(0);
+    Transition::Stay
+}
+
+/// Keys while the calculator popup is open. Enter puts the result into the amount (or limit)
+/// field of the open form, or just shows it when there is none.
+fn handle_calculator_key(app: &mut App, key: KeyEvent) {
+    let Some(expression) = app.calculator.as_mut() else {
+        return;
+    };
+    match key.code {
+        KeyCode::Esc => app.calculator = None,
+        KeyCode::Backspace => {
+            expression.pop();
+        }
+        KeyCode::Delete | KeyCode::Char('c') => expression.clear(),
+        KeyCode::Char(c) if c.is_ascii_digit() || "+-*/().$ ".contains(c) => expression.push(c),
+        KeyCode::Enter => match input::evaluate_expression(expression) {
+            Ok(value) => {
+                let result = format!("{value:.2}");
+                match &mut app.form {
+                    ActiveForm::Transaction(form) => form.fields[1].value = result,
+                    ActiveForm::Budget(form) => form.fields[1].value = result,
+                    _ => app.last_message = format!("= {result}"),
+                }
+                app.calculator = None;
+            }
+            Err(err) => app.last_message = format!("Calculator: {err}"),
+        },
+        _ => {}
+    }
+}
+
+fn draw(f: &mut ratatui::Frame, app: &App) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Min(10),
+            Constraint::Length(footer_height(app, f.size().width)),
+        ])
+        .split(f.size());
+
+    let top = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Length(3)])
+        .split(layout[0]);
+
+    render_header(f, top[0], app);
+
+    let tab_titles = TAB_TITLES
+        .iter()
+        .map(|t| Line::from(*t))
+        .collect::<Vec<_>>();
+    let tabs = Tabs::new(tab_titles)
+        .block(Block::default().borders(Borders::BOTTOM))
+        .highlight_style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+        .select(app.active_tab);
+    f.render_widget(tabs, top[1]);
+
+    match app.active_tab {
+        0 => render_overview(f, layout[1], app),
+        1 => render_transactions(f, layout[1], app),
+        2 => render_budgets(f, layout[1], app),
+        3 => render_fiscal_year(f, layout[1], app),
+        4 => render_archive(f, layout[1], app),
+        5 => render_templates(f, layout[1], app),
+        6 => render_comparison(f, layout[1], app),
+        7 => render_envelopes(f, layout[1], app),
+        _ => render_trends(f, layout[1], app),
+    }
+
+    render_footer(f, layout[2], app);
+
+    if let ActiveForm::Wizard(wizard) = &app.form {
+        let currency = wizard.currency.as_ref().unwrap_or(&app.config.currency);
+        render_wizard(f, f.size(), wizard, currency);
+    }
+    if let Some(expression) = &app.calculator {
+        render_calculator(f, f.size(), expression);
+    }
+}
+
+/// The expression being typed with its live result, or why it doesn't evaluate yet.
+fn render_calculator(f: &mut ratatui::Frame, area: Rect, expression: &str) {
+    let popup = centered_rect(area, 44, 6);
+    let result = if expression.trim().is_empty() {
+        Line::from(Span::styled(
+            "e.g. 42.80 + 3*4.50 - 10",
+            Style::default().fg(Color::DarkGray),
+        ))
+    } else {
+        match input::evaluate_expression(expression) {
+            Ok(value) => Line::from(Span::styled(
+                format!("= {value:.2}"),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            )),
+            Err(err) => Line::from(Span::styled(
+                err.to_string(),
+                Style::default().fg(Color::Red),
+            )),
+        }
+    };
+    let lines = vec![
+        Line::from(format!("{expression}_")),
+        result,
+        Line::from("Enter: use result   c: clear   Esc: close"),
+    ];
+    let block = Block::default()
+        .title("Calculator")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+/// A labelled single-field input on a setup wizard step.
+fn wizard_input_line(label: &'static str, value: &str) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(
+            label,
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(": "),
+        Span::raw(value.to_string()),
+    ])
+}
+
+fn render_wizard(f: &mut ratatui::Frame, area: Rect, wizard: &Wizard, currency: &CurrencyFormat) {
+    let popup = centered_rect(area, 64, 18);
+    let heading = Style::default()
+        .fg(Color::Cyan)
+        .add_modifier(Modifier::BOLD);
+    let mut lines = match wizard.step {
+        WizardStep::Welcome => vec![
+            Line::from(Span::styled("Welcome to centsh", heading)),
+            Line::from(" "),
+            Line::from("No ledger was found, so let's set one up."),
+            Line::from(" "),
+            Line::from("Enter: guided setup (currency, an account, balance, budgets)"),
+            Line::from("s: skip and explore sample data"),
+            Line::from("e: skip and start with an empty ledger"),
+        ],
+        WizardStep::Currency => vec![
+            Line::from(Span::styled("Currency", heading)),
+            Line::from("Which currency do you budget in? Type a code such as USD, EUR,"),
+            Line::from("GBP, JPY or CHF, or any symbol to show before amounts."),
+            Line::from(" "),
+            wizard_input_line("Currency", &wizard.currency_input),
+            Line::from(" "),
+            Line::from("Enter: next (blank for $)   Esc: skip"),
+        ],
+        WizardStep::Account => vec![
+            Line::from(Span::styled("First account", heading)),
+            Line::from("Name the account you spend from most, e.g. Checking. New"),
+            Line::from("transactions are recorded against it; add more later with N."),
+            Line::from(" "),
+            wizard_input_line("Account", &wizard.account_name),
+            Line::from(" "),
+            Line::from("Enter: next (blank to skip)   Esc: skip"),
+        ],
+        WizardStep::OpeningBalance => vec![
+            Line::from(Span::styled("Starting balance", heading)),
+            Line::from(match wizard.account {
+                Some(_) => "How much money is in that account right now?",
+                None => "How much money do you have right now, across your accounts?",
+            }),
+            Line::from("It becomes the opening balance, so past paychecks don't need"),
+            Line::from("logging; it never counts as income."),
+            Line::from(" "),
+            wizard_input_line("Balance", &wizard.opening_balance),
+            Line::from(" "),
+            Line::from("Enter: next (blank to skip)   Esc: skip"),
+        ],
+        WizardStep::Budgets => {
+            let mut lines = vec![
+                Line::from(Span::styled("Monthly budgets", heading)),
+                Line::from("Add a limit for each category you want to track."),
+                Line::from(" "),
+            ];
+            for budget in &wizard.ledger.budgets {
+                lines.push(Line::from(format!(
+                    "  {}: {}",
+                    budget.category,
+                    format_currency(budget.monthly_limit, currency)
+                )));
+            }
+            lines.push(Line::from(" "));
+            for (i, field) in wizard.budget.fields.iter().enumerate() {
+                let style = if i == wizard.budget.index {
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(field.label, style),
+                    Span::raw(": "),
+                    Span::raw(field.value.clone()),
+                ]));
+            }
+            lines.push(Line::from(" "));
+            lines.push(Line::from(
+                "Enter: next/add budget   Tab: next   Esc: finish",
+            ));
+            lines
+        }
+        WizardStep::Done => vec![
+            Line::from(Span::styled("All set", heading)),
+            Line::from(" "),
+            Line::from(format!(
+                "{} budgets ready. Press a to add transactions and / to search them.",
+                wizard.ledger.budgets.len()
+            )),
+            Line::from(" "),
+            Line::from("Enter: start using centsh   Esc: back to budgets"),
+        ],
+    };
+    if !wizard.error.is_empty() {
+        lines.push(Line::from(Span::styled(
+            wizard.error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    let block = Block::default()
+        .title("Setup")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Green));
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        popup,
+    );
+}
+
+/// A `width` x `height` rectangle centred in `area`, clamped to fit.
+fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+fn render_header(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let stats = app.ledger.stats();
+    let span = match (stats.earliest, stats.latest) {
+        (Some(earliest), Some(latest)) => format!("{earliest} → {latest}"),
+        _ => "no dates yet".to_string(),
+    };
+    let header = Paragraph::new(Line::from(vec![
+        Span::styled(
+            "centsh",
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            match app.storage.profile() {
+                Some(profile) => format!("  | profile {profile} (W to switch)"),
+                None => String::new(),
+            },
+            Style::default().fg(Color::Cyan),
+        ),
+        Span::raw(
+            match app.current_account.and_then(|id| app.ledger.account(id)) {
+                Some(account) => format!("  | account {} (o to switch)", account.name),
+                None => String::new(),
+            },
+        ),
+        Span::raw(format!(
+            "  | {} txns, {} categories, {span}",
+            stats.tx_count, stats.category_count
+        )),
+        Span::styled(
+            if app.ledger.history_loaded() {
+                "  | archives loaded"
+            } else {
+                ""
+            },
+            Style::default().fg(Color::Magenta),
+        ),
+        Span::styled(
+            match app.ledger.logging_streak(Local::now().naive_local().date()) {
+                0 => String::new(),
+                days => format!("  | 🔥 {days}-day streak"),
+            },
+            Style::default().fg(Color::Yellow),
+        ),
+        Span::raw("  | data "),
+        Span::styled(
+            app.storage.path().to_string_lossy(),
+            Style::default().fg(Color::Gray),
+        ),
+    ]))
+    .wrap(Wrap { trim: true });
+    f.render_widget(header, area);
+}
+
+fn render_overview(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let ledger = &app.ledger;
+    let today = Local::now().naive_local().date();
+    let range = app
+        .overview_range
+        .unwrap_or_else(|| DateRange::month(today));
+    let overview = match app.overview_range {
+        Some(range) => ledger.overview_for_range(range),
+        None => ledger.current_month_overview(),
+    };
+    let safe = ledger.safe_to_spend(today, app.config.safe_to_spend_basis);
+    let cashflow = ledger.spending_last_n_months(6, &app.config.excluded_categories);
+    let average_spend = ledger.average_monthly_spend(AVERAGE_SPEND_MONTHS, today);
+    let monthly_spend: Vec<f64> = cashflow
+        .iter()
+        .map(|(label, _)| {
+            NaiveDate::parse_from_str(&format!("{label}-01"), "%Y-%m-%d")
+                .map(|month| ledger.month_overview(month).total_outgoing)
+                .unwrap_or(0.0)
+        })
+        .collect();
+
+    let mut stats_title = match app.overview_range {
+        Some(range) => range_label(range),
+        None => "This month".to_string(),
+    };
+    if app.config.accrual {
+        stats_title.push_str(" (accrual)");
+    }
+    let stats_block = Block::default().title(stats_title).borders(Borders::ALL);
+    let mut stats_lines = vec![
+        Line::from(vec![
+            Span::styled(
+                "Safe to spend: ",
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(
+                format_currency(safe.amount, &app.config.currency),
+                styled_net(safe.amount, &app.config.currency)
+                    .style
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ]),
+        Line::from(Span::styled(safe.reason, Style::default().fg(Color::Gray))),
+    ];
+    if let Some(alert) = budget_alert_summary(ledger, today) {
+        stats_lines.push(alert);
+    }
+    stats_lines.extend([
+        Line::from(" "),
+        Line::from(format!(
+            "Income: {}",
+            format_currency(overview.total_income, &app.config.currency)
+        )),
+        spending_vs_average_line(
+            overview.total_outgoing,
+            // A monthly average says little about a range of another length.
+            if range.is_month() { average_spend } else { 0.0 },
+            &app.config.currency,
+        ),
+        Line::from(vec![
+            Span::raw("Net: "),
+            styled_net(overview.net, &app.config.currency),
+        ]),
+        savings_rate_line(ledger.savings_rate(today), app.config.savings_rate_target),
+    ]);
+    if let Some(business) = ledger.business_spending(today.year()) {
+        stats_lines.push(Line::from(format!(
+            "Business expenses {}: {}",
+            today.year(),
+            format_currency(business, &app.config.currency)
+        )));
+    }
+    if !app.config.fixed_categories.is_empty() || !app.config.discretionary_categories.is_empty() {
+        stats_lines.extend(spending_split_lines(ledger.fixed_vs_discretionary(today)));
+    }
+    stats_lines.push(Line::from(" "));
+    let hidden = ledger.budgets.len() - app.overview_budgets().len();
+    if hidden > 0 {
+        stats_lines.push(Line::from(vec![
+            Span::raw("Budgets: "),
+            Span::styled(
+                format!("({hidden} with no spending hidden, z to show)"),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]));
+    } else {
+        stats_lines.push(Line::from("Budgets:"));
+    }
+
+    let mut budget_lines = Vec::new();
+    if let Some(warning) = app
+        .config
+        .low_balance_threshold
+        .and_then(|threshold| ledger.balance_warning(threshold, today))
+    {
+        let what = if warning.projected {
+            "Projected month-end balance"
+        } else {
+            "Balance this month"
+        };
+        budget_lines.push(Line::from(Span::styled(
+            format!(
+                " LOW BALANCE: {what} is {} ",
+                format_currency(warning.amount, &app.config.currency)
+            ),
+            Style::default()
+                .fg(Color::White)
+                .bg(Color::Red)
+                .add_modifier(Modifier::BOLD),
+        )));
+    }
+    budget_lines.extend(stats_lines);
+    let budgets_focused = app.overview_focus == Some(OverviewFocus::Budgets);
+    let mut rows: Vec<Line> = app
+        .overview_budgets()
+        .into_iter()
+        .enumerate()
+        .map(|(i, budget)| {
+            // Weekly, biweekly and yearly budgets count their own current period.
+            let (cat, limit) = (&budget.category, budget.period_limit());
+            let spent = ledger.budget_spent_in(cat, budget.period.containing(today));
+            let pct = if limit > 0.0 {
+                (spent / limit * 100.0).min(999.0)
+            } else {
+                0.0
+            };
+            let remaining = ledger.budget_remaining(cat, today);
+            let name = budget_name(ledger, budget);
+            let left_pct = if limit > 0.0 {
+                remaining / limit * 100.0
+            } else {
+                0.0
+            };
+            let alert = budget_alert_style(pct);
+            let line = match budget.direction {
+                BudgetDirection::SpendCap if app.show_remaining && remaining < 0.0 => {
+                    Line::from(Span::styled(
+                        format!(
+                            "- {}: {} over ({left_pct:.0}%)",
+                            name,
+                            format_currency(remaining, &app.config.currency)
+                        ),
+                        alert,
+                    ))
+                }
+                BudgetDirection::SpendCap if app.show_remaining => Line::from(Span::styled(
+                    format!(
+                        "- {}: {} left ({left_pct:.0}%)",
+                        name,
+                        format_currency(remaining, &app.config.currency)
+                    ),
+                    alert,
+                )),
+                BudgetDirection::SaveTarget if app.show_remaining && remaining > 0.0 => {
+                    Line::from(format!(
+                        "- {}: {} to go ({left_pct:.0}%)",
+                        name,
+                        format_currency(remaining, &app.config.currency)
+                    ))
+                }
+                BudgetDirection::SaveTarget if app.show_remaining => Line::from(Span::styled(
+                    format!("- {}: target met", name),
+                    Style::default().fg(Color::Green),
+                )),
+                BudgetDirection::SpendCap => Line::from(Span::styled(
+                    format!(
+                        "- {}: {} / {} ({pct:.0}%)",
+                        name,
+                        format_currency(spent, &app.config.currency),
+                        format_currency(limit, &app.config.currency)
+                    ),
+                    alert,
+                )),
+                // Money moved into a savings category counts toward its target.
+                BudgetDirection::SaveTarget => {
+                    let style = if spent >= limit {
+                        Style::default().fg(Color::Green)
+                    } else {
+                        Style::default()
+                    };
+                    Line::from(Span::styled(
+                        format!(
+                            "- {}: saved {} of {} target ({pct:.0}%)",
+                            name,
+                            format_currency(spent, &app.config.currency),
+                            format_currency(limit, &app.config.currency)
+                        ),
+                        style,
+                    ))
+                }
+            };
+            if budgets_focused && i == app.overview_index {
+                line.patch_style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                line
+            }
+        })
+        .collect();
+    if rows.is_empty() {
+        rows.push(Line::from("No budgets yet. Press b to add one."));
+    }
+    budget_lines.extend(rows);
+    let stats_block = if budgets_focused {
+        stats_block.border_style(Style::default().fg(Color::Yellow))
+    } else {
+        stats_block
+    };
+    let stats = Paragraph::new(budget_lines).block(stats_block);
+
+    let (panels, charts): (Vec<OverviewWidget>, Vec<OverviewWidget>) = app
+        .config
+        .overview_widgets
+        .iter()
+        .partition(|w| !is_overview_chart(**w));
+    let columns: Vec<Vec<OverviewWidget>> = [panels, charts]
+        .into_iter()
+        .filter(|column| !column.is_empty())
+        .collect();
+    let column_areas = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+            Constraint::Ratio(1, columns.len() as u32);
+            columns.len()
+        ])
+        .split(area);
+    for (column, column_area) in columns.iter().zip(column_areas.iter()) {
+        let rows = overview_widget_rows(column);
+        let row_areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(rows.iter().map(|row| overview_widget_height(row[0])))
+            .split(*column_area);
+        for (row, row_area) in rows.iter().zip(row_areas.iter()) {
+            let cells = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(row.iter().map(|widget| {
+                    match overview_widget_width(*widget).filter(|_| row.len() > 1) {
+                        Some(width) => Constraint::Length(width),
+                        None => Constraint::Min(20),
+                    }
+                }))
+                .split(*row_area);
+            for (widget, cell) in row.iter().zip(cells.iter().copied()) {
+                match widget {
+                    OverviewWidget::Stats => f.render_widget(stats.clone(), cell),
+                    OverviewWidget::TopExpenses => render_top_expenses(f, cell, app, today),
+                    OverviewWidget::IncomeSources => render_income_sources(f, cell, app, today),
+                    OverviewWidget::Calendar => render_spending_calendar(f, cell, ledger, today),
+                    OverviewWidget::UpcomingBills => render_upcoming_bills(f, cell, app, today),
+                    OverviewWidget::CategoryBar => render_category_chart(
+                        f,
+                        cell,
+                        ledger,
+                        app.overview_range,
+                        today,
+                        app.expand_subcategories,
+                        app.hide_unbudgeted,
+                    ),
+                    OverviewWidget::CashflowLine => render_cashflow_chart(
+                        f,
+                        cell,
+                        cashflow.clone(),
+                        &monthly_spend,
+                        average_spend,
+                        &ledger.annotations,
+                    ),
+                    OverviewWidget::Sparkline => render_spending_sparkline(f, cell, ledger, today),
+                }
+            }
+        }
+    }
+}
+
+/// Charts go in the overview's right column, everything else in the left.
+fn is_overview_chart(widget: OverviewWidget) -> bool {
+    matches!(
+        widget,
+        OverviewWidget::CategoryBar | OverviewWidget::CashflowLine | OverviewWidget::Sparkline
+    )
+}
+
+fn overview_widget_height(widget: OverviewWidget) -> Constraint {
+    match widget {
+        OverviewWidget::Stats => Constraint::Fill(1),
+        OverviewWidget::TopExpenses | OverviewWidget::IncomeSources => {
+            Constraint::Length(TOP_EXPENSES as u16 + 2)
+        }
+        OverviewWidget::Calendar | OverviewWidget::UpcomingBills => Constraint::Length(9),
+        OverviewWidget::CategoryBar => Constraint::Fill(11),
+        OverviewWidget::CashflowLine => Constraint::Fill(9),
+        OverviewWidget::Sparkline => Constraint::Length(6),
+    }
+}
+
+/// Fixed width when sharing a row; the calendar needs exactly this much for its grid.
+fn overview_widget_width(widget: OverviewWidget) -> Option<u16> {
+    match widget {
+        OverviewWidget::IncomeSources | OverviewWidget::Calendar => Some(30),
+        _ => None,
+    }
+}
+
+/// Splits one column's widgets into rows: neighbours with the same fixed height sit side
+/// by side (top expenses beside income sources, the calendar beside upcoming bills).
+fn overview_widget_rows(column: &[OverviewWidget]) -> Vec<Vec<OverviewWidget>> {
+    let mut rows: Vec<Vec<OverviewWidget>> = Vec::new();
+    for widget in column {
+        let height = overview_widget_height(*widget);
+        match rows.last_mut() {
+            Some(row)
+                if matches!(height, Constraint::Length(_))
+                    && overview_widget_height(row[0]) == height =>
+            {
+                row.push(*widget)
+            }
+            _ => rows.push(vec![*widget]),
+        }
+    }
+    rows
+}
+
+/// The month's spending per day, first to last day so far, as a sparkline.
+fn render_spending_sparkline(
+    f: &mut ratatui::Frame,
+    area: Rect,
+    ledger: &Ledger,
+    today: NaiveDate,
+) {
+    let days = ledger.daily_spending_map(today);
+    let data: Vec<u64> = (1..=today.day())
+        .filter_map(|day| today.with_day(day))
+        .map(|date| days.get(&date).copied().unwrap_or(0.0).max(0.0) as u64)
+        .collect();
+    let sparkline = Sparkline::default()
+        .block(
+            Block::default()
+                .title(format!("Daily spend ({})", today.format("%B")))
+                .borders(Borders::ALL),
+        )
+        .data(&data)
+        .style(Style::default().fg(Color::Yellow));
+    f.render_widget(sparkline, area);
+}
+
+/// This month's largest expenses; selectable when the panel has focus.
+fn render_top_expenses(f: &mut ratatui::Frame, area: Rect, app: &App, today: NaiveDate) {
+    let focused = app.overview_focus == Some(OverviewFocus::TopExpenses);
+    let rows: Vec<Row> = app
+        .ledger
+        .top_expenses(today, TOP_EXPENSES)
+        .into_iter()
+        .map(|tx| {
+            Row::new(vec![
+                Cell::from(tx.date.format("%m-%d").to_string()),
+                Cell::from(tx.description.clone()),
+                Cell::from(styled_amount(tx.amount, &app.config.currency)),
+            ])
+        })
+        .collect();
+    let mut block = Block::default()
+        .title("Top expenses (Tab to focus, Enter to open)")
+        .borders(Borders::ALL);
+    if focused {
+        block = block.border_style(Style::default().fg(Color::Yellow));
+    }
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(6),
+            Constraint::Min(10),
+            Constraint::Length(12),
+        ],
+    )
+    .block(block)
+    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = TableState::default().with_selected(focused.then_some(app.overview_index));
+    f.render_stateful_widget(table, area, &mut state);
+}
+
+/// The month's income per category with each one's share of the total.
+fn render_income_sources(f: &mut ratatui::Frame, area: Rect, app: &App, today: NaiveDate) {
+    let sources = app
+        .ledger
+        .income_by_category(today, &app.config.excluded_categories);
+    let total: f64 = sources.iter().map(|(_, amount)| amount).sum();
+    let mut lines: Vec<Line> = sources
+        .iter()
+        .map(|(category, amount)| {
+            Line::from(vec![
+                Span::raw(format!("{category}: ")),
+                Span::styled(
+                    format_currency(*amount, &app.config.currency),
+                    Style::default().fg(Color::Green),
+                ),
+                Span::raw(format!(" ({:.0}%)", amount / total * 100.0)),
+            ])
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Line::from("No income yet this month."));
+    }
+    let list = Paragraph::new(lines).block(
+        Block::default()
+            .title("Income sources")
+            .borders(Borders::ALL),
+    );
+    f.render_widget(list, area);
+}
+
+/// This month's spending with how it compares to the trailing average.
+fn spending_vs_average_line(spent: f64, average: f64, currency: &CurrencyFormat) -> Line<'static> {
+    let mut spans = vec![Span::raw(format!(
+        "Spending: {}",
+        format_currency(spent, currency)
+    ))];
+    if average > 0.0 {
+        let change = (spent - average) / average * 100.0;
+        let color = if change > 0.0 {
+            Color::Red
+        } else {
+            Color::Green
+        };
+        spans.push(Span::styled(
+            format!(
+                " ({change:+.0}% vs {AVERAGE_SPEND_MONTHS}-month avg {})",
+                format_currency(average, currency)
+            ),
+            Style::default().fg(color),
+        ));
+    }
+    Line::from(spans)
+}
+
+fn savings_rate_line(rate: Option<f64>, target: f64) -> Line<'static> {
+    let target_label = format!(" (target {:.0}%)", target * 100.0);
+    match rate {
+        Some(rate) => {
+            let color = if rate >= target {
+                Color::Green
+            } else if rate >= 0.0 {
+                Color::Yellow
+            } else {
+                Color::Red
+            };
+            Line::from(vec![
+                Span::raw("Savings rate: "),
+                Span::styled(format!("{:.0}%", rate * 100.0), Style::default().fg(color)),
+                Span::styled(target_label, Style::default().fg(Color::Gray)),
+            ])
+        }
+        None => Line::from(vec![
+            Span::raw("Savings rate: n/a (no income yet)"),
+            Span::styled(target_label, Style::default().fg(Color::Gray)),
+        ]),
+    }
+}
+
+/// Spending caps at or over their limit in red and bold, within 20% of it in yellow.
+fn budget_alert_style(pct: f64) -> Style {
+    if pct >= 100.0 {
+        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+    } else if pct >= NEAR_LIMIT * 100.0 {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    }
+}
+
+/// "2 budgets over, 1 near its limit" across every spending cap (whatever the overview
+/// filters hide), or `None` when all are under 80%.
+fn budget_alert_summary(ledger: &Ledger, today: NaiveDate) -> Option<Line<'static>> {
+    let (mut over, mut near) = (0, 0);
+    for budget in &ledger.budgets {
+        let limit = budget.period_limit();
+        if budget.direction != BudgetDirection::SpendCap || limit <= 0.0 {
+            continue;
+        }
+        let used = (limit - ledger.budget_remaining(&budget.category, today)) / limit;
+        if used >= 1.0 {
+            over += 1;
+        } else if used >= NEAR_LIMIT {
+            near += 1;
+        }
+    }
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    let mut spans = Vec::new();
+    if over > 0 {
+        spans.push(Span::styled(
+            format!("{over} budget{} over", plural(over)),
+            budget_alert_style(100.0),
+        ));
+    }
+    if near > 0 {
+        if !spans.is_empty() {
+            spans.push(Span::raw(", "));
+        }
+        spans.push(Span::styled(
+            format!("{near} near the limit"),
+            budget_alert_style(NEAR_LIMIT * 100.0),
+        ));
     }
+    (!spans.is_empty()).then(|| Line::from(spans))
+}
 
-    Ok(false)
+/// Two-segment bar of fixed vs discretionary spending with their shares, plus whatever
+/// falls in neither on its own line.
+fn spending_split_lines(split: SpendingSplit) -> Vec<Line<'static>> {
+    const BAR_WIDTH: usize = 20;
+    let classified = split.fixed + split.discretionary;
+    if classified <= 0.0 && split.unclassified <= 0.0 {
+        return Vec::new();
+    }
+    let mut lines = Vec::new();
+    if classified > 0.0 {
+        let fixed_share = (split.fixed / classified).clamp(0.0, 1.0);
+        let fixed_cells = ((fixed_share * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+        lines.push(Line::from(vec![
+            Span::styled("█".repeat(fixed_cells), Style::default().fg(Color::Blue)),
+            Span::styled(
+                "█".repeat(BAR_WIDTH.saturating_sub(fixed_cells)),
+                Style::default().fg(Color::Magenta),
+            ),
+        ]));
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("Fixed {:.0}%", fixed_share * 100.0),
+                Style::default().fg(Color::Blue),
+            ),
+            Span::raw("  "),
+            Span::styled(
+                format!("Discretionary {:.0}%", (1.0 - fixed_share) * 100.0),
+                Style::default().fg(Color::Magenta),
+            ),
+        ]));
+    }
+    if split.unclassified > 0.0 {
+        lines.push(Line::from(Span::styled(
+            format!(
+                "Unclassified {:.0}% of spending",
+                split.unclassified / split.total() * 100.0
+            ),
+            Style::default().fg(Color::Gray),
+        )));
+    }
+    lines
 }
 
-fn draw(f: &mut ratatui::Frame, app: &App) {
-    let layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(4),
-            Constraint::Min(10),
-            Constraint::Length(5),
-        ])
-        .split(f.size());
+/// Month grid with one cell per day, shaded by how that day's spending ranks in the month.
+fn render_spending_calendar(f: &mut ratatui::Frame, area: Rect, ledger: &Ledger, month: NaiveDate) {
+    const SHADES: [Color; 5] = [
+        Color::DarkGray,
+        Color::Indexed(22),
+        Color::Indexed(28),
+        Color::Indexed(34),
+        Color::Indexed(40),
+    ];
+    let days = ledger.daily_spending_map(month);
+    let mut ranked: Vec<f64> = days.values().copied().filter(|v| *v > 0.0).collect();
+    ranked.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    // Quartile of the day among spending days: 1 (lightest) to 4 (heaviest).
+    let shade = |amount: f64| {
+        if amount <= 0.0 || ranked.is_empty() {
+            return SHADES[0];
+        }
+        let below = ranked.partition_point(|v| *v < amount);
+        SHADES[1 + below * 4 / ranked.len()]
+    };
 
-    let top = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Length(1), Constraint::Length(3)])
-        .split(layout[0]);
+    let first = month.with_day(1).unwrap_or(month);
+    let mut lines = vec![Line::from(Span::styled(
+        " Mo  Tu  We  Th  Fr  Sa  Su",
+        Style::default().fg(Color::Gray),
+    ))];
+    // Blank cells before the 1st so each column is a real weekday.
+    let mut week: Vec<Span> = (0..first.weekday().num_days_from_monday())
+        .map(|_| Span::raw("    "))
+        .collect();
+    let mut day = first;
+    while day.month() == first.month() {
+        let amount = days.get(&day).copied().unwrap_or(0.0);
+        week.push(Span::styled(
+            format!("{:>3} ", day.day()),
+            Style::default().bg(shade(amount)).fg(Color::White),
+        ));
+        if week.len() == 7 {
+            lines.push(Line::from(std::mem::take(&mut week)));
+        }
+        match day.succ_opt() {
+            Some(next) => day = next,
+            None => break,
+        }
+    }
+    if !week.is_empty() {
+        lines.push(Line::from(week));
+    }
 
-    render_header(f, top[0], app);
+    let title = format!("Daily spending ({})", first.format("%B"));
+    let calendar = Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL));
+    f.render_widget(calendar, area);
+}
 
-    let tab_titles = ["Overview", "Transactions", "Budgets"]
+/// Bars per parent category, or with `expanded` per category including subcategories. With
+/// `budgeted_only`, spending no budget covers is left out of the chart.
+fn render_category_chart(
+    f: &mut ratatui::Frame,
+    area: Rect,
+    ledger: &Ledger,
+    range: Option<DateRange>,
+    today: NaiveDate,
+    expanded: bool,
+    budgeted_only: bool,
+) {
+    const GAP: u16 = 1;
+    const MIN_BAR: u16 = 4;
+    const MAX_BARS: u16 = 8;
+    const ANNOTATED_BARS: usize = 3;
+
+    // Fit as many bars as the inner width allows, then widen them to fill the space.
+    let inner = area.width.saturating_sub(2);
+    let bars = ((inner + GAP) / (MIN_BAR + GAP)).clamp(1, MAX_BARS);
+    let cat_spend = ledger.top_categories_with_other(
+        range.unwrap_or_else(|| DateRange::month(today)),
+        bars as usize,
+        !expanded,
+        budgeted_only,
+    );
+    let shown = (cat_spend.len() as u16).max(1);
+    let bar_width = ((inner.saturating_sub(GAP * (shown - 1))) / shown).clamp(MIN_BAR, 12);
+
+    // The biggest few bars get a ▲/▼ change from last month after their label, when the
+    // label can spare the room and last month had spending to compare with.
+    let deltas =
+        ledger.category_deltas(range.unwrap_or_else(|| DateRange::month(today)), !expanded);
+    let data: Vec<Bar> = cat_spend
         .iter()
-        .map(|t| Line::from(*t))
-        .collect::<Vec<_>>();
-    let tabs = Tabs::new(tab_titles)
-        .block(Block::default().borders(Borders::BOTTOM))
-        .highlight_style(
-            Style::default()
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
+        .enumerate()
+        .map(|(i, (cat, amt))| {
+            let marker = deltas
+                .get(cat)
+                .filter(|_| i < ANNOTATED_BARS)
+                .map(|pct| {
+                    let (arrow, color) = if *pct >= 0.0 {
+                        ("▲", Color::Red)
+                    } else {
+                        ("▼", Color::Green)
+                    };
+                    (format!("{arrow}{:.0}%", pct.abs()), color)
+                })
+                .filter(|(text, _)| text.chars().count() + 2 <= bar_width as usize);
+            let label = match marker {
+                Some((text, color)) => {
+                    let room = bar_width as usize - text.chars().count();
+                    Line::from(vec![
+                        Span::raw(truncate_label(cat, room)),
+                        Span::styled(text, Style::default().fg(color)),
+                    ])
+                }
+                None => Line::from(truncate_label(cat, bar_width as usize)),
+            };
+            Bar::default().value(amt.max(0.0) as u64).label(label)
+        })
+        .collect();
+
+    let chart = BarChart::default()
+        .block(
+            Block::default()
+                .title(format!(
+                    "Category spend ({}{}{})",
+                    range.map_or_else(|| "this month".to_string(), range_label),
+                    if expanded { ", subcategories" } else { "" },
+                    if budgeted_only { ", budgeted only" } else { "" },
+                ))
+                .borders(Borders::ALL),
         )
-        .select(app.active_tab);
-    f.render_widget(tabs, top[1]);
+        .bar_width(bar_width)
+        .bar_gap(GAP)
+        .data(BarGroup::default().bars(&data))
+        .value_style(Style::default().fg(Color::Yellow))
+        .label_style(Style::default().fg(Color::White));
+    f.render_widget(chart, area);
+}
 
-    match app.active_tab {
-        0 => render_overview(f, layout[1], &app.ledger),
-        1 => render_transactions(f, layout[1], &app.ledger),
-        _ => render_budgets(f, layout[1], &app.ledger, app.show_suggestions),
+/// Shortens `label` to `width` characters, marking the cut with an ellipsis.
+fn truncate_label(label: &str, width: usize) -> String {
+    if label.chars().count() <= width {
+        return label.to_string();
     }
+    let mut short: String = label.chars().take(width.saturating_sub(1)).collect();
+    short.push('…');
+    short
+}
 
-    render_footer(f, layout[2], app);
+/// Net per month with that month's spending, the trailing average spend as a horizontal
+/// reference, and annotation markers.
+fn render_cashflow_chart(
+    f: &mut ratatui::Frame,
+    area: Rect,
+    cashflow: Vec<(String, f64)>,
+    spending: &[f64],
+    average_spend: f64,
+    annotations: &[Annotation],
+) {
+    let data: Vec<(f64, f64)> = cashflow
+        .iter()
+        .enumerate()
+        .map(|(i, (_, v))| (i as f64, *v))
+        .collect();
+    let spend_data: Vec<(f64, f64)> = spending
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (i as f64, *v))
+        .collect();
+    let average = [
+        (0.0, average_spend),
+        (data.len().max(1) as f64, average_spend),
+    ];
+    let values = || {
+        data.iter()
+            .chain(&spend_data)
+            .map(|(_, y)| *y)
+            .chain([average_spend])
+    };
+    let y_min = values().fold(0.0, f64::min) - 50.0;
+    let y_max = values().fold(0.0, f64::max) + 50.0;
+
+    // A vertical line per annotation, placed within its month by day. Events in months
+    // outside the chart are skipped.
+    let markers: Vec<(&str, [(f64, f64); 2])> = annotations
+        .iter()
+        .filter_map(|a| {
+            let month = a.date.format("%Y-%m").to_string();
+            let index = cashflow.iter().position(|(label, _)| *label == month)?;
+            let x = index as f64 + f64::from(a.date.day() - 1) / 31.0;
+            Some((a.label.as_str(), [(x, y_min), (x, y_max)]))
+        })
+        .collect();
+
+    let labels: Vec<Span> = cashflow
+        .iter()
+        .map(|(label, _)| Span::raw(label.clone()))
+        .collect();
+
+    let mut dataset = vec![
+        Dataset::default()
+            .name("Net by month")
+            .marker(symbols::Marker::Braille)
+            .style(Style::default().fg(Color::Cyan))
+            .data(&data),
+        Dataset::default()
+            .name("Spending")
+            .marker(symbols::Marker::Braille)
+            .style(Style::default().fg(Color::Red))
+            .data(&spend_data),
+    ];
+    if average_spend > 0.0 {
+        dataset.push(
+            Dataset::default()
+                .name("Avg spend")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::DarkGray))
+                .data(&average),
+        );
+    }
+    dataset.extend(markers.iter().map(|(label, points)| {
+        Dataset::default()
+            .name(*label)
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Yellow))
+            .data(points)
+    }));
+
+    let chart = Chart::new(dataset)
+        .block(Block::default().title("Cashflow").borders(Borders::ALL))
+        .x_axis(
+            ratatui::widgets::Axis::default()
+                .bounds([0.0, data.len().max(1) as f64])
+                .labels(labels),
+        )
+        .y_axis(
+            ratatui::widgets::Axis::default()
+                .bounds([y_min, y_max])
+                .labels(vec![Span::raw("-"), Span::raw("0"), Span::raw("+")]),
+        );
+    f.render_widget(chart, area);
 }
 
-fn render_header(f: &mut ratatui::Frame, area: Rect, app: &App) {
-    let header = Paragraph::new(Line::from(vec![
-        Span::styled(
-            "centsh",
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
+fn render_transactions(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let header = Row::new(vec!["Date", "Description", "Category", "Amount"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let visible = app.visible_transactions();
+    let reconciliation = app
+        .reconcile
+        .map(|(target, date)| (app.ledger.reconcile(target, date), target, date));
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(5), Constraint::Length(4)])
+        .split(area);
+
+    // Borders and the header row take three lines.
+    let page = (chunks[0].height.saturating_sub(3) as usize).max(1);
+    let selected = (!visible.is_empty()).then(|| app.selected_tx().min(visible.len() - 1));
+    // `select_tx` keeps the selection on screen; this only catches a terminal resized since.
+    let mut offset = app
+        .tx_table
+        .offset()
+        .min(visible.len().saturating_sub(page));
+    if let Some(selected) = selected {
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + page {
+            offset = selected + 1 - page;
+        }
+    }
+
+    let rows: Vec<Row> = visible
+        .iter()
+        .skip(offset)
+        .take(page)
+        .map(|tx| {
+            let mut description = if tx.refund_of.is_some() {
+                format!("↩ {}", tx.description)
+            } else {
+                tx.description.clone()
+            };
+            if tx.business {
+                description = format!("💼 {description}");
+            }
+            if tx.cleared {
+                description = format!("✓ {description}");
+            }
+            let mut row = Row::new(vec![
+                Cell::from(tx.date.to_string()),
+                Cell::from(description),
+                Cell::from(with_icon(&app.ledger, &tx.category)),
+                Cell::from(styled_amount(tx.amount, &app.config.currency)),
+            ]);
+            if app.pending_refund == Some(tx.id) {
+                row = row.style(Style::default().fg(Color::Magenta));
+            } else if let Some((rec, _, _)) = &reconciliation
+                && rec.uncleared.iter().any(|t| t.id == tx.id)
+            {
+                row = row.style(Style::default().fg(Color::Yellow));
+            }
+            row
+        })
+        .collect();
+
+    let widths = [
+        Constraint::Length(12),
+        Constraint::Percentage(40),
+        Constraint::Length(14),
+        Constraint::Length(12),
+    ];
+    let mut title = match app.kind_filter {
+        None => "Transactions".to_string(),
+        Some(TxKind::Income) => "Income".to_string(),
+        Some(TxKind::Expense) => "Expenses".to_string(),
+    };
+    if visible.len() > page {
+        title.push_str(&format!(
+            " — showing {}–{} of {}",
+            offset + 1,
+            (offset + page).min(visible.len()),
+            visible.len()
+        ));
+    }
+    if !app.filter.is_empty() {
+        title.push_str(&format!(" — search \"{}\"", app.filter));
+    }
+    let mut block = Block::default().title(title).borders(Borders::ALL);
+    if !app.filter.is_empty() || app.kind_filter.is_some() {
+        let (count, total) = app.ledger.filter_summary(
+            &app.filter,
+            app.kind_filter,
+            &app.config.excluded_categories,
+        );
+        block = block.title_bottom(Line::from(vec![
+            Span::raw(format!(" {count} matching, total ")),
+            styled_amount(total, &app.config.currency),
+            Span::raw(" "),
+        ]));
+    }
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = TableState::default().with_selected(selected.map(|i| i - offset));
+    f.render_stateful_widget(table, chunks[0], &mut state);
+
+    let (details, details_title) = match &reconciliation {
+        Some((rec, target, date)) => (
+            reconciliation_lines(rec, *target, *date, &app.config.currency),
+            "Reconcile (c mark cleared, Esc finish)",
         ),
-        Span::raw("  | data "),
-        Span::styled(
-            app.storage.path().to_string_lossy(),
-            Style::default().fg(Color::Gray),
+        None => (
+            selected
+                .and_then(|i| visible.get(i))
+                .map(|tx| transaction_details(&app.ledger, tx, &app.config.currency))
+                .unwrap_or_default(),
+            "Details (R link refund, t save as template, c cleared, d move to trash)",
         ),
-    ]))
-    .wrap(Wrap { trim: true });
-    f.render_widget(header, area);
+    };
+    let panel = Paragraph::new(details)
+        .block(Block::default().title(details_title).borders(Borders::ALL))
+        .wrap(Wrap { trim: true });
+    f.render_widget(panel, chunks[1]);
+}
+
+fn reconciliation_lines(
+    rec: &Reconciliation,
+    target: f64,
+    date: NaiveDate,
+    currency: &CurrencyFormat,
+) -> Vec<Line<'static>> {
+    let status = if rec.difference == 0.0 {
+        Span::styled("balanced", Style::default().fg(Color::Green))
+    } else if rec.unexplained == 0.0 {
+        Span::styled(
+            format!("{} uncleared (yellow) explain it", rec.uncleared.len()),
+            Style::default().fg(Color::Yellow),
+        )
+    } else {
+        Span::styled(
+            format!(
+                "{} unexplained after {} uncleared (yellow)",
+                format_currency(rec.unexplained, currency),
+                rec.uncleared.len()
+            ),
+            Style::default().fg(Color::Red),
+        )
+    };
+    vec![
+        Line::from(format!(
+            "Statement {} vs ledger {} on {date}: difference {}",
+            format_currency(target, currency),
+            format_currency(rec.ledger_balance, currency),
+            format_currency(rec.difference, currency)
+        )),
+        Line::from(status),
+    ]
+}
+
+/// Summary of one transaction and its refund links in both directions.
+fn transaction_details(
+    ledger: &Ledger,
+    tx: &Transaction,
+    currency: &CurrencyFormat,
+) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::from(format!(
+        "#{} {} — {} on {}",
+        tx.id,
+        tx.description,
+        format_currency(tx.amount, currency),
+        tx.date
+    ))];
+    if let Some(original) = ledger.refund_original(tx) {
+        lines.push(Line::from(format!(
+            "Refund of #{} {} ({}, {})",
+            original.id,
+            original.description,
+            format_currency(original.amount, currency),
+            original.date
+        )));
+    }
+    if let Some(account) = tx.account.and_then(|id| ledger.account(id)) {
+        lines.push(Line::from(format!("Account: {}", account.name)));
+    }
+    if tx.business {
+        lines.push(Line::from("Business expense"));
+    }
+    if !tx.note.is_empty() {
+        lines.push(Line::from(format!("Note: {}", tx.note)));
+    }
+    if tx.opening_balance {
+        lines.push(Line::from(
+            "Opening balance: counts toward balances, never as income or spending",
+        ));
+    }
+    if let Some(months) = tx.amortize_months {
+        lines.push(Line::from(format!(
+            "Amortized over {months} months ({} each)",
+            format_currency(tx.amount / months as f64, currency)
+        )));
+    }
+    let refunds = ledger.refunds_of(tx.id);
+    if !refunds.is_empty() {
+        let refunded: f64 = refunds.iter().map(|r| r.amount.abs()).sum();
+        let ids: Vec<String> = refunds.iter().map(|r| format!("#{}", r.id)).collect();
+        lines.push(Line::from(format!(
+            "Refunded {} by {}",
+            format_currency(refunded, currency),
+            ids.join(", ")
+        )));
+    }
+    lines
+}
+
+/// Last first row worth scrolling to on the Envelopes tab: the one that shows the last row
+/// of cards at the bottom of `inner`.
+fn envelope_max_scroll(cards: usize, inner: Rect) -> usize {
+    let columns = usize::from((inner.width / ENVELOPE_WIDTH).max(1));
+    let visible_rows = usize::from((inner.height / ENVELOPE_HEIGHT).max(1));
+    cards.div_ceil(columns).saturating_sub(visible_rows)
+}
+
+/// One card per budget with a fill gauge and allocated/spent/remaining, wrapped into a grid
+/// that scrolls by rows.
+fn render_envelopes(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let ledger = &app.ledger;
+    let today = Local::now().naive_local().date();
+    let block = Block::default()
+        .title(format!("Envelopes for {}", today.format("%B %Y")))
+        .borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    if ledger.budgets.is_empty() {
+        f.render_widget(Paragraph::new("No budgets yet. Press b to add one."), inner);
+        return;
+    }
+
+    let columns = usize::from((inner.width / ENVELOPE_WIDTH).max(1));
+    let visible_rows = usize::from((inner.height / ENVELOPE_HEIGHT).max(1));
+    let first_row = app
+        .envelope_scroll
+        .min(envelope_max_scroll(ledger.budgets.len(), inner));
+    let currency = &app.config.currency;
+
+    for (i, budget) in ledger
+        .budgets
+        .iter()
+        .enumerate()
+        .skip(first_row * columns)
+        .take(visible_rows * columns)
+    {
+        let (row, column) = (i / columns - first_row, i % columns);
+        let card = Rect {
+            x: inner.x + column as u16 * ENVELOPE_WIDTH,
+            y: inner.y + row as u16 * ENVELOPE_HEIGHT,
+            width: ENVELOPE_WIDTH.min(inner.width),
+            height: ENVELOPE_HEIGHT,
+        };
+        let limit = budget.period_limit();
+        let remaining = ledger.budget_remaining(&budget.category, today);
+        let spent = limit - remaining;
+        let used = if limit > 0.0 { spent / limit } else { 0.0 };
+        let color = budget_fill_color(budget.direction, used);
+        let card_block = Block::default()
+            .title(budget_name(ledger, budget))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(color));
+        let card_inner = card_block.inner(card);
+        f.render_widget(card_block, card);
+        let parts = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(card_inner);
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(color))
+            .ratio(used.clamp(0.0, 1.0))
+            .label(format!("{:.0}%", used * 100.0));
+        f.render_widget(gauge, parts[0]);
+        let left_label = match budget.direction {
+            BudgetDirection::SpendCap if remaining < 0.0 => "Over",
+            BudgetDirection::SpendCap => "Left",
+            BudgetDirection::SaveTarget => "To go",
+        };
+        let numbers = Paragraph::new(vec![
+            Line::from(format!(
+                "{} of {}",
+                format_currency(spent, currency),
+                format_currency(limit, currency)
+            )),
+            Line::from(Span::styled(
+                format!(
+                    "{left_label} {}",
+                    format_currency(remaining.abs(), currency)
+                ),
+                Style::default().fg(color),
+            )),
+        ]);
+        f.render_widget(numbers, parts[1]);
+    }
 }
 
-fn render_overview(f: &mut ratatui::Frame, area: Rect, ledger: &Ledger) {
-    let overview = ledger.current_month_overview();
-    let cat_spend = ledger.category_spending_current_month();
-    let budgets = ledger.budgets_by_category();
-    let cashflow = ledger.spending_last_n_months(6);
+/// Green/yellow/red as a spending cap fills up; save targets turn green once met.
+fn budget_fill_color(direction: BudgetDirection, used: f64) -> Color {
+    match direction {
+        BudgetDirection::SpendCap if used >= 1.0 => Color::Red,
+        BudgetDirection::SpendCap if used >= NEAR_LIMIT => Color::Yellow,
+        BudgetDirection::SpendCap => Color::Green,
+        BudgetDirection::SaveTarget if used >= 1.0 => Color::Green,
+        BudgetDirection::SaveTarget => Color::Cyan,
+    }
+}
+
+/// One line gauge per budget for its current period, scrolled to keep the selected budget
+/// in view. The bar stops at full but the label keeps the real share, e.g. "112%".
+fn render_budget_gauges(f: &mut ratatui::Frame, area: Rect, app: &App, today: NaiveDate) {
+    let ledger = &app.ledger;
+    let block = Block::default()
+        .title("Progress this period")
+        .borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let visible = usize::from(inner.height).max(1);
+    let selected = app
+        .selected_budget
+        .min(ledger.budgets.len().saturating_sub(1));
+    let first = (selected + 1).saturating_sub(visible);
+    let name_width = ledger
+        .budgets
+        .iter()
+        .map(|b| budget_name(ledger, b).chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(usize::from(inner.width / 2));
+
+    for (row, (i, budget)) in ledger
+        .budgets
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(visible)
+        .enumerate()
+    {
+        let limit = budget.period_limit();
+        let name: String = budget_name(ledger, budget)
+            .chars()
+            .take(name_width)
+            .collect();
+        let (ratio, label, color) = if limit > 0.0 {
+            let spent = limit - ledger.budget_remaining(&budget.category, today);
+            let used = spent / limit;
+            (
+                used.clamp(0.0, 1.0),
+                format!("{name:<name_width$} {:>4.0}%", used * 100.0),
+                budget_fill_color(budget.direction, used),
+            )
+        } else {
+            (0.0, format!("{name:<name_width$}  n/a"), Color::DarkGray)
+        };
+        let label_style = if i == selected {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        let gauge = LineGauge::default()
+            .ratio(ratio)
+            .label(Span::styled(label, label_style))
+            .gauge_style(Style::default().fg(color).bg(Color::Reset))
+            .line_set(symbols::line::THICK);
+        let line = Rect {
+            y: inner.y + row as u16,
+            height: 1,
+            ..inner
+        };
+        f.render_widget(gauge, line);
+    }
+}
 
+fn render_budgets(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let ledger = &app.ledger;
+    let today = Local::now().naive_local().date();
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
         .split(area);
 
-    let stats_block = Block::default().title("This month").borders(Borders::ALL);
-    let stats_lines = vec![
-        Line::from(format!(
-            "Income: {}",
-            format_currency(overview.total_income)
-        )),
-        Line::from(format!(
-            "Spending: {}",
-            format_currency(overview.total_outgoing)
-        )),
-        Line::from(vec![Span::raw("Net: "), styled_net(overview.net)]),
-        Line::from(" "),
-        Line::from("Budgets:"),
-    ];
-
-    let mut budget_lines = stats_lines;
-    let mut rows: Vec<Line> = budgets
+    let mut rows: Vec<Row> = ledger
+        .budgets
         .iter()
-        .map(|(cat, limit)| {
-            let spent = cat_spend
-                .iter()
-                .find(|(c, _)| c == cat)
-                .map(|(_, v)| *v)
-                .unwrap_or(0.0);
-            let pct = if *limit > 0.0 {
-                (spent / limit * 100.0).min(999.0)
+        .map(|b| {
+            let category = if b.pinned {
+                format!("* {}", b.category)
             } else {
-                0.0
+                b.category.clone()
             };
-            Line::from(format!(
-                "- {cat}: {} / {} ({pct:.0}%)",
-                format_currency(spent),
-                format_currency(*limit)
-            ))
+            let forecast = ledger.forecast_category(&b.category, today);
+            let forecast_style =
+                if b.direction == BudgetDirection::SpendCap && forecast > b.monthly_limit {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default()
+                };
+            Row::new(vec![
+                Cell::from(category),
+                Cell::from(match b.direction {
+                    BudgetDirection::SpendCap => {
+                        format_currency(b.monthly_limit, &app.config.currency)
+                    }
+                    BudgetDirection::SaveTarget => format!(
+                        "{} target",
+                        format_currency(b.monthly_limit, &app.config.currency)
+                    ),
+                }),
+                Cell::from(Span::styled(
+                    format_currency(forecast, &app.config.currency),
+                    forecast_style,
+                )),
+                Cell::from(match b.period {
+                    BudgetPeriod::Monthly => b.note.clone(),
+                    period => {
+                        let limit = format!(
+                            "{} {}",
+                            format_currency(b.period_limit(), &app.config.currency),
+                            period.label()
+                        );
+                        if b.note.is_empty() {
+                            limit
+                        } else {
+                            format!("{limit}; {}", b.note)
+                        }
+                    }
+                }),
+            ])
         })
         .collect();
-    if rows.is_empty() {
-        rows.push(Line::from("No budgets yet. Press b to add one."));
+    let total = ledger.total_budgeted();
+    let income = ledger.current_month_overview().total_income;
+    let share = if income > 0.0 {
+        format!("{:.0}% of this month's income", total / income * 100.0)
+    } else {
+        String::new()
+    };
+    rows.push(
+        Row::new(vec![
+            Cell::from("Total"),
+            Cell::from(format_currency(total, &app.config.currency)),
+            Cell::from(""),
+            Cell::from(share),
+        ])
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+    );
+
+    let table = Table::new(
+        rows,
+        &[
+            Constraint::Percentage(30),
+            Constraint::Percentage(18),
+            Constraint::Percentage(18),
+            Constraint::Percentage(34),
+        ],
+    )
+    .header(
+        Row::new(vec!["Category", "Monthly limit", "Next month", "Note"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(
+        Block::default()
+            .title("Budgets (j/k select, Enter edit, d delete, J/K move, P pin)")
+            .borders(Borders::ALL),
+    )
+    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let selected =
+        (!ledger.budgets.is_empty()).then(|| app.selected_budget.min(ledger.budgets.len() - 1));
+    let mut state = TableState::default().with_selected(selected);
+    if ledger.budgets.is_empty() {
+        f.render_stateful_widget(table, chunks[0], &mut state);
+    } else {
+        let gauge_height = (ledger.budgets.len() as u16 + 2).min(chunks[0].height / 2);
+        let left = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(gauge_height)])
+            .split(chunks[0]);
+        f.render_stateful_widget(table, left[0], &mut state);
+        render_budget_gauges(f, left[1], app, today);
     }
-    budget_lines.extend(rows);
-    let stats = Paragraph::new(budget_lines).block(stats_block);
-    f.render_widget(stats, chunks[0]);
 
-    let right_chunks = Layout::default()
+    let side = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
         .split(chunks[1]);
+    render_recurring(f, side[1], ledger, &app.config.currency);
+
+    if let Some(results) = &app.simulation {
+        render_simulation(f, side[0], results, &app.config.currency);
+        return;
+    }
+
+    let suggestion_block = Block::default()
+        .title(format!(
+            "Auto-budgets ({}d window [ ], +{:.0}% buffer -/+)",
+            app.suggestion_window_days,
+            app.suggestion_buffer * 100.0
+        ))
+        .borders(Borders::ALL);
 
-    render_category_chart(f, right_chunks[0], cat_spend);
-    render_cashflow_chart(f, right_chunks[1], cashflow);
+    let stale_days = app.config.stale_budget_days;
+    let mut lines: Vec<Line> = ledger
+        .stale_budgets(stale_days, today)
+        .into_iter()
+        .map(|category| {
+            Line::from(Span::styled(
+                format!("{category}: no activity in {stale_days} days — remove?"),
+                Style::default().fg(Color::Yellow),
+            ))
+        })
+        .collect();
+    if app.show_suggestions {
+        let suggestions = app.current_suggestions();
+        lines.extend(suggestions.into_iter().map(|s| {
+            let limit = if s.suggested_limit <= 0.0 {
+                "add target".to_string()
+            } else if s.suggested_limit != s.raw_limit {
+                format!(
+                    "{} (raw {})",
+                    format_currency(s.suggested_limit, &app.config.currency),
+                    format_currency(s.raw_limit, &app.config.currency)
+                )
+            } else {
+                format_currency(s.suggested_limit, &app.config.currency)
+            };
+            Line::from(format!("{}: {limit} ({})", s.category, s.reason))
+        }));
+    } else {
+        lines.push(Line::from("Press g to show auto-budget ideas"));
+    }
+    let paragraph = Paragraph::new(lines)
+        .block(suggestion_block)
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, side[0]);
 }
 
-fn render_category_chart(f: &mut ratatui::Frame, area: Rect, cat_spend: Vec<(String, f64)>) {
-    let data: Vec<(&str, u64)> = cat_spend
-        .iter()
-        .map(|(cat, amt)| (cat.as_str(), amt.max(0.0) as u64))
+/// One line per proposed budget with a mark for each simulated month: green when the limit
+/// would have held, red with the amount over when it would have been blown.
+fn render_simulation(
+    f: &mut ratatui::Frame,
+    area: Rect,
+    results: &[MonthBudgetResult],
+    currency: &CurrencyFormat,
+) {
+    let proposed = results.first().map_or(0, |month| month.budgets.len());
+    let lines: Vec<Line> = (0..proposed)
+        .map(|i| {
+            let budget = &results[0].budgets[i];
+            let met = results
+                .iter()
+                .filter(|month| month.budgets[i].met())
+                .count();
+            let mut spans = vec![Span::styled(
+                format!(
+                    "{} {}: met {met}/{} ",
+                    budget.category,
+                    format_currency(budget.limit, currency),
+                    results.len()
+                ),
+                Style::default().add_modifier(Modifier::BOLD),
+            )];
+            spans.extend(results.iter().map(|month| {
+                let b = &month.budgets[i];
+                let label = month.month.format("%b");
+                if b.met() {
+                    Span::styled(format!("{label} ✓ "), Style::default().fg(Color::Green))
+                } else {
+                    Span::styled(
+                        format!("{label} +{} ", format_currency(b.spent - b.limit, currency)),
+                        Style::default().fg(Color::Red),
+                    )
+                }
+            }));
+            Line::from(spans)
+        })
         .collect();
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(format!(
+                    "What-if over the last {} months (w to change, Esc to close)",
+                    results.len()
+                ))
+                .borders(Borders::ALL),
+        )
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
 
-    let chart = BarChart::default()
+fn render_upcoming_bills(f: &mut ratatui::Frame, area: Rect, app: &App, today: NaiveDate) {
+    let days = app.config.upcoming_bill_days;
+    let bills = app.ledger.upcoming_bills(days, today);
+    let lines: Vec<Line> = if bills.is_empty() {
+        vec![Line::from(format!("Nothing due in the next {days} days"))]
+    } else {
+        bills
+            .iter()
+            .map(|bill| {
+                let until = (bill.due - today).num_days();
+                let when = match until {
+                    0 => "today".to_string(),
+                    1 => "tomorrow".to_string(),
+                    n => format!("in {n} days"),
+                };
+                let color = if until <= 2 {
+                    Color::Red
+                } else {
+                    Color::Yellow
+                };
+                Line::from(vec![
+                    Span::styled(format!("{when:<12}"), Style::default().fg(color)),
+                    Span::raw(format!(
+                        "{} {}{}",
+                        bill.description,
+                        format_currency(bill.amount, &app.config.currency),
+                        if bill.recurring { " (expected)" } else { "" }
+                    )),
+                ])
+            })
+            .collect()
+    };
+    let paragraph = Paragraph::new(lines)
         .block(
             Block::default()
-                .title("Category spend (this month)")
+                .title("Upcoming bills")
                 .borders(Borders::ALL),
         )
-        .bar_width(8)
-        .data(&data)
-        .value_style(Style::default().fg(Color::Yellow))
-        .label_style(Style::default().fg(Color::White));
-    f.render_widget(chart, area);
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
 }
 
-fn render_cashflow_chart(f: &mut ratatui::Frame, area: Rect, cashflow: Vec<(String, f64)>) {
-    let data: Vec<(f64, f64)> = cashflow
+fn render_recurring(
+    f: &mut ratatui::Frame,
+    area: Rect,
+    ledger: &Ledger,
+    currency: &CurrencyFormat,
+) {
+    let candidates = ledger.detect_recurring();
+    let lines: Vec<Line> = if candidates.is_empty() {
+        vec![Line::from(
+            "Nothing recurring yet (needs 3+ months of a repeated charge)",
+        )]
+    } else {
+        candidates
+            .iter()
+            .map(|c| {
+                Line::from(format!(
+                    "{} ({}): {} {}, {} months, last {}",
+                    c.description,
+                    c.category,
+                    format_currency(c.average_amount, currency),
+                    c.cadence.label(),
+                    c.months,
+                    c.last_date
+                ))
+            })
+            .collect()
+    };
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title("Recurring charges")
+                .borders(Borders::ALL),
+        )
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+fn render_fiscal_year(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let today = Local::now().naive_local().date();
+    let start = fiscal_year_start(today, app.config.fiscal_year_start_month);
+    let months = app.ledger.fiscal_year_summary(start);
+
+    let mut rows: Vec<Row> = months
         .iter()
-        .enumerate()
-        .map(|(i, (_, v))| (i as f64, *v))
+        .map(|m| {
+            Row::new(vec![
+                Cell::from(m.month.format("%b %Y").to_string()),
+                Cell::from(format_currency(m.income, &app.config.currency)),
+                Cell::from(format_currency(m.spending, &app.config.currency)),
+                Cell::from(styled_net(m.net, &app.config.currency)),
+            ])
+        })
         .collect();
+    let (income, spending) = months
+        .iter()
+        .fold((0.0, 0.0), |acc, m| (acc.0 + m.income, acc.1 + m.spending));
+    rows.push(
+        Row::new(vec![
+            Cell::from("Total"),
+            Cell::from(format_currency(income, &app.config.currency)),
+            Cell::from(format_currency(spending, &app.config.currency)),
+            Cell::from(styled_net(income - spending, &app.config.currency)),
+        ])
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+    );
 
-    let labels: Vec<Span> = cashflow
+    let title = match months.last() {
+        Some(last) if app.config.fiscal_year_start_month > 1 => format!(
+            "Fiscal year {} – {}",
+            start.format("%b %Y"),
+            last.month.format("%b %Y")
+        ),
+        _ => format!("Year {}", start.year()),
+    };
+    let widths = [
+        Constraint::Length(12),
+        Constraint::Length(14),
+        Constraint::Length(14),
+        Constraint::Length(14),
+    ];
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(vec!["Month", "Income", "Spending", "Net"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().title(title).borders(Borders::ALL));
+    f.render_widget(table, area);
+}
+
+fn render_comparison(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let currency = &app.config.currency;
+    let (a, b) = (app.compare_a, app.compare_b);
+    let comparison = app.ledger.compare_months(a, b);
+    let mut rows: Vec<Row> = comparison
         .iter()
-        .map(|(label, _)| Span::raw(label.clone()))
+        .map(|c| {
+            Row::new(vec![
+                Cell::from(c.category.clone()),
+                Cell::from(format_currency(c.a, currency)),
+                Cell::from(format_currency(c.b, currency)),
+                Cell::from(styled_amount(c.difference, currency)),
+            ])
+        })
         .collect();
+    let (total_a, total_b) = comparison
+        .iter()
+        .fold((0.0, 0.0), |acc, c| (acc.0 + c.a, acc.1 + c.b));
+    rows.push(
+        Row::new(vec![
+            Cell::from("Total"),
+            Cell::from(format_currency(total_a, currency)),
+            Cell::from(format_currency(total_b, currency)),
+            Cell::from(styled_amount(total_b - total_a, currency)),
+        ])
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+    );
 
-    let dataset = vec![
-        Dataset::default()
-            .name("Net by month")
-            .marker(symbols::Marker::Braille)
-            .style(Style::default().fg(Color::Cyan))
-            .data(&data),
+    let (label_a, label_b) = (a.format("%b %Y").to_string(), b.format("%b %Y").to_string());
+    let widths = [
+        Constraint::Length(16),
+        Constraint::Length(14),
+        Constraint::Length(14),
+        Constraint::Length(14),
     ];
-
-    let chart = Chart::new(dataset)
-        .block(Block::default().title("Cashflow").borders(Borders::ALL))
-        .x_axis(
-            ratatui::widgets::Axis::default()
-                .bounds([0.0, data.len().max(1) as f64])
-                .labels(labels),
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(vec![
+                "Category".to_string(),
+                label_a.clone(),
+                label_b.clone(),
+                "Difference".to_string(),
+            ])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
         )
-        .y_axis(
-            ratatui::widgets::Axis::default()
-                .bounds([
-                    data.iter().map(|(_, y)| *y).fold(0.0, f64::min) - 50.0,
-                    data.iter().map(|(_, y)| *y).fold(0.0, f64::max) + 50.0,
-                ])
-                .labels(vec![Span::raw("-"), Span::raw("0"), Span::raw("+")]),
+        .block(
+            Block::default()
+                .title(format!(
+                    "{label_a} vs {label_b}  ([ ] change first month, {{ }} change second)"
+                ))
+                .borders(Borders::ALL),
         );
-    f.render_widget(chart, area);
+    f.render_widget(table, area);
 }
 
-fn render_transactions(f: &mut ratatui::Frame, area: Rect, ledger: &Ledger) {
-    let header = Row::new(vec!["Date", "Description", "Category", "Amount"])
-        .style(Style::default().add_modifier(Modifier::BOLD));
+fn render_trends(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let currency = &app.config.currency;
+    let today = Local::now().naive_local().date();
+    let mut trends = app.ledger.ttm_by_category(today);
+    match app.trend_sort {
+        TrendSort::Total => {}
+        TrendSort::Average => {
+            trends.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal))
+        }
+        TrendSort::Category => trends.sort_by(|a, b| a.0.cmp(&b.0)),
+    }
+    let rows: Vec<Row> = trends
+        .iter()
+        .map(|(category, total, average)| {
+            Row::new(vec![
+                Cell::from(with_icon(&app.ledger, category)),
+                Cell::from(format_currency(*total, currency)),
+                Cell::from(format_currency(*average, currency)),
+            ])
+        })
+        .collect();
+    let widths = [
+        Constraint::Length(20),
+        Constraint::Length(14),
+        Constraint::Length(14),
+    ];
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(vec!["Category", "Last 12 months", "Monthly avg"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(
+            Block::default()
+                .title(format!(
+                    "Trailing 12 months to {} (sorted by {}, S to change)",
+                    today.format("%b %Y"),
+                    app.trend_sort.label()
+                ))
+                .borders(Borders::ALL),
+        );
+    f.render_widget(table, area);
+}
 
-    let rows: Vec<Row> = ledger
-        .transactions
+fn render_archive(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let archived = &app.ledger.archived;
+    let rows: Vec<Row> = archived
         .iter()
-        .take(18)
         .map(|tx| {
             Row::new(vec![
                 Cell::from(tx.date.to_string()),
                 Cell::from(tx.description.clone()),
                 Cell::from(tx.category.clone()),
-                Cell::from(styled_amount(tx.amount)),
+                Cell::from(styled_amount(tx.amount, &app.config.currency)),
             ])
         })
         .collect();
-
     let widths = [
         Constraint::Length(12),
         Constraint::Percentage(40),
         Constraint::Length(14),
         Constraint::Length(12),
     ];
-    let table = Table::new(rows, widths).header(header).block(
-        Block::default()
-            .title("Recent transactions")
-            .borders(Borders::ALL),
-    );
-
-    f.render_widget(table, area);
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(vec!["Date", "Description", "Category", "Amount"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(
+            Block::default()
+                .title(format!(
+                    "Trash: {} transactions (u restore, X purge all)",
+                    archived.len()
+                ))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let selected = (!archived.is_empty()).then(|| app.selected_archived.min(archived.len() - 1));
+    let mut state = TableState::default().with_selected(selected);
+    f.render_stateful_widget(table, area, &mut state);
 }
 
-fn render_budgets(f: &mut ratatui::Frame, area: Rect, ledger: &Ledger, show_suggestions: bool) {
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
-        .split(area);
-
-    let rows: Vec<Row> = ledger
-        .budgets
+fn render_templates(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let templates = &app.ledger.templates;
+    let rows: Vec<Row> = templates
         .iter()
-        .map(|b| {
+        .map(|t| {
             Row::new(vec![
-                Cell::from(b.category.clone()),
-                Cell::from(format_currency(b.monthly_limit)),
+                Cell::from(t.description.clone()),
+                Cell::from(t.category.clone()),
+                Cell::from(styled_amount(t.amount, &app.config.currency)),
             ])
         })
         .collect();
+    let widths = [
+        Constraint::Percentage(50),
+        Constraint::Length(14),
+        Constraint::Length(12),
+    ];
+    let title = if templates.is_empty() {
+        "Templates (select a transaction and press t to save one)"
+    } else {
+        "Templates (Enter use, e edit, d delete)"
+    };
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(vec!["Description", "Category", "Amount"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let selected = (!templates.is_empty()).then(|| app.selected_template.min(templates.len() - 1));
+    let mut state = TableState::default().with_selected(selected);
+    f.render_stateful_widget(table, area, &mut state);
+}
 
-    let table = Table::new(
-        rows,
-        &[Constraint::Percentage(70), Constraint::Percentage(30)],
-    )
-    .header(
-        Row::new(vec!["Category", "Monthly limit"])
-            .style(Style::default().add_modifier(Modifier::BOLD)),
-    )
-    .block(Block::default().title("Budgets").borders(Borders::ALL));
-    f.render_widget(table, chunks[0]);
-
-    let suggestion_block = Block::default()
-        .title("Auto-budgets (90d trend)")
-        .borders(Borders::ALL);
+/// Rows of transactions the Transactions table shows at the current terminal size, as laid
+/// out by `draw` and `render_transactions`.
+fn transactions_page(app: &App) -> usize {
+    let (width, height) = terminal::size().unwrap_or((80, 24));
+    // Header and tabs (4), the details panel (4), then the table's borders and header (3).
+    usize::from(height.saturating_sub(footer_height(app, width) + 4 + 4 + 3)).max(1)
+}
 
-    if show_suggestions {
-        let suggestions = ledger.suggested_budgets();
-        let lines: Vec<Line> = suggestions
-            .into_iter()
-            .map(|s| {
-                Line::from(format!(
-                    "{}: {} ({})",
-                    s.category,
-                    if s.suggested_limit > 0.0 {
-                        format_currency(s.suggested_limit)
-                    } else {
-                        "add target".into()
-                    },
-                    s.reason
-                ))
-            })
-            .collect();
-        let paragraph = Paragraph::new(lines)
-            .block(suggestion_block)
-            .wrap(Wrap { trim: true });
-        f.render_widget(paragraph, chunks[1]);
-    } else {
-        let paragraph = Paragraph::new("Press g to show auto-budget ideas").block(suggestion_block);
-        f.render_widget(paragraph, chunks[1]);
-    }
+/// Rows the footer needs at `width` columns: forms get one line per field plus the help
+/// line and borders; browsing gets the wrapped key hints and the status line.
+fn footer_height(app: &App, width: u16) -> u16 {
+    let fields = match &app.form {
+        ActiveForm::Transaction(form) => form.fields.len(),
+        ActiveForm::Budget(form) => form.fields.len(),
+        ActiveForm::Prompt(form) => form.fields.len(),
+        ActiveForm::None => {
+            let (tab_lines, global_lines) = footer_hints(app, width);
+            return (tab_lines.len() + global_lines.len()) as u16 + 2;
+        }
+        _ => return 6,
+    };
+    (fields as u16 + 3).max(6)
 }
 
 fn render_footer(f: &mut ratatui::Frame, area: Rect, app: &App) {
     if let ActiveForm::Transaction(form) = &app.form {
-        render_form(f, area, "Add transaction", form.fields.clone(), form.index);
+        render_form(f, area, &form.heading(), form.fields.clone(), form.index);
         return;
     }
     if let ActiveForm::Budget(form) = &app.form {
-        render_form(f, area, "Add budget", form.fields.clone(), form.index);
+        render_form(f, area, form.title(), form.fields.clone(), form.index);
+        return;
+    }
+    if let ActiveForm::Prompt(form) = &app.form {
+        render_form(f, area, form.title, form.fields.clone(), form.index);
         return;
     }
 
-    let last_saved = app
-        .last_save
-        .map(|_| "Saved recently".to_string())
-        .unwrap_or_default();
-    let footer = Paragraph::new(Line::from(vec![
-        Span::raw(
-            "q quit  a add txn  b add budget  h/l tabs  s save  g toggle auto-budget  r reload  ",
-        ),
-        Span::styled(last_saved, Style::default().fg(Color::Gray)),
-        Span::raw("  "),
-        Span::styled(&app.last_message, Style::default().fg(Color::Yellow)),
-    ]))
-    .wrap(Wrap { trim: true })
-    .block(Block::default().borders(Borders::TOP));
+    if let ActiveForm::Confirm(action) = &app.form {
+        let mut lines = vec![Line::from(Span::styled(
+            action.question(app),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ))];
+        lines.extend(action.details(app).into_iter().map(Line::from));
+        lines.push(Line::from("y/Enter: yes   n/Esc: no"));
+        let prompt = Paragraph::new(lines).block(Block::default().borders(Borders::TOP));
+        f.render_widget(prompt, area);
+        return;
+    }
+
+    if let ActiveForm::Search = app.form {
+        let prompt = Paragraph::new(vec![
+            Line::from(vec![
+                Span::styled("Search: ", Style::default().fg(Color::Yellow)),
+                Span::raw(format!("{}_", app.filter)),
+            ]),
+            Line::from(
+                "Text plus amount conditions: >100  <=20  =4.50  20..50 (e.g. \"food >50\")   Enter: keep filter   Esc: clear",
+            ),
+        ])
+        .block(Block::default().borders(Borders::TOP));
+        f.render_widget(prompt, area);
+        return;
+    }
+
+    let last_saved = if !app.storage.writable() {
+        Span::styled("READ-ONLY", Style::default().fg(Color::Red))
+    } else {
+        Span::styled(
+            app.last_save
+                .map(|_| "Saved recently".to_string())
+                .unwrap_or_default(),
+            Style::default().fg(Color::Gray),
+        )
+    };
+    let mut status = vec![last_saved, Span::raw("  ")];
+    let today = Local::now().naive_local().date();
+    let factor = app.config.velocity_alert_factor;
+    if factor > 0.0
+        && let Some(ratio) =
+            app.ledger
+                .velocity_ratio(today, VELOCITY_WINDOW_DAYS, &app.config.excluded_categories)
+        && ratio >= factor
+    {
+        status.push(Span::styled(
+            format!(" spending {ratio:.1}× faster than usual this week "),
+            Style::default().fg(Color::White).bg(Color::Red),
+        ));
+        status.push(Span::raw("  "));
+    }
+    status.push(Span::styled(
+        &app.last_message,
+        Style::default().fg(Color::Yellow),
+    ));
+    let (tab_lines, global_lines) = footer_hints(app, area.width);
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines: Vec<Line> = tab_lines
+        .into_iter()
+        .map(|line| Line::from(Span::styled(line, bold)))
+        .collect();
+    lines.extend(global_lines.into_iter().map(Line::from));
+    lines.push(Line::from(status));
+    let footer = Paragraph::new(lines)
+        .wrap(Wrap { trim: true })
+        .block(Block::default().borders(Borders::TOP));
     f.render_widget(footer, area);
 }
 
+/// `key action` pairs two spaces apart, packed into lines of at most `width` columns
+/// without splitting a pair. `lead` starts the first line.
+fn key_hint_lines(lead: &str, keys: &[(&str, &str)], width: u16) -> Vec<String> {
+    let width = usize::from(width).max(1);
+    let mut lines = Vec::new();
+    let mut line = lead.to_string();
+    let mut empty = true;
+    for (key, action) in keys {
+        let hint = format!("{key} {action}");
+        let len = line.chars().count();
+        if !empty && len + 2 + hint.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        } else if !empty {
+            line.push_str("  ");
+        }
+        line.push_str(&hint);
+        empty = false;
+    }
+    lines.push(line);
+    lines
+}
+
+/// Footer hints wrapped to `width`: the active tab's keys (replaced by the keys that finish
+/// a pending refund link or a reconciliation while one is in progress), then the keys
+/// available on every tab.
+fn footer_hints(app: &App, width: u16) -> (Vec<String>, Vec<String>) {
+    let name = TAB_TITLES[app.active_tab];
+    let tab_keys: &[(&str, &str)] = if app.active_tab == 1 && app.pending_refund.is_some() {
+        &[("j/k", "pick original"), ("R", "link"), ("Esc", "cancel")]
+    } else if app.active_tab == 1 && app.reconcile.is_some() {
+        &[("j/k", "select"), ("c", "cleared"), ("Esc", "finish")]
+    } else {
+        TAB_KEYS[app.active_tab]
+    };
+    let tab_lines = match tab_keys {
+        [] => vec![format!("{name}: no tab-specific keys")],
+        keys => key_hint_lines(&format!("{name}: "), keys, width),
+    };
+    (tab_lines, key_hint_lines("", GLOBAL_KEYS, width))
+}
+
 fn render_form(f: &mut ratatui::Frame, area: Rect, title: &str, fields: Vec<Field>, index: usize) {
     let mut lines: Vec<Line> = Vec::new();
     for (i, field) in fields.iter().enumerate() {
@@ -696,24 +5044,198 @@ fn render_form(f: &mut ratatui::Frame, area: Rect, title: &str, fields: Vec<Fiel
     f.render_widget(paragraph, area);
 }
 
-fn format_currency(value: f64) -> String {
-    if value.is_sign_negative() {
-        format!("-${:.2}", value.abs())
+/// `category` prefixed with its budget's icon, when one is set.
+fn with_icon(ledger: &Ledger, category: &str) -> String {
+    match ledger.category_icon(category) {
+        Some(icon) => format!("{icon} {category}"),
+        None => category.to_string(),
+    }
+}
+
+/// A budget's category with its icon, and its period unless monthly, e.g. "Groceries
+/// (weekly)".
+fn budget_name(ledger: &Ledger, budget: &Budget) -> String {
+    let name = with_icon(ledger, &budget.category);
+    match budget.period {
+        BudgetPeriod::Monthly => name,
+        period => format!("{name} ({})", period.label()),
+    }
+}
+
+/// Two decimal places with the configured symbol and separators, e.g. `-$1234.50` or
+/// `1.234,50 €`.
+fn format_currency(value: f64, currency: &CurrencyFormat) -> String {
+    let cents = (value.abs() * 100.0).round() as u64;
+    let whole = (cents / 100).to_string();
+    let mut grouped = String::new();
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i).is_multiple_of(3) {
+            grouped.push_str(&currency.thousands_separator);
+        }
+        grouped.push(digit);
+    }
+    let number = format!("{grouped}{}{:02}", currency.decimal_separator, cents % 100);
+    let gap = if currency.space { " " } else { "" };
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    if currency.symbol_after {
+        format!("{sign}{number}{gap}{}", currency.symbol)
     } else {
-        format!("${:.2}", value)
+        format!("{sign}{}{gap}{number}", currency.symbol)
     }
 }
 
-fn styled_amount(amount: f64) -> Span<'static> {
+fn styled_amount(amount: f64, currency: &CurrencyFormat) -> Span<'static> {
     let color = if amount >= 0.0 {
         Color::Red
     } else {
         Color::Green
     };
-    Span::styled(format_currency(amount), Style::default().fg(color))
+    Span::styled(
+        format_currency(amount, currency),
+        Style::default().fg(color),
+    )
 }
 
-fn styled_net(net: f64) -> Span<'static> {
+fn styled_net(net: f64, currency: &CurrencyFormat) -> Span<'static> {
     let color = if net >= 0.0 { Color::Green } else { Color::Red };
-    Span::styled(format_currency(net), Style::default().fg(color))
+    Span::styled(format_currency(net, currency), Style::default().fg(color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn currency_symbol_goes_before_by_default() {
+        let dollars = CurrencyFormat::default();
+        assert_eq!(format_currency(1234.5, &dollars), "$1234.50");
+        assert_eq!(format_currency(-12.0, &dollars), "-$12.00");
+    }
+
+    #[test]
+    fn currency_symbol_can_follow_with_a_space_and_separators() {
+        let euros = CurrencyFormat {
+            symbol: "€".to_string(),
+            symbol_after: true,
+            space: true,
+            thousands_separator: ".".to_string(),
+            decimal_separator: ",".to_string(),
+        };
+        assert_eq!(format_currency(1234.5, &euros), "1.234,50 €");
+        assert_eq!(format_currency(-1234567.891, &euros), "-1.234.567,89 €");
+        assert_eq!(format_currency(0.5, &euros), "0,50 €");
+    }
+
+    #[test]
+    fn currency_symbol_before_with_a_space() {
+        let francs = CurrencyFormat {
+            symbol: "CHF".to_string(),
+            space: true,
+            thousands_separator: "'".to_string(),
+            ..CurrencyFormat::default()
+        };
+        assert_eq!(format_currency(98765.4, &francs), "CHF 98'765.40");
+    }
+
+    fn press(app: &mut App, code: KeyCode) -> bool {
+        handle_key(app, KeyEvent::new(code, KeyModifiers::NONE)).unwrap()
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn key_sequence_runs_setup_then_adds_a_transaction() {
+        let dir = std::env::temp_dir().join(format!("centsh-keys-{}", std::process::id()));
+        let mut app = App::with_storage(Storage::at(dir.join("ledger.json"))).unwrap();
+        assert!(matches!(app.form, ActiveForm::Wizard(_)));
+
+        // Welcome, currency, account, opening balance, no budgets, done.
+        press(&mut app, KeyCode::Enter);
+        type_text(&mut app, "EUR");
+        press(&mut app, KeyCode::Enter);
+        type_text(&mut app, "Checking");
+        press(&mut app, KeyCode::Enter);
+        type_text(&mut app, "100");
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Esc);
+        assert!(matches!(app.form, ActiveForm::Wizard(_)));
+        press(&mut app, KeyCode::Enter);
+
+        assert!(matches!(app.form, ActiveForm::None));
+        assert_eq!(app.config.currency.symbol, "€");
+        assert_eq!(app.ledger.accounts.len(), 1);
+        assert_eq!(app.current_account, Some(app.ledger.accounts[0].id));
+
+        // Description and amount, then Enter through category, date and share to submit.
+        press(&mut app, KeyCode::Char('a'));
+        assert!(matches!(app.form, ActiveForm::Transaction(_)));
+        type_text(&mut app, "Lunch");
+        press(&mut app, KeyCode::Enter);
+        type_text(&mut app, "12.5");
+        for _ in 0..4 {
+            press(&mut app, KeyCode::Enter);
+        }
+        assert!(matches!(app.form, ActiveForm::None));
+        let lunch = app
+            .ledger
+            .transactions
+            .iter()
+            .find(|t| t.description == "Lunch");
+        assert_eq!(lunch.map(|t| t.amount), Some(12.5));
+
+        // Esc closes a form without adding anything; q quits from browsing.
+        let count = app.ledger.transactions.len();
+        press(&mut app, KeyCode::Char('a'));
+        press(&mut app, KeyCode::Esc);
+        assert!(matches!(app.form, ActiveForm::None));
+        assert_eq!(app.ledger.transactions.len(), count);
+        assert!(press(&mut app, KeyCode::Char('q')));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn shared_bill_keeps_the_full_amount_in_the_note() {
+        let mut form = TxForm::new(None);
+        form.fields[0].value = "Electricity".into();
+        form.fields[1].value = "200".into();
+        form.fields[4].value = "33.333%".into();
+
+        let tx = form.try_submit(false).unwrap();
+
+        assert_eq!(tx.description, "Electricity");
+        assert!((tx.amount - 66.666).abs() < 1e-9);
+        assert_eq!(tx.note, "My 33.3% share of 200.00");
+
+        form.fields[4].value = "100".into();
+        let whole = form.try_submit(false).unwrap();
+        assert_eq!(whole.amount, 200.0);
+        assert!(whole.note.is_empty());
+    }
+
+    #[test]
+    fn key_hints_wrap_to_the_terminal_width_without_splitting_a_key() {
+        let lines = key_hint_lines("Budgets: ", TAB_KEYS[2], 40);
+        assert!(lines[0].starts_with("Budgets: j/k select  Enter edit"));
+        assert!(
+            lines.iter().all(|line| line.chars().count() <= 40),
+            "{lines:?}"
+        );
+
+        let lines = key_hint_lines("", GLOBAL_KEYS, 80);
+        assert!(lines.len() > 1);
+        assert!(
+            lines.iter().all(|line| line.chars().count() <= 80),
+            "{lines:?}"
+        );
+        let hints: Vec<String> = GLOBAL_KEYS
+            .iter()
+            .map(|(key, action)| format!("{key} {action}"))
+            .collect();
+        assert_eq!(lines.join("  "), hints.join("  "));
+    }
 }
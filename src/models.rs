@@ -1,7 +1,9 @@
-use chrono::{Datelike, Duration, Local, NaiveDate};
+use crate::error::{CentshError, Result};
+use chrono::{Datelike, Days, Duration, Local, Months, NaiveDate};
 use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
@@ -11,23 +13,248 @@ pub struct Transaction {
     pub amount: f64,
     pub category: String,
     pub date: NaiveDate,
+    /// Id of the purchase this transaction refunds, if any.
+    #[serde(default)]
+    pub refund_of: Option<u64>,
+    /// Spread the amount evenly over this many months, starting with the transaction's own,
+    /// when reports use accrual treatment.
+    #[serde(default)]
+    pub amortize_months: Option<u32>,
+    /// Seen on a bank statement; set while reconciling.
+    #[serde(default)]
+    pub cleared: bool,
+    /// Id of the account the money moved through, if any.
+    #[serde(default)]
+    pub account: Option<u64>,
+    /// A business expense, kept apart from personal spending for the tax export.
+    #[serde(default)]
+    pub business: bool,
+    /// Money already held when tracking started. It counts toward balances but is never
+    /// income or spending; see `Ledger::set_opening_balance`.
+    #[serde(default)]
+    pub opening_balance: bool,
+    /// Free text kept with the transaction, such as the full bill when only my share of a
+    /// split expense was recorded.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+impl Transaction {
+    /// Case-insensitive match against description or category. `needle` must be lowercase.
+    fn matches_text(&self, needle: &str) -> bool {
+        self.description.to_lowercase().contains(needle)
+            || self.category.to_lowercase().contains(needle)
+    }
+}
+
+/// Somewhere money is held, such as a checking account or a credit card.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub id: u64,
+    pub name: String,
+    /// Category the transaction form starts with for this account; empty means "General".
+    #[serde(default)]
+    pub default_category: String,
+}
+
+/// A dated note such as "got a raise", drawn as a marker on the cashflow chart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    pub date: NaiveDate,
+    pub label: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Budget {
     pub id: u64,
     pub category: String,
+    /// The limit per month, also for budgets with another `period`, so monthly reports
+    /// (safe to spend, forecasts, totals) can add them up.
     pub monthly_limit: f64,
+    /// Free-form context for why the limit is what it is.
+    #[serde(default)]
+    pub note: String,
+    /// Pinned budgets are kept at the top of the list; otherwise list order is user-defined.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Optional emoji or glyph shown before the category name; purely cosmetic.
+    #[serde(default)]
+    pub icon: String,
+    #[serde(default)]
+    pub direction: BudgetDirection,
+    /// How often the limit resets, as entered and shown on the overview.
+    #[serde(default)]
+    pub period: BudgetPeriod,
+    /// The limit for one `period` exactly as typed, which `monthly_limit` is derived from;
+    /// converting back from the monthly figure would lose cents (yearly $1,000 → $999.96).
+    /// `None` for monthly budgets and limits set as a monthly figure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<f64>,
+}
+
+impl Budget {
+    /// The limit for one `period`, e.g. the weekly amount of a weekly budget.
+    pub fn period_limit(&self) -> f64 {
+        self.limit
+            .unwrap_or_else(|| round_cents(self.monthly_limit / self.period.per_month()))
+    }
+}
+
+/// How often a budget's limit resets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetPeriod {
+    Weekly,
+    /// Every two weeks, in fortnights counted from Monday 2024-01-01.
+    Biweekly,
+    #[default]
+    Monthly,
+    Yearly,
+}
+
+impl BudgetPeriod {
+    /// Parses `weekly`, `biweekly`, `monthly` or `yearly` (case-insensitive, or just the
+    /// first letter); empty means monthly.
+    pub fn parse(input: &str) -> Result<Self> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "w" | "week" | "weekly" => Ok(BudgetPeriod::Weekly),
+            "b" | "biweekly" | "fortnightly" => Ok(BudgetPeriod::Biweekly),
+            "" | "m" | "month" | "monthly" => Ok(BudgetPeriod::Monthly),
+            "y" | "year" | "yearly" | "annual" => Ok(BudgetPeriod::Yearly),
+            _ => Err(CentshError::Validation(
+                "Period must be weekly, biweekly, monthly or yearly".into(),
+            )),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BudgetPeriod::Weekly => "weekly",
+            BudgetPeriod::Biweekly => "biweekly",
+            BudgetPeriod::Monthly => "monthly",
+            BudgetPeriod::Yearly => "yearly",
+        }
+    }
+
+    /// Periods per month on average, e.g. 52 / 12 for weekly.
+    pub fn per_month(self) -> f64 {
+        match self {
+            BudgetPeriod::Weekly => 52.0 / 12.0,
+            BudgetPeriod::Biweekly => 26.0 / 12.0,
+            BudgetPeriod::Monthly => 1.0,
+            BudgetPeriod::Yearly => 1.0 / 12.0,
+        }
+    }
+
+    /// The period containing `date`: a Monday-to-Sunday week, a fortnight, a calendar month
+    /// or a calendar year.
+    pub fn containing(self, date: NaiveDate) -> DateRange {
+        let days = |start: NaiveDate, len: u64| DateRange {
+            start,
+            end: start.checked_add_days(Days::new(len - 1)).unwrap_or(start),
+        };
+        match self {
+            BudgetPeriod::Weekly => days(
+                date - Duration::days(i64::from(date.weekday().num_days_from_monday())),
+                7,
+            ),
+            BudgetPeriod::Biweekly => {
+                let epoch = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap_or(date);
+                let offset = (date - epoch).num_days().rem_euclid(14);
+                days(date - Duration::days(offset), 14)
+            }
+            BudgetPeriod::Monthly => DateRange::month(date),
+            BudgetPeriod::Yearly => DateRange {
+                start: NaiveDate::from_ymd_opt(date.year(), 1, 1).unwrap_or(date),
+                end: NaiveDate::from_ymd_opt(date.year(), 12, 31).unwrap_or(date),
+            },
+        }
+    }
+}
+
+/// Whether a budget's amount is a ceiling to stay under or a goal to reach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetDirection {
+    /// Spending in the category should stay under the limit.
+    #[default]
+    SpendCap,
+    /// Money moved into the category (e.g. transfers to savings) should reach the amount.
+    SaveTarget,
+}
+
+impl BudgetDirection {
+    /// Parses `cap` or `save` (case-insensitive); empty means a spending cap.
+    pub fn parse(input: &str) -> Result<Self> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "" | "cap" => Ok(BudgetDirection::SpendCap),
+            "save" | "target" => Ok(BudgetDirection::SaveTarget),
+            _ => Err(CentshError::Validation(
+                "Direction must be cap or save".into(),
+            )),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BudgetDirection::SpendCap => "cap",
+            BudgetDirection::SaveTarget => "save",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Ledger {
+    /// Newest first. Mutate through `Ledger` methods so the month cache stays valid.
     pub transactions: Vec<Transaction>,
     pub budgets: Vec<Budget>,
+    /// Deleted transactions, most recently deleted first. Kept out of every report until
+    /// restored or purged.
+    #[serde(default)]
+    pub archived: Vec<Transaction>,
+    /// Saved transactions for quick re-entry; the date is replaced when one is used.
+    #[serde(default)]
+    pub templates: Vec<NewTransaction>,
+    /// Life events marked on the cashflow chart, oldest first.
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    #[serde(default)]
+    pub accounts: Vec<Account>,
     pub next_tx_id: u64,
     pub next_budget_id: u64,
+    /// Per-month aggregates computed on demand and cleared by every transaction mutation.
+    #[serde(skip)]
+    month_cache: RefCell<HashMap<(i32, u32), MonthAggregate>>,
+    /// Reports spread amortized transactions over their months instead of counting them
+    /// when paid. Set from the config after loading.
+    #[serde(skip)]
+    accrual: bool,
+    /// Categories whose transactions are income whatever their sign. Set from the config
+    /// after loading.
+    #[serde(skip)]
+    income_categories: Vec<String>,
+    /// Categories whose spending is fixed or discretionary. Set from the config after
+    /// loading.
+    #[serde(skip)]
+    fixed_categories: Vec<String>,
+    #[serde(skip)]
+    discretionary_categories: Vec<String>,
+    /// Ids of transactions merged in from yearly archive files. They take part in reports
+    /// but are left out when the ledger is saved, since the archive keeps them.
+    #[serde(skip)]
+    from_archive: HashSet<u64>,
 }
 
+/// Category given to opening balance transactions.
+pub const OPENING_BALANCE_CATEGORY: &str = "Opening Balance";
+
+/// Why a transaction merged in from a yearly archive can't be changed.
+pub const ARCHIVED_READ_ONLY: &str =
+    "That transaction comes from a yearly archive and can't be changed here";
+
+/// Complete months averaged by `Ledger::forecast_category`.
+pub const FORECAST_MONTHS: usize = 3;
+
 impl Default for Ledger {
     fn default() -> Self {
         Self::with_sample_data()
@@ -35,30 +262,67 @@ impl Default for Ledger {
 }
 
 impl Ledger {
+    /// A ledger with no transactions or budgets, as built by the first-run wizard.
+    pub fn empty() -> Self {
+        Self {
+            transactions: Vec::new(),
+            budgets: Vec::new(),
+            archived: Vec::new(),
+            templates: Vec::new(),
+            annotations: Vec::new(),
+            accounts: Vec::new(),
+            next_tx_id: 1,
+            next_budget_id: 1,
+            month_cache: RefCell::default(),
+            accrual: false,
+            income_categories: Vec::new(),
+            fixed_categories: Vec::new(),
+            discretionary_categories: Vec::new(),
+            from_archive: HashSet::new(),
+        }
+    }
+
     pub fn with_sample_data() -> Self {
         let now = Local::now().naive_local().date();
         let last_month = now - Duration::days(30);
         let mut ledger = Self {
-            transactions: Vec::new(),
             budgets: vec![
                 Budget {
                     id: 1,
                     category: "Housing".into(),
                     monthly_limit: 1800.0,
+                    note: String::new(),
+                    pinned: false,
+                    icon: String::new(),
+                    direction: BudgetDirection::SpendCap,
+                    period: BudgetPeriod::Monthly,
+                    limit: None,
                 },
                 Budget {
                     id: 2,
                     category: "Food".into(),
                     monthly_limit: 600.0,
+                    note: String::new(),
+                    pinned: false,
+                    icon: String::new(),
+                    direction: BudgetDirection::SpendCap,
+                    period: BudgetPeriod::Monthly,
+                    limit: None,
                 },
                 Budget {
                     id: 3,
                     category: "Transport".into(),
                     monthly_limit: 250.0,
+                    note: String::new(),
+                    pinned: false,
+                    icon: String::new(),
+                    direction: BudgetDirection::SpendCap,
+                    period: BudgetPeriod::Monthly,
+                    limit: None,
                 },
             ],
-            next_tx_id: 1,
             next_budget_id: 4,
+            ..Self::empty()
         };
 
         let sample = vec![
@@ -100,157 +364,1968 @@ impl Ledger {
         amount: f64,
         category: impl Into<String>,
         date: NaiveDate,
-    ) {
+    ) -> u64 {
+        let id = self.next_tx_id;
         let tx = Transaction {
-            id: self.next_tx_id,
+            id,
             description: description.into(),
-            amount,
+            amount: round_cents(amount),
             category: category.into(),
             date,
+            refund_of: None,
+            amortize_months: None,
+            cleared: false,
+            account: None,
+            business: false,
+            opening_balance: false,
+            note: String::new(),
         };
         self.next_tx_id += 1;
-        self.transactions.push(tx);
-        self.transactions.sort_by(|a, b| b.date.cmp(&a.date));
+        // Keep newest-first order; same-day entries go after the existing ones.
+        let index = self.transactions.partition_point(|t| t.date >= date);
+        self.transactions.insert(index, tx);
+        self.invalidate_cache();
+        id
     }
 
-    pub fn add_or_update_budget(&mut self, category: impl Into<String>, monthly_limit: f64) {
-        let category = category.into();
-        if let Some(budget) = self.budgets.iter_mut().find(|b| b.category == category) {
-            budget.monthly_limit = monthly_limit;
-            return;
+    /// Replaces the description, amount, category and date of transaction `id`, keeping its
+    /// id and flags, and its note unless `updated` has one. A new date moves it to its place
+    /// in the newest-first order. Rows merged in from archives are refused, since saving
+    /// leaves them out.
+    pub fn update_transaction(&mut self, id: u64, updated: NewTransaction) -> Result<()> {
+        if self.is_from_archive(id) {
+            return Err(CentshError::Validation(ARCHIVED_READ_ONLY.into()));
         }
+        let index = self
+            .transactions
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or_else(|| CentshError::NotFound(format!("No transaction with id {id}")))?;
+        let mut tx = self.transactions.remove(index);
+        tx.description = updated.description;
+        tx.amount = round_cents(updated.amount);
+        tx.category = updated.category;
+        tx.date = updated.date;
+        if !updated.note.is_empty() {
+            tx.note = updated.note;
+        }
+        let position = self.transactions.partition_point(|t| t.date >= tx.date);
+        self.transactions.insert(position, tx);
+        self.invalidate_cache();
+        Ok(())
+    }
 
-        let budget = Budget {
-            id: self.next_budget_id,
-            category,
-            monthly_limit,
+    /// Moves a transaction to the archive. Returns false when no transaction has `id`.
+    pub fn archive_transaction(&mut self, id: u64) -> bool {
+        let Some(index) = self.transactions.iter().position(|t| t.id == id) else {
+            return false;
         };
-        self.next_budget_id += 1;
-        self.budgets.push(budget);
+        let tx = self.transactions.remove(index);
+        self.archived.insert(0, tx);
+        self.invalidate_cache();
+        true
     }
 
-    pub fn current_month_overview(&self) -> Overview {
-        let now = Local::now().naive_local().date();
-        let (income, outgoing) = self.transactions.iter().fold((0.0, 0.0), |mut acc, tx| {
-            if tx.date.year() == now.year() && tx.date.month() == now.month() {
-                if tx.amount < 0.0 {
-                    acc.0 += -tx.amount;
-                } else {
-                    acc.1 += tx.amount;
+    /// Puts an archived transaction back in the ledger. Returns false when it is not archived.
+    pub fn restore_archived(&mut self, id: u64) -> bool {
+        let Some(index) = self.archived.iter().position(|t| t.id == id) else {
+            return false;
+        };
+        let tx = self.archived.remove(index);
+        let position = self.transactions.partition_point(|t| t.date >= tx.date);
+        self.transactions.insert(position, tx);
+        self.invalidate_cache();
+        true
+    }
+
+    /// Permanently drops everything in the archive and returns how many were removed.
+    pub fn purge_archive(&mut self) -> usize {
+        let count = self.archived.len();
+        self.archived.clear();
+        count
+    }
+
+    /// Saves a transaction as a reusable template.
+    pub fn save_template(&mut self, template: NewTransaction) {
+        self.templates.push(template);
+    }
+
+    /// Replaces the template at `index`. Returns false when there is none.
+    pub fn update_template(&mut self, index: usize, template: NewTransaction) -> bool {
+        match self.templates.get_mut(index) {
+            Some(existing) => {
+                *existing = template;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_template(&mut self, index: usize) -> Option<NewTransaction> {
+        (index < self.templates.len()).then(|| self.templates.remove(index))
+    }
+
+    /// Normalizes every stored category with `normalize_category` so variants such as
+    /// "Food " and "Food" merge. Budgets that end up on the same category are merged into
+    /// the first one, adding up their limits and notes, and named in the result.
+    pub fn normalize_categories(&mut self, title_case: bool) -> CategoryCleanup {
+        let mut changed = 0;
+        let mut fix = |category: &mut String| {
+            let normalized = normalize_category(category, title_case);
+            if *category != normalized {
+                *category = normalized;
+                changed += 1;
+            }
+        };
+        self.transactions
+            .iter_mut()
+            .chain(self.archived.iter_mut())
+            .for_each(|t| fix(&mut t.category));
+        self.templates.iter_mut().for_each(|t| fix(&mut t.category));
+        self.budgets.iter_mut().for_each(|b| fix(&mut b.category));
+
+        let mut merged_budgets = Vec::new();
+        let mut kept: Vec<Budget> = Vec::with_capacity(self.budgets.len());
+        for budget in std::mem::take(&mut self.budgets) {
+            let Some(first) = kept.iter_mut().find(|b| b.category == budget.category) else {
+                kept.push(budget);
+                continue;
+            };
+            first.monthly_limit = round_cents(first.monthly_limit + budget.monthly_limit);
+            first.limit = match (first.limit, budget.limit) {
+                (Some(a), Some(b)) if first.period == budget.period => Some(round_cents(a + b)),
+                _ => None,
+            };
+            if !budget.note.is_empty() {
+                if !first.note.is_empty() {
+                    first.note.push_str("; ");
                 }
+                first.note.push_str(&budget.note);
             }
-            acc
+            first.pinned |= budget.pinned;
+            if !merged_budgets.contains(&first.category) {
+                merged_budgets.push(first.category.clone());
+            }
+        }
+        self.budgets = kept;
+        if changed > 0 {
+            self.invalidate_cache();
+        }
+        CategoryCleanup {
+            changed,
+            merged_budgets,
+        }
+    }
+
+    /// Switches reports between cash (amounts count when paid) and accrual (amortized
+    /// transactions are spread over their months) treatment.
+    pub fn set_accrual(&mut self, accrual: bool) {
+        self.accrual = accrual;
+        self.invalidate_cache();
+    }
+
+    /// Flags `categories` (and their subcategories) as income, so every transaction in them
+    /// counts as income regardless of sign and stays out of spending.
+    pub fn set_income_categories(&mut self, categories: Vec<String>) {
+        self.income_categories = categories;
+        self.invalidate_cache();
+    }
+
+    /// Whether `category` was flagged as income; see `set_income_categories`.
+    pub fn is_income_category(&self, category: &str) -> bool {
+        in_category_list(category, &self.income_categories)
+    }
+
+    /// Sorts spending categories (and their subcategories) into fixed and discretionary for
+    /// `fixed_vs_discretionary`. A category in both lists counts as fixed.
+    pub fn set_spending_classes(&mut self, fixed: Vec<String>, discretionary: Vec<String>) {
+        self.fixed_categories = fixed;
+        self.discretionary_categories = discretionary;
+    }
+
+    /// The month's spending split into fixed, discretionary and unclassified, per the
+    /// categories passed to `set_spending_classes`. A bucket never goes below zero, even when
+    /// refunds outweigh its spending.
+    pub fn fixed_vs_discretionary(&self, month: NaiveDate) -> SpendingSplit {
+        let mut split = SpendingSplit::default();
+        for (category, amount) in self.category_spending_for_month(month) {
+            let bucket = if in_category_list(&category, &self.fixed_categories) {
+                &mut split.fixed
+            } else if in_category_list(&category, &self.discretionary_categories) {
+                &mut split.discretionary
+            } else {
+                &mut split.unclassified
+            };
+            *bucket += amount;
+        }
+        split.fixed = round_cents(split.fixed.max(0.0));
+        split.discretionary = round_cents(split.discretionary.max(0.0));
+        split.unclassified = round_cents(split.unclassified.max(0.0));
+        split
+    }
+
+    /// Adds an account and returns its id. Names must be unique (case-insensitive).
+    pub fn add_account(&mut self, name: &str, default_category: &str) -> Result<u64> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(CentshError::Validation("Account name is required".into()));
+        }
+        if self
+            .accounts
+            .iter()
+            .any(|a| a.name.eq_ignore_ascii_case(name))
+        {
+            return Err(CentshError::Validation(format!(
+                "An account named {name} already exists"
+            )));
+        }
+        let id = self.accounts.iter().map(|a| a.id).max().unwrap_or(0) + 1;
+        self.accounts.push(Account {
+            id,
+            name: name.to_string(),
+            default_category: default_category.trim().to_string(),
         });
+        Ok(id)
+    }
 
-        Overview {
-            total_income: income,
-            total_outgoing: outgoing,
-            net: income - outgoing,
+    /// Records `balance` (positive when money is held, negative for a debt such as a credit
+    /// card) as the starting balance of `account`, or of untracked money for `None`, on
+    /// `date`. Replaces that account's previous opening balance. Returns the transaction id.
+    pub fn set_opening_balance(
+        &mut self,
+        account: Option<u64>,
+        balance: f64,
+        date: NaiveDate,
+    ) -> u64 {
+        self.transactions
+            .retain(|t| !(t.opening_balance && t.account == account));
+        let id = self.add_transaction("Opening balance", -balance, OPENING_BALANCE_CATEGORY, date);
+        if let Some(tx) = self.transactions.iter_mut().find(|t| t.id == id) {
+            tx.opening_balance = true;
+            tx.cleared = true;
+            tx.account = account;
         }
+        id
     }
 
-    pub fn category_spending_current_month(&self) -> Vec<(String, f64)> {
-        let now = Local::now().naive_local().date();
-        let mut by_category: HashMap<String, f64> = HashMap::new();
-        for tx in self.transactions.iter().filter(|t| {
-            t.amount > 0.0 && t.date.year() == now.year() && t.date.month() == now.month()
-        }) {
-            *by_category.entry(tx.category.clone()).or_insert(0.0) += tx.amount;
+    pub fn account(&self, id: u64) -> Option<&Account> {
+        self.accounts.iter().find(|a| a.id == id)
+    }
+
+    /// The account called `name`, compared case-insensitively.
+    pub fn account_by_name(&self, name: &str) -> Option<&Account> {
+        let name = name.trim();
+        self.accounts
+            .iter()
+            .find(|a| a.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Records which account a transaction went through. Returns false when no transaction
+    /// has `id`.
+    pub fn set_transaction_account(&mut self, id: u64, account: Option<u64>) -> bool {
+        match self.transactions.iter_mut().find(|t| t.id == id) {
+            Some(tx) => {
+                tx.account = account;
+                true
+            }
+            None => false,
         }
+    }
 
-        let mut pairs: Vec<_> = by_category.into_iter().collect();
-        pairs.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
-        pairs
+    /// Replaces the note on a transaction. Returns false when no transaction has `id`.
+    pub fn set_transaction_note(&mut self, id: u64, note: impl Into<String>) -> bool {
+        match self.transactions.iter_mut().find(|t| t.id == id) {
+            Some(tx) => {
+                tx.note = note.into();
+                true
+            }
+            None => false,
+        }
     }
 
-    pub fn budgets_by_category(&self) -> HashMap<String, f64> {
-        let mut map = HashMap::new();
-        for budget in &self.budgets {
-            map.insert(budget.category.clone(), budget.monthly_limit);
+    /// Sets how many months the transaction's amount is spread over; `None` or 1 counts it
+    /// once. Returns false when no transaction has `id` or it was merged in from an archive.
+    pub fn set_amortization(&mut self, id: u64, months: Option<u32>) -> bool {
+        if self.is_from_archive(id) {
+            return false;
         }
-        map
+        let Some(tx) = self.transactions.iter_mut().find(|t| t.id == id) else {
+            return false;
+        };
+        tx.amortize_months = months.filter(|m| *m > 1);
+        self.invalidate_cache();
+        true
     }
 
-    pub fn spending_last_n_months(&self, months: usize) -> Vec<(String, f64)> {
-        if months == 0 {
-            return Vec::new();
+    /// Adds an event to show on the cashflow chart, keeping annotations in date order.
+    pub fn add_annotation(&mut self, date: NaiveDate, label: impl Into<String>) {
+        let index = self.annotations.partition_point(|a| a.date <= date);
+        self.annotations.insert(
+            index,
+            Annotation {
+                date,
+                label: label.into(),
+            },
+        );
+    }
+
+    /// Rounds every stored amount and limit to the cent, undoing float noise such as
+    /// `32.50000000000001` in files written by older versions or by hand.
+    pub fn round_amounts(&mut self) {
+        for tx in self.transactions.iter_mut().chain(self.archived.iter_mut()) {
+            tx.amount = round_cents(tx.amount);
         }
-        let mut bucket: HashMap<(i32, u32), f64> = HashMap::new();
-        let now = Local::now().naive_local().date();
-        let earliest = now - Duration::days((months as i64) * 31);
+        for template in &mut self.templates {
+            template.amount = round_cents(template.amount);
+        }
+        for budget in &mut self.budgets {
+            budget.monthly_limit = round_cents(budget.monthly_limit);
+            budget.limit = budget.limit.map(round_cents);
+        }
+        self.invalidate_cache();
+    }
 
-        for tx in &self.transactions {
-            if tx.date < earliest {
+    /// Restores newest-first order, e.g. after loading a hand-edited file.
+    pub fn sort_transactions(&mut self) {
+        self.transactions.sort_by_key(|t| Reverse(t.date));
+        self.invalidate_cache();
+    }
+
+    /// Removes and returns the transactions dated in `year`, oldest first.
+    pub fn take_year(&mut self, year: i32) -> Vec<Transaction> {
+        let (mut taken, kept) = std::mem::take(&mut self.transactions)
+            .into_iter()
+            .partition::<Vec<_>, _>(|t| t.date.year() == year);
+        self.transactions = kept;
+        for tx in &taken {
+            self.from_archive.remove(&tx.id);
+        }
+        taken.reverse();
+        self.invalidate_cache();
+        taken
+    }
+
+    /// Adds transactions read back from yearly archives so reports can cover those years.
+    /// Ids already in the ledger or the trash are skipped. Returns how many were added.
+    pub fn merge_history(&mut self, history: Vec<Transaction>) -> usize {
+        let known: HashSet<u64> = self
+            .transactions
+            .iter()
+            .chain(&self.archived)
+            .map(|t| t.id)
+            .collect();
+        let mut added = 0;
+        for tx in history {
+            if known.contains(&tx.id) || !self.from_archive.insert(tx.id) {
                 continue;
             }
-            let key = (tx.date.year(), tx.date.month());
-            // Treat income (negative numbers) as positive inflow.
-            *bucket.entry(key).or_insert(0.0) += -tx.amount;
+            // Archives share the id counter, but never hand out an id they already use.
+            self.next_tx_id = self.next_tx_id.max(tx.id + 1);
+            self.transactions.push(tx);
+            added += 1;
         }
+        self.sort_transactions();
+        added
+    }
 
-        let mut series: Vec<_> = bucket
+    /// Takes the archived years merged by `merge_history` back out.
+    pub fn drop_history(&mut self) {
+        let from_archive = std::mem::take(&mut self.from_archive);
+        self.transactions.retain(|t| !from_archive.contains(&t.id));
+        self.invalidate_cache();
+    }
+
+    pub fn history_loaded(&self) -> bool {
+        !self.from_archive.is_empty()
+    }
+
+    /// Whether transaction `id` was merged in by `merge_history`. Such rows are read-only:
+    /// they live in the archive files and are left out when the ledger is saved.
+    pub fn is_from_archive(&self, id: u64) -> bool {
+        self.from_archive.contains(&id)
+    }
+
+    /// The ledger as it should be saved: without transactions merged in from archives.
+    pub fn without_history(&self) -> Option<Ledger> {
+        if self.from_archive.is_empty() {
+            return None;
+        }
+        let mut lean = self.clone();
+        lean.drop_history();
+        Some(lean)
+    }
+
+    fn invalidate_cache(&self) {
+        self.month_cache.borrow_mut().clear();
+    }
+
+    /// Cached totals for the month containing `month`.
+    fn month_aggregate(&self, month: NaiveDate) -> MonthAggregate {
+        let key = (month.year(), month.month());
+        if let Some(cached) = self.month_cache.borrow().get(&key) {
+            return cached.clone();
+        }
+        let aggregate = self.compute_aggregate(DateRange::month(month));
+        self.month_cache.borrow_mut().insert(key, aggregate.clone());
+        aggregate
+    }
+
+    /// One pass over the range's postings, so linked refunds reduce spending in the original
+    /// purchase's category and month instead of counting as income.
+    fn compute_aggregate(&self, range: DateRange) -> MonthAggregate {
+        let mut income = 0.0;
+        let mut outgoing = 0.0;
+        let mut transaction_count = 0;
+        let mut by_category: HashMap<String, f64> = HashMap::new();
+        for posting in self.query().range(range).iter() {
+            transaction_count += 1;
+            match posting.kind {
+                TxKind::Income => income -= posting.amount,
+                TxKind::Expense => {
+                    outgoing += posting.amount;
+                    if posting.amount != 0.0 {
+                        *by_category
+                            .entry(posting.category.to_string())
+                            .or_insert(0.0) += posting.amount;
+                    }
+                }
+            }
+        }
+
+        let mut by_category: Vec<_> = by_category
             .into_iter()
-            .map(|((y, m), v)| (format!("{y}-{m:02}"), v))
+            .map(|(category, amount)| (category, round_cents(amount)))
             .collect();
-        series.sort_by(|a, b| a.0.cmp(&b.0));
-        series
+        by_category.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        let (income, outgoing) = (round_cents(income), round_cents(outgoing));
+        MonthAggregate {
+            overview: Overview {
+                total_income: income,
+                total_outgoing: outgoing,
+                net: round_cents(income - outgoing),
+            },
+            by_category,
+            transaction_count,
+        }
     }
 
-    pub fn suggested_budgets(&self) -> Vec<BudgetSuggestion> {
-        let cutoff = Local::now().naive_local().date() - Duration::days(90);
-        let mut spend: HashMap<String, f64> = HashMap::new();
-        for tx in self
+    pub fn add_or_update_budget(
+        &mut self,
+        category: impl Into<String>,
+        monthly_limit: f64,
+        note: impl Into<String>,
+    ) {
+        let category = category.into();
+        let note = note.into();
+        let monthly_limit = round_cents(monthly_limit);
+        if let Some(budget) = self.budgets.iter_mut().find(|b| b.category == category) {
+            budget.monthly_limit = monthly_limit;
+            budget.limit = None;
+            budget.note = note;
+            return;
+        }
+
+        let budget = Budget {
+            id: self.next_budget_id,
+            category,
+            monthly_limit,
+            note,
+            pinned: false,
+            icon: String::new(),
+            direction: BudgetDirection::SpendCap,
+            period: BudgetPeriod::Monthly,
+            limit: None,
+        };
+        self.next_budget_id += 1;
+        self.budgets.push(budget);
+    }
+
+    /// Changes the budget for `original`, keeping its position and pin. Renaming it onto
+    /// another budget's category is refused rather than merging the two.
+    pub fn edit_budget(
+        &mut self,
+        original: &str,
+        category: impl Into<String>,
+        monthly_limit: f64,
+        note: impl Into<String>,
+    ) -> Result<()> {
+        let category = category.into();
+        if category != original && self.budgets.iter().any(|b| b.category == category) {
+            return Err(CentshError::Validation(format!(
+                "A budget for {category} already exists"
+            )));
+        }
+        let budget = self
+            .budgets
+            .iter_mut()
+            .find(|b| b.category == original)
+            .ok_or_else(|| CentshError::NotFound(format!("No budget for {original}")))?;
+        budget.category = category;
+        budget.monthly_limit = round_cents(monthly_limit);
+        budget.limit = None;
+        budget.note = note.into();
+        Ok(())
+    }
+
+    /// Sets the icon (empty for none) and direction of `category`'s budget.
+    pub fn set_budget_options(&mut self, category: &str, icon: &str, direction: BudgetDirection) {
+        if let Some(budget) = self.budgets.iter_mut().find(|b| b.category == category) {
+            budget.icon = icon.trim().to_string();
+            budget.direction = direction;
+        }
+    }
+
+    /// Makes `category`'s budget reset every `period` with `limit` per period, kept as
+    /// entered; the monthly limit becomes its monthly equivalent.
+    pub fn set_budget_period(&mut self, category: &str, period: BudgetPeriod, limit: f64) {
+        if let Some(budget) = self.budgets.iter_mut().find(|b| b.category == category) {
+            let limit = round_cents(limit);
+            budget.period = period;
+            budget.monthly_limit = round_cents(limit * period.per_month());
+            budget.limit = (period != BudgetPeriod::Monthly).then_some(limit);
+        }
+    }
+
+    /// The icon set on `category`'s budget, if any.
+    pub fn category_icon(&self, category: &str) -> Option<&str> {
+        self.budgets
+            .iter()
+            .find(|b| b.category == category && !b.icon.is_empty())
+            .map(|b| b.icon.as_str())
+    }
+
+    pub fn remove_budget(&mut self, category: &str) -> Option<Budget> {
+        let index = self.budgets.iter().position(|b| b.category == category)?;
+        Some(self.budgets.remove(index))
+    }
+
+    /// Turns suggestions into budgets, skipping zero-limit placeholders. Categories that
+    /// already have a budget only change when `overwrite` is set. Returns how many budgets
+    /// were created and updated.
+    pub fn apply_suggestions(
+        &mut self,
+        suggestions: &[BudgetSuggestion],
+        overwrite: bool,
+    ) -> (usize, usize) {
+        let (mut created, mut updated) = (0, 0);
+        for suggestion in suggestions.iter().filter(|s| s.suggested_limit > 0.0) {
+            match self
+                .budgets
+                .iter_mut()
+                .find(|b| b.category == suggestion.category)
+            {
+                Some(budget) if overwrite => {
+                    budget.monthly_limit = suggestion.suggested_limit;
+                    budget.limit = None;
+                    updated += 1;
+                }
+                Some(_) => {}
+                None => {
+                    self.add_or_update_budget(
+                        suggestion.category.clone(),
+                        suggestion.suggested_limit,
+                        "",
+                    );
+                    created += 1;
+                }
+            }
+        }
+        (created, updated)
+    }
+
+    /// Marks `refund_id` as a (possibly partial) refund of the earlier `original_id`. A
+    /// refund merged in from an archive is refused, since saving leaves it out.
+    pub fn link_refund(&mut self, refund_id: u64, original_id: u64) -> Result<()> {
+        if self.is_from_archive(refund_id) {
+            return Err(CentshError::Validation(ARCHIVED_READ_ONLY.into()));
+        }
+        if refund_id == original_id {
+            return Err(CentshError::Validation(
+                "A transaction cannot refund itself".into(),
+            ));
+        }
+        let original_date = self
             .transactions
             .iter()
-            .filter(|t| t.date >= cutoff && t.amount > 0.0)
-        {
-            *spend.entry(tx.category.clone()).or_insert(0.0) += tx.amount;
+            .find(|t| t.id == original_id)
+            .map(|t| t.date)
+            .ok_or_else(|| {
+                CentshError::NotFound(format!("No transaction with id {original_id}"))
+            })?;
+        let refund = self
+            .transactions
+            .iter_mut()
+            .find(|t| t.id == refund_id)
+            .ok_or_else(|| CentshError::NotFound(format!("No transaction with id {refund_id}")))?;
+        if refund.date < original_date {
+            return Err(CentshError::Validation(
+                "A refund cannot predate the original purchase".into(),
+            ));
         }
+        refund.refund_of = Some(original_id);
+        self.invalidate_cache();
+        Ok(())
+    }
 
-        let window_months = 3.0;
-        let mut suggestions: Vec<_> = spend
-            .into_iter()
-            .map(|(cat, amt)| {
-                let base = (amt / window_months).max(50.0);
-                let suggested = (base * 1.1 * 100.0).round() / 100.0; // 10% buffer
-                BudgetSuggestion {
-                    category: cat.clone(),
-                    suggested_limit: suggested,
-                    reason: "Last 90 days average + 10% buffer".to_string(),
-                }
+    /// Starts a query over every transaction; narrow it with the `LedgerQuery` builders.
+    pub fn query(&self) -> LedgerQuery<'_> {
+        LedgerQuery {
+            ledger: self,
+            month: None,
+            since: None,
+            until: None,
+            category: None,
+            kind: None,
+            search: SearchQuery::default(),
+            excluded: &[],
+        }
+    }
+
+    /// `tx` as reports count it: one posting, or one equal share per month for an amortized
+    /// transaction under accrual treatment.
+    fn postings<'a>(&'a self, tx: &'a Transaction) -> Vec<Posting<'a>> {
+        if tx.opening_balance {
+            return Vec::new();
+        }
+        let posting = self.posting(tx);
+        let months = match tx.amortize_months {
+            Some(months) if self.accrual && months > 1 && tx.refund_of.is_none() => months,
+            _ => return vec![posting],
+        };
+        (0..months)
+            .filter_map(|i| posting.date.checked_add_months(Months::new(i)))
+            .map(|date| Posting {
+                date,
+                amount: posting.amount / months as f64,
+                ..posting
             })
-            .collect();
+            .collect()
+    }
 
-        // Encourage common buckets if user does not have any data yet.
-        if suggestions.is_empty() {
-            suggestions = vec![
-                BudgetSuggestion {
-                    category: "Housing".into(),
-                    suggested_limit: 0.0,
-                    reason: "Add your rent/mortgage so you can track it monthly".into(),
-                },
-                BudgetSuggestion {
-                    category: "Food".into(),
-                    suggested_limit: 0.0,
-                    reason: "Groceries, coffee, restaurants".into(),
-                },
-                BudgetSuggestion {
-                    category: "Savings".into(),
-                    suggested_limit: 0.0,
-                    reason: "Pay yourself first".into(),
+    /// A linked refund is an expense reduction dated and categorized like its original
+    /// purchase; everything else keeps its own fields. Income is money coming in or anything
+    /// in a flagged income category.
+    fn posting<'a>(&'a self, tx: &'a Transaction) -> Posting<'a> {
+        match self.refund_original(tx) {
+            Some(original) => Posting {
+                tx,
+                date: original.date,
+                category: &original.category,
+                amount: -tx.amount.abs(),
+                kind: TxKind::Expense,
+            },
+            None => Posting {
+                tx,
+                date: tx.date,
+                category: &tx.category,
+                amount: tx.amount,
+                kind: if tx.amount < 0.0 || self.is_income_category(&tx.category) {
+                    TxKind::Income
+                } else {
+                    TxKind::Expense
                 },
-            ];
+            },
         }
+    }
 
-        suggestions.sort_by(|a, b| {
-            b.suggested_limit
-                .partial_cmp(&a.suggested_limit)
+    /// An existing transaction with the same date, amount and description (ignoring case), as
+    /// a double submit would leave behind.
+    pub fn find_duplicate(&self, tx: &NewTransaction) -> Option<&Transaction> {
+        self.transactions.iter().find(|t| {
+            t.date == tx.date
+                && (t.amount - tx.amount).abs() < 0.005
+                && t.description
+                    .trim()
+                    .eq_ignore_ascii_case(tx.description.trim())
+        })
+    }
+
+    /// The purchase `tx` refunds, when it is a linked refund.
+    pub fn refund_original(&self, tx: &Transaction) -> Option<&Transaction> {
+        let id = tx.refund_of?;
+        self.transactions.iter().find(|t| t.id == id)
+    }
+
+    /// Refunds linked to the transaction with `id`.
+    pub fn refunds_of(&self, id: u64) -> Vec<&Transaction> {
+        self.transactions
+            .iter()
+            .filter(|t| t.refund_of == Some(id))
+            .collect()
+    }
+
+    /// Transactions whose description or category contains `query`. An empty query matches all.
+    pub fn filter_transactions(&self, query: &str) -> Vec<&Transaction> {
+        self.transactions_by_kind(query, None)
+    }
+
+    /// Transactions matching `query` that are income or spending as reports count them
+    /// (a linked refund is spending), newest first. `None` keeps both.
+    pub fn transactions_by_kind(&self, query: &str, kind: Option<TxKind>) -> Vec<&Transaction> {
+        let search = parse_search_query(query);
+        self.transactions
+            .iter()
+            .filter(|tx| search.matches(tx))
+            .filter(|tx| kind.is_none_or(|k| !tx.opening_balance && self.posting(tx).kind == k))
+            .collect()
+    }
+
+    /// Count of transactions matching `query` and `kind` and the sum of their amounts,
+    /// leaving out `excluded` categories from the sum.
+    pub fn filter_summary(
+        &self,
+        query: &str,
+        kind: Option<TxKind>,
+        excluded: &[String],
+    ) -> (usize, f64) {
+        let count = self.transactions_by_kind(query, kind).len();
+        let mut total = self.query().text(query).excluding(excluded);
+        if let Some(kind) = kind {
+            total = total.kind(kind);
+        }
+        (count, total.sum())
+    }
+
+    /// Opening balances plus income minus spending over every transaction through `account`
+    /// (all of them for `None`) dated on or before `date`. Refunds count on the day the money
+    /// came back.
+    pub fn balance_as_of(&self, date: NaiveDate, account: Option<u64>) -> f64 {
+        let balance = self
+            .transactions
+            .iter()
+            .filter(|t| t.date <= date && (account.is_none() || t.account == account))
+            .map(|t| -t.amount)
+            .sum::<f64>();
+        round_cents(balance)
+    }
+
+    /// Transactions through `account` (all of them for `None`), oldest first, each with the
+    /// balance after it. Opening balances come first whatever their date, so every balance
+    /// includes them; from there money in raises it and money out lowers it.
+    pub fn running_balances(&self, account: Option<u64>) -> Vec<(&Transaction, f64)> {
+        let mut rows: Vec<&Transaction> = self
+            .transactions
+            .iter()
+            .rev()
+            .filter(|t| account.is_none() || t.account == account)
+            .collect();
+        rows.sort_by_key(|t| !t.opening_balance);
+        let mut balance = 0.0;
+        rows.into_iter()
+            .map(|t| {
+                balance = round_cents(balance - t.amount);
+                (t, balance)
+            })
+            .collect()
+    }
+
+    /// Compares a statement's ending balance with `balance_as_of`. Transactions not yet
+    /// marked cleared are listed as the likely cause of any difference, newest first.
+    pub fn reconcile(&self, target: f64, as_of: NaiveDate) -> Reconciliation<'_> {
+        let ledger_balance = self.balance_as_of(as_of, None);
+        let uncleared: Vec<&Transaction> = self
+            .transactions
+            .iter()
+            .filter(|t| t.date <= as_of && !t.cleared)
+            .collect();
+        let difference = round_cents(target - ledger_balance);
+        // The bank has not seen uncleared transactions yet, so they explain a difference of
+        // exactly their summed amounts.
+        let pending: f64 = uncleared.iter().map(|t| t.amount).sum();
+        Reconciliation {
+            ledger_balance,
+            difference,
+            unexplained: round_cents(difference - pending),
+            uncleared,
+        }
+    }
+
+    /// Flips the cleared flag and returns the new value, or `None` when no transaction has
+    /// `id` or it was merged in from an archive.
+    pub fn toggle_cleared(&mut self, id: u64) -> Option<bool> {
+        if self.is_from_archive(id) {
+            return None;
+        }
+        let tx = self.transactions.iter_mut().find(|t| t.id == id)?;
+        tx.cleared = !tx.cleared;
+        Some(tx.cleared)
+    }
+
+    /// Flips the business flag and returns the new value, or `None` when no transaction has
+    /// `id` or it was merged in from an archive.
+    pub fn toggle_business(&mut self, id: u64) -> Option<bool> {
+        if self.is_from_archive(id) {
+            return None;
+        }
+        let tx = self.transactions.iter_mut().find(|t| t.id == id)?;
+        tx.business = !tx.business;
+        Some(tx.business)
+    }
+
+    /// Business-flagged spending dated in `year`, or `None` when no transaction is flagged.
+    pub fn business_spending(&self, year: i32) -> Option<f64> {
+        let mut flagged = self.transactions.iter().filter(|t| t.business).peekable();
+        flagged.peek()?;
+        let total = flagged
+            .filter(|t| t.date.year() == year)
+            .map(|t| self.posting(t))
+            .filter(|p| p.kind == TxKind::Expense)
+            .map(|p| p.amount)
+            .sum();
+        Some(round_cents(total))
+    }
+
+    /// Income, spending and net for the calendar month containing `month`. Linked refunds
+    /// reduce spending in the month of the original purchase instead of counting as income.
+    pub fn month_overview(&self, month: NaiveDate) -> Overview {
+        self.month_aggregate(month).overview
+    }
+
+    /// Spending per category for the month containing `month`, largest first. Linked refunds
+    /// are netted against the original purchase's category and month.
+    pub fn category_spending_for_month(&self, month: NaiveDate) -> Vec<(String, f64)> {
+        self.month_aggregate(month).by_category
+    }
+
+    /// `month_overview` for the current month.
+    pub fn current_month_overview(&self) -> Overview {
+        self.overview_for_range(DateRange::month(Local::now().naive_local().date()))
+    }
+
+    /// `category_spending_for_month` for the current month.
+    pub fn category_spending_current_month(&self) -> Vec<(String, f64)> {
+        self.category_spending_for_range(DateRange::month(Local::now().naive_local().date()))
+    }
+
+    /// `month_overview` for any span of days. Whole calendar months come from the month cache.
+    pub fn overview_for_range(&self, range: DateRange) -> Overview {
+        self.range_aggregate(range).overview
+    }
+
+    /// `category_spending_for_month` for any span of days.
+    pub fn category_spending_for_range(&self, range: DateRange) -> Vec<(String, f64)> {
+        self.range_aggregate(range).by_category
+    }
+
+    fn range_aggregate(&self, range: DateRange) -> MonthAggregate {
+        if range.is_month() {
+            self.month_aggregate(range.start)
+        } else {
+            self.compute_aggregate(range)
+        }
+    }
+
+    /// What is left of `category`'s budget in its period containing `date` (the week for a
+    /// weekly budget): the period's limit minus spending, negative once overspent. For a save
+    /// target it is the amount still to save. Zero when the category has no budget.
+    pub fn budget_remaining(&self, category: &str, date: NaiveDate) -> f64 {
+        let Some(budget) = self.budgets.iter().find(|b| b.category == category) else {
+            return 0.0;
+        };
+        let spent = self.budget_spent_in(category, budget.period.containing(date));
+        round_cents(budget.period_limit() - spent)
+    }
+
+    /// Where the month's money came from: every category whose postings net to money in,
+    /// with that amount as a positive number, biggest first. `excluded` categories such as
+    /// transfers are left out.
+    pub fn income_by_category(&self, month: NaiveDate, excluded: &[String]) -> Vec<(String, f64)> {
+        let mut net: HashMap<&str, f64> = HashMap::new();
+        for posting in self.query().month(month).excluding(excluded).iter() {
+            *net.entry(posting.category).or_insert(0.0) += posting.amount;
+        }
+        let mut sources: Vec<_> = net
+            .into_iter()
+            .map(|(category, amount)| (category.to_string(), round_cents(-amount)))
+            .filter(|(_, amount)| *amount > 0.0)
+            .collect();
+        sources.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        sources
+    }
+
+    /// Spending counted against a budget for `category` in the month containing `month`: the
+    /// category itself plus its subcategories, so "Food" includes "Food/Groceries".
+    pub fn budget_spent(&self, category: &str, month: NaiveDate) -> f64 {
+        self.budget_spent_in(category, DateRange::month(month))
+    }
+
+    /// `budget_spent` over any span of days, e.g. a weekly budget's current week.
+    pub fn budget_spent_in(&self, category: &str, range: DateRange) -> f64 {
+        let spent = self
+            .category_spending_for_range(range)
+            .into_iter()
+            .filter(|(c, _)| in_category(c, category))
+            .map(|(_, amount)| amount)
+            .sum();
+        round_cents(spent)
+    }
+
+    /// The month's spending grouped by parent category (see `category_parent`), biggest
+    /// first. Each entry is the parent's total, which includes spending recorded on the parent
+    /// itself, and its subcategories by name after the separator. Flat categories have no
+    /// children.
+    pub fn category_rollup(&self, range: DateRange) -> Vec<CategoryRollup> {
+        let mut rollup: Vec<CategoryRollup> = Vec::new();
+        for (category, amount) in self.category_spending_for_range(range) {
+            let (parent, child) = match split_category(&category) {
+                Some((parent, child)) => (parent, Some(child)),
+                None => (category.as_str(), None),
+            };
+            let index = match rollup.iter().position(|(p, _, _)| p == parent) {
+                Some(index) => index,
+                None => {
+                    rollup.push((parent.to_string(), 0.0, Vec::new()));
+                    rollup.len() - 1
+                }
+            };
+            let entry = &mut rollup[index];
+            entry.1 += amount;
+            if let Some(child) = child {
+                entry.2.push((child.to_string(), amount));
+            }
+        }
+        for entry in &mut rollup {
+            entry.1 = round_cents(entry.1);
+        }
+        rollup.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        rollup
+    }
+
+    /// Average daily spending over the `window_days` days ending on `as_of`, leaving out
+    /// income and `excluded` categories such as transfers.
+    pub fn spending_velocity(
+        &self,
+        as_of: NaiveDate,
+        window_days: u32,
+        excluded: &[String],
+    ) -> f64 {
+        let window_days = window_days.max(1);
+        let start = as_of - Duration::days(i64::from(window_days) - 1);
+        let spent: f64 = self
+            .query()
+            .since(start)
+            .kind(TxKind::Expense)
+            .excluding(excluded)
+            .iter()
+            .filter(|p| p.date <= as_of)
+            .map(|p| p.amount)
+            .sum();
+        spent / f64::from(window_days)
+    }
+
+    /// How many times faster than the month's own daily average the last `window_days` were
+    /// spent, once the month is far enough along for the two to differ. `None` early in the
+    /// month or before any spending.
+    pub fn velocity_ratio(
+        &self,
+        as_of: NaiveDate,
+        window_days: u32,
+        excluded: &[String],
+    ) -> Option<f64> {
+        let elapsed = as_of.day();
+        if elapsed <= window_days {
+            return None;
+        }
+        let month_spent = self
+            .query()
+            .month(as_of)
+            .kind(TxKind::Expense)
+            .excluding(excluded)
+            .iter()
+            .filter(|p| p.date <= as_of)
+            .map(|p| p.amount)
+            .sum::<f64>();
+        let month_daily = month_spent / f64::from(elapsed);
+        (month_daily > 0.0)
+            .then(|| self.spending_velocity(as_of, window_days, excluded) / month_daily)
+    }
+
+    /// Average spending over the `months` complete months before the one containing `as_of`
+    /// (the partial current month would drag it down). With less history than that, only
+    /// months from the first transaction on are averaged; zero when there are none.
+    pub fn average_monthly_spend(&self, months: usize, as_of: NaiveDate) -> f64 {
+        let Some(first) = self
+            .transactions
+            .iter()
+            .filter(|t| !t.opening_balance)
+            .map(|t| t.date)
+            .min()
+        else {
+            return 0.0;
+        };
+        let this_month = as_of.with_day(1).unwrap_or(as_of);
+        let first_month = first.with_day(1).unwrap_or(first);
+        let totals: Vec<f64> = (1..=months as u32)
+            .filter_map(|back| this_month.checked_sub_months(Months::new(back)))
+            .filter(|month| *month >= first_month)
+            .map(|month| self.month_overview(month).total_outgoing)
+            .collect();
+        if totals.is_empty() {
+            return 0.0;
+        }
+        round_cents(totals.iter().sum::<f64>() / totals.len() as f64)
+    }
+
+    /// How `proposed` (category, monthly limit) budgets would have fared in each of the
+    /// `months` complete months before this one, oldest first. Spending is counted like real
+    /// budgets, subcategories included; the ledger's budgets are left untouched.
+    pub fn simulate_budgets(
+        &self,
+        proposed: &[(String, f64)],
+        months: usize,
+    ) -> Vec<MonthBudgetResult> {
+        let today = Local::now().naive_local().date();
+        let this_month = today.with_day(1).unwrap_or(today);
+        (1..=months as u32)
+            .rev()
+            .filter_map(|back| this_month.checked_sub_months(Months::new(back)))
+            .map(|month| MonthBudgetResult {
+                month,
+                budgets: proposed
+                    .iter()
+                    .map(|(category, limit)| SimulatedBudget {
+                        category: category.clone(),
+                        limit: *limit,
+                        spent: self.budget_spent(category, month),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Spending per category in the months containing `a` and `b`, with categories missing
+    /// from one month shown as zero there. Largest change first.
+    pub fn compare_months(&self, a: NaiveDate, b: NaiveDate) -> Vec<CategoryComparison> {
+        let mut by_category: BTreeMap<String, (f64, f64)> = BTreeMap::new();
+        for (category, amount) in self.category_spending_for_month(a) {
+            by_category.entry(category).or_default().0 = amount;
+        }
+        for (category, amount) in self.category_spending_for_month(b) {
+            by_category.entry(category).or_default().1 = amount;
+        }
+        let mut rows: Vec<_> = by_category
+            .into_iter()
+            .map(|(category, (a, b))| CategoryComparison {
+                category,
+                a,
+                b,
+                difference: b - a,
+            })
+            .collect();
+        rows.sort_by(|x, y| {
+            y.difference
+                .abs()
+                .partial_cmp(&x.difference.abs())
+                .unwrap_or(Ordering::Equal)
+        });
+        rows
+    }
+
+    /// Each category's spending over the twelve months ending with the one containing `as_of`,
+    /// as (category, total, monthly average), biggest total first. The average is over the
+    /// months the category had spending in, so a category started in March is not diluted by
+    /// the empty months before it.
+    pub fn ttm_by_category(&self, as_of: NaiveDate) -> Vec<(String, f64, f64)> {
+        let mut by_category: BTreeMap<String, (f64, u32)> = BTreeMap::new();
+        for back in 0..12 {
+            let Some(month) = as_of.checked_sub_months(Months::new(back)) else {
+                break;
+            };
+            for (category, amount) in self.category_spending_for_month(month) {
+                let entry = by_category.entry(category).or_default();
+                entry.0 += amount;
+                entry.1 += 1;
+            }
+        }
+        let mut rows: Vec<_> = by_category
+            .into_iter()
+            .map(|(category, (total, months))| {
+                (
+                    category,
+                    round_cents(total),
+                    round_cents(total / months as f64),
+                )
+            })
+            .collect();
+        rows.sort_by(|x, y| y.1.partial_cmp(&x.1).unwrap_or(Ordering::Equal));
+        rows
+    }
+
+    /// Spending per day in the month containing `month`. Days without spending are absent;
+    /// linked refunds land on the original purchase's day.
+    pub fn daily_spending_map(&self, month: NaiveDate) -> BTreeMap<NaiveDate, f64> {
+        let mut days = BTreeMap::new();
+        for posting in self.query().month(month).kind(TxKind::Expense).iter() {
+            *days.entry(posting.date).or_insert(0.0) += posting.amount;
+        }
+        days
+    }
+
+    /// Share of the month's income that was not spent, or `None` when there was no income.
+    pub fn savings_rate(&self, month: NaiveDate) -> Option<f64> {
+        let overview = self.month_overview(month);
+        (overview.total_income > 0.0).then(|| overview.net / overview.total_income)
+    }
+
+    /// Projected spending for `category` in the month after `as_of`: the average of the last
+    /// `FORECAST_MONTHS` complete months that have any activity. With fewer than two such
+    /// months it falls back to the category's budget limit (or zero).
+    pub fn forecast_category(&self, category: &str, as_of: NaiveDate) -> f64 {
+        let current = as_of.with_day(1).unwrap_or(as_of);
+        let history: Vec<f64> = (1..=FORECAST_MONTHS as u32)
+            .filter_map(|back| current.checked_sub_months(Months::new(back)))
+            .filter(|month| self.month_aggregate(*month).transaction_count > 0)
+            .map(|month| {
+                self.query()
+                    .month(month)
+                    .category(category)
+                    .kind(TxKind::Expense)
+                    .sum()
+            })
+            .collect();
+
+        if history.len() < 2 {
+            return self
+                .budgets
+                .iter()
+                .find(|b| b.category == category)
+                .map(|b| b.monthly_limit)
+                .unwrap_or(0.0);
+        }
+        history.iter().sum::<f64>() / history.len() as f64
+    }
+
+    /// The month's `n` largest expenses, biggest first. Refunds are left out.
+    pub fn top_expenses(&self, month: NaiveDate, n: usize) -> Vec<&Transaction> {
+        let mut postings: Vec<Posting> = self
+            .query()
+            .month(month)
+            .kind(TxKind::Expense)
+            .iter()
+            .filter(|p| p.tx.refund_of.is_none())
+            .collect();
+        postings.sort_by(|a, b| b.amount.total_cmp(&a.amount));
+        postings.into_iter().take(n).map(|p| p.tx).collect()
+    }
+
+    /// Spending per category in `range`, biggest first. With `rollup`, subcategories count
+    /// as their parent.
+    fn category_totals(&self, range: DateRange, rollup: bool) -> Vec<(String, f64)> {
+        if rollup {
+            self.category_rollup(range)
+                .into_iter()
+                .map(|(parent, total, _)| (parent, total))
+                .collect()
+        } else {
+            self.category_spending_for_range(range)
+        }
+    }
+
+    /// Percent change in each category's spending from the span before `range` (see
+    /// `DateRange::previous`) to `range`, e.g. `25.0` for a quarter more. Categories with no
+    /// spending before are absent, since there is nothing to compare with. `rollup` as for
+    /// `top_categories_with_other`.
+    pub fn category_deltas(&self, range: DateRange, rollup: bool) -> HashMap<String, f64> {
+        let Some(previous) = range.previous() else {
+            return HashMap::new();
+        };
+        let before: HashMap<String, f64> =
+            self.category_totals(previous, rollup).into_iter().collect();
+        self.category_totals(range, rollup)
+            .into_iter()
+            .filter_map(|(category, now)| {
+                let then = *before.get(&category).filter(|then| **then > 0.0)?;
+                Some((category, (now - then) / then * 100.0))
+            })
+            .collect()
+    }
+
+    /// The range's biggest spending categories, `n` entries at most, with everything past the
+    /// first `n - 1` summed into "Other". With `rollup`, subcategories count as their parent;
+    /// with `budgeted_only`, categories no budget covers are left out.
+    pub fn top_categories_with_other(
+        &self,
+        range: DateRange,
+        n: usize,
+        rollup: bool,
+        budgeted_only: bool,
+    ) -> Vec<(String, f64)> {
+        let mut spend = self.category_totals(range, rollup);
+        if budgeted_only {
+            // A rolled-up parent counts as budgeted when any of its subcategories has a budget.
+            spend.retain(|(category, _)| {
+                self.budgets.iter().any(|b| {
+                    in_category(category, &b.category) || in_category(&b.category, category)
+                })
+            });
+        }
+        if spend.len() > n {
+            let rest = spend.split_off(n.saturating_sub(1));
+            spend.push(("Other".to_string(), rest.iter().map(|(_, v)| v).sum()));
+        }
+        spend
+    }
+
+    /// What is left to spend this month after income is reduced by either the budgets
+    /// committed so far or the spending so far, depending on `basis`.
+    pub fn safe_to_spend(&self, month: NaiveDate, basis: SafeToSpendBasis) -> SafeToSpend {
+        let overview = self.month_overview(month);
+        match basis {
+            SafeToSpendBasis::Budgets => {
+                let spent = self.category_spending_for_month(month);
+                // A budget commits its full limit, or more once it has been overspent.
+                let committed: f64 = self
+                    .budgets
+                    .iter()
+                    .map(|b| b.monthly_limit.max(self.budget_spent(&b.category, month)))
+                    .sum();
+                let unbudgeted: f64 = spent
+                    .iter()
+                    .filter(|(c, _)| !self.budgets.iter().any(|b| in_category(c, &b.category)))
+                    .map(|(_, v)| v)
+                    .sum();
+                SafeToSpend {
+                    amount: overview.total_income - committed - unbudgeted,
+                    reason: format!(
+                        "income {:.2} - budgets {:.2} - unbudgeted spend {:.2}",
+                        overview.total_income, committed, unbudgeted
+                    ),
+                }
+            }
+            SafeToSpendBasis::Spending => SafeToSpend {
+                amount: overview.net,
+                reason: format!(
+                    "income {:.2} - spending so far {:.2}",
+                    overview.total_income, overview.total_outgoing
+                ),
+            },
+        }
+    }
+
+    /// Warns when the month's net so far, or its projection to month end, is below
+    /// `threshold`. The projection keeps income as received and extends spending at the
+    /// month's daily rate so far.
+    pub fn balance_warning(&self, threshold: f64, as_of: NaiveDate) -> Option<BalanceWarning> {
+        let overview = self.month_overview(as_of);
+        if overview.net < threshold {
+            return Some(BalanceWarning {
+                amount: overview.net,
+                projected: false,
+            });
+        }
+        let first = as_of.with_day(1)?;
+        let days_in_month = (first.checked_add_months(Months::new(1))? - first).num_days();
+        let projected_spending =
+            overview.total_outgoing * days_in_month as f64 / f64::from(as_of.day());
+        let projected = round_cents(overview.total_income - projected_spending);
+        (projected < threshold).then_some(BalanceWarning {
+            amount: projected,
+            projected: true,
+        })
+    }
+
+    /// A self-contained, printable HTML report for one month: totals, spending per category
+    /// against budgets with inline SVG bars, and the month's transactions. Empty when `month`
+    /// is not 1-12.
+    pub fn monthly_report_html(&self, year: i32, month: u32) -> String {
+        let Some(first) = NaiveDate::from_ymd_opt(year, month, 1) else {
+            return String::new();
+        };
+        let overview = self.month_overview(first);
+        let spending = self.category_spending_for_month(first);
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Report {year}-{month:02}</title>\n<style>{REPORT_CSS}</style>\n</head>\n<body>\n\
+             <h1>Report for {year}-{month:02}</h1>\n"
+        );
+
+        html.push_str("<h2>Summary</h2>\n<table>\n");
+        html.push_str(&format!(
+            "<tr><th>Income</th><td class=\"num\">{:.2}</td></tr>\n\
+             <tr><th>Spending</th><td class=\"num\">{:.2}</td></tr>\n\
+             <tr><th>Net</th><td class=\"num\">{:.2}</td></tr>\n",
+            overview.total_income, overview.total_outgoing, overview.net
+        ));
+        if let Some(rate) = self.savings_rate(first) {
+            html.push_str(&format!(
+                "<tr><th>Savings rate</th><td class=\"num\">{:.0}%</td></tr>\n",
+                rate * 100.0
+            ));
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Spending by category</h2>\n");
+        if spending.is_empty() {
+            html.push_str("<p>No spending this month.</p>\n");
+        } else {
+            html.push_str(&spending_bars_svg(&spending));
+            html.push_str(
+                "<table>\n<tr><th>Category</th><th>Spent</th><th>Budget</th><th>Used</th></tr>\n",
+            );
+            for (category, spent) in &spending {
+                let budget = self.budgets.iter().find(|b| &b.category == category);
+                let (limit, used) = match budget {
+                    Some(b) if b.monthly_limit > 0.0 => (
+                        format!("{:.2}", b.monthly_limit),
+                        format!("{:.0}%", spent / b.monthly_limit * 100.0),
+                    ),
+                    _ => (String::new(), String::new()),
+                };
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td class=\"num\">{spent:.2}</td>\
+                     <td class=\"num\">{limit}</td><td class=\"num\">{used}</td></tr>\n",
+                    html_escape(category)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("<h2>Transactions</h2>\n<table>\n");
+        html.push_str(
+            "<tr><th>Date</th><th>Description</th><th>Category</th><th>Amount</th></tr>\n",
+        );
+        let mut postings: Vec<Posting> = self.query().month(first).iter().collect();
+        postings.sort_by_key(|p| p.date);
+        for posting in postings {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"num\">{:.2}</td></tr>\n",
+                posting.date,
+                html_escape(&posting.tx.description),
+                html_escape(posting.category),
+                posting.amount
+            ));
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+
+    /// Twelve monthly summaries starting at the month of `fy_start`, crossing the calendar
+    /// year boundary when the fiscal year does.
+    pub fn fiscal_year_summary(&self, fy_start: NaiveDate) -> Vec<MonthSummary> {
+        let start = fy_start.with_day(1).unwrap_or(fy_start);
+        (0..12)
+            .filter_map(|i| start.checked_add_months(Months::new(i)))
+            .map(|month| {
+                let overview = self.month_overview(month);
+                MonthSummary {
+                    month,
+                    income: overview.total_income,
+                    spending: overview.total_outgoing,
+                    net: overview.net,
+                }
+            })
+            .collect()
+    }
+
+    /// Finds charges that look like subscriptions: the same normalized description with an
+    /// amount within 10% showing up in at least three distinct months.
+    pub fn detect_recurring(&self) -> Vec<RecurringCandidate> {
+        let mut by_description: HashMap<String, Vec<&Transaction>> = HashMap::new();
+        for tx in &self.transactions {
+            let key = normalize_description(&tx.description);
+            if !key.is_empty() {
+                by_description.entry(key).or_default().push(tx);
+            }
+        }
+
+        let mut candidates = Vec::new();
+        for mut group in by_description.into_values() {
+            group.sort_by(|a, b| a.amount.partial_cmp(&b.amount).unwrap_or(Ordering::Equal));
+            let mut clusters: Vec<Vec<&Transaction>> = Vec::new();
+            for tx in group {
+                match clusters.last_mut() {
+                    Some(cluster) if amounts_similar(cluster[0].amount, tx.amount) => {
+                        cluster.push(tx)
+                    }
+                    _ => clusters.push(vec![tx]),
+                }
+            }
+
+            for mut cluster in clusters {
+                let months: HashSet<(i32, u32)> = cluster
+                    .iter()
+                    .map(|t| (t.date.year(), t.date.month()))
+                    .collect();
+                if months.len() < 3 {
+                    continue;
+                }
+                cluster.sort_by_key(|t| t.date);
+                let first = cluster[0].date;
+                let last = cluster[cluster.len() - 1];
+                let average_gap =
+                    (last.date - first).num_days() as f64 / (cluster.len() - 1) as f64;
+                candidates.push(RecurringCandidate {
+                    description: last.description.clone(),
+                    category: last.category.clone(),
+                    average_amount: cluster.iter().map(|t| t.amount).sum::<f64>()
+                        / cluster.len() as f64,
+                    months: months.len(),
+                    cadence: Cadence::from_average_gap(average_gap),
+                    last_date: last.date,
+                });
+            }
+        }
+
+        candidates.sort_by(|a, b| {
+            b.average_amount
+                .abs()
+                .partial_cmp(&a.average_amount.abs())
+                .unwrap_or(Ordering::Equal)
+        });
+        candidates
+    }
+
+    /// Bills due from `as_of` through `within_days` days later, soonest first: future-dated
+    /// purchases already in the ledger plus the next occurrence of each recurring charge.
+    pub fn upcoming_bills(&self, within_days: u32, as_of: NaiveDate) -> Vec<UpcomingBill> {
+        let until = as_of + Duration::days(within_days.into());
+        let future = self.query().since(as_of).kind(TxKind::Expense);
+        let scheduled = future
+            .iter()
+            // Bills are due in full on their own date, whatever the report treatment.
+            .filter(|p| p.date == p.tx.date && p.tx.amount > 0.0 && p.tx.refund_of.is_none())
+            .map(|p| UpcomingBill {
+                description: p.tx.description.clone(),
+                amount: p.tx.amount,
+                due: p.date,
+                recurring: false,
+            });
+        let recurring = self
+            .detect_recurring()
+            .into_iter()
+            .filter(|c| c.average_amount > 0.0)
+            .filter_map(|c| {
+                // Roll past occurrences that were never entered forward to the next one.
+                let mut due = c.cadence.next_after(c.last_date)?;
+                while due < as_of {
+                    due = c.cadence.next_after(due)?;
+                }
+                Some(UpcomingBill {
+                    description: c.description,
+                    amount: c.average_amount,
+                    due,
+                    recurring: true,
+                })
+            });
+
+        let mut bills: Vec<_> = scheduled
+            .chain(recurring)
+            .filter(|b| b.due <= until)
+            .collect();
+        bills.sort_by_key(|b| b.due);
+        bills
+    }
+
+    /// Budgeted categories with no transactions in the `inactive_days` up to `as_of`, in
+    /// display order. Such budgets are probably out of date.
+    pub fn stale_budgets(&self, inactive_days: u32, as_of: NaiveDate) -> Vec<String> {
+        let since = as_of - Duration::days(inactive_days.into());
+        self.budgets
+            .iter()
+            .filter(|b| {
+                !self
+                    .query()
+                    .since(since)
+                    .category(&b.category)
+                    .iter()
+                    .any(|p| p.date <= as_of)
+            })
+            .map(|b| b.category.clone())
+            .collect()
+    }
+
+    /// Sum of every monthly limit.
+    pub fn total_budgeted(&self) -> f64 {
+        round_cents(self.budgets.iter().map(|b| b.monthly_limit).sum())
+    }
+
+    /// Up to `n` distinct categories from the newest transactions, most recent first.
+    pub fn recent_categories(&self, n: usize) -> Vec<String> {
+        let mut recent: Vec<String> = Vec::new();
+        for tx in self.transactions.iter().filter(|t| !t.opening_balance) {
+            if recent.len() == n {
+                break;
+            }
+            if !recent.contains(&tx.category) {
+                recent.push(tx.category.clone());
+            }
+        }
+        recent
+    }
+
+    /// Size and date coverage of the transactions, for the header.
+    pub fn stats(&self) -> LedgerStats {
+        let categories: HashSet<&str> = self
+            .transactions
+            .iter()
+            .map(|t| t.category.as_str())
+            .collect();
+        LedgerStats {
+            tx_count: self.transactions.len(),
+            category_count: categories.len(),
+            earliest: self.transactions.iter().map(|t| t.date).min(),
+            latest: self.transactions.iter().map(|t| t.date).max(),
+        }
+    }
+
+    /// Consecutive days, ending on `as_of`, with at least one transaction dated that day. A
+    /// day with nothing logged breaks the streak, except `as_of` itself: until it has an
+    /// entry the streak still counts up to the day before, so it isn't lost first thing in
+    /// the morning. Zero when neither `as_of` nor the day before has an entry.
+    pub fn logging_streak(&self, as_of: NaiveDate) -> u32 {
+        let days: HashSet<NaiveDate> = self
+            .transactions
+            .iter()
+            .map(|t| t.date)
+            .filter(|d| *d <= as_of)
+            .collect();
+        let mut day = if days.contains(&as_of) {
+            as_of
+        } else {
+            match as_of.pred_opt() {
+                Some(yesterday) => yesterday,
+                None => return 0,
+            }
+        };
+        let mut streak = 0;
+        while days.contains(&day) {
+            streak += 1;
+            match day.pred_opt() {
+                Some(previous) => day = previous,
+                None => break,
+            }
+        }
+        streak
+    }
+
+    /// Every category used by a transaction or budget, alphabetically.
+    pub fn categories(&self) -> Vec<String> {
+        let categories: BTreeSet<&str> = self
+            .transactions
+            .iter()
+            .map(|t| t.category.as_str())
+            .chain(self.budgets.iter().map(|b| b.category.as_str()))
+            .collect();
+        categories.into_iter().map(str::to_string).collect()
+    }
+
+    /// Moves the budget at `index` one place up or down within its pinned/unpinned group and
+    /// returns its new index.
+    pub fn move_budget(&mut self, index: usize, up: bool) -> usize {
+        let target = if up {
+            index.checked_sub(1)
+        } else {
+            Some(index + 1)
+        };
+        match target {
+            Some(target)
+                if index < self.budgets.len()
+                    && target < self.budgets.len()
+                    && self.budgets[target].pinned == self.budgets[index].pinned =>
+            {
+                self.budgets.swap(index, target);
+                target
+            }
+            _ => index,
+        }
+    }
+
+    /// Pins or unpins the budget at `index`, keeping pinned budgets ahead of the rest, and
+    /// returns its new index.
+    pub fn toggle_budget_pin(&mut self, index: usize) -> usize {
+        let Some(budget) = self.budgets.get_mut(index) else {
+            return index;
+        };
+        budget.pinned = !budget.pinned;
+        let id = budget.id;
+        self.budgets.sort_by_key(|b| !b.pinned);
+        self.budgets
+            .iter()
+            .position(|b| b.id == id)
+            .unwrap_or(index)
+    }
+
+    /// Net cashflow (income minus spending) per month for roughly the last `months` months.
+    /// Transactions in `excluded` categories (savings, transfers) are skipped entirely, so
+    /// moving money to savings does not read as consumption.
+    pub fn spending_last_n_months(&self, months: usize, excluded: &[String]) -> Vec<(String, f64)> {
+        if months == 0 {
+            return Vec::new();
+        }
+        let mut bucket: HashMap<(i32, u32), f64> = HashMap::new();
+        let now = Local::now().naive_local().date();
+        let earliest = now - Duration::days((months as i64) * 31);
+
+        for posting in self.query().since(earliest).excluding(excluded).iter() {
+            let key = (posting.date.year(), posting.date.month());
+            // Treat income (negative numbers) as positive inflow.
+            *bucket.entry(key).or_insert(0.0) += -posting.amount;
+        }
+
+        let mut series: Vec<_> = bucket
+            .into_iter()
+            .map(|((y, m), v)| (format!("{y}-{m:02}"), v))
+            .collect();
+        series.sort_by(|a, b| a.0.cmp(&b.0));
+        series
+    }
+
+    /// Suggests monthly limits from the average spend over the last `window_days`, raised by
+    /// `buffer` (0.1 = 10%). `floor` lifts small categories towards a minimum in proportion
+    /// to how close they already are, so an $8/month category is not suggested a $50 limit.
+    pub fn suggested_budgets(
+        &self,
+        floor: f64,
+        window_days: u32,
+        buffer: f64,
+        round_step: f64,
+    ) -> Vec<BudgetSuggestion> {
+        let window_days = window_days.max(1);
+        let cutoff = Local::now().naive_local().date() - Duration::days(window_days.into());
+        let mut spend: HashMap<String, f64> = HashMap::new();
+        for posting in self.query().since(cutoff).kind(TxKind::Expense).iter() {
+            *spend.entry(posting.category.to_string()).or_insert(0.0) += posting.amount;
+        }
+
+        let window_months = f64::from(window_days) / 30.0;
+        let reason = format!(
+            "Last {window_days} days average + {:.0}% buffer",
+            buffer * 100.0
+        );
+        let mut suggestions: Vec<_> = spend
+            .into_iter()
+            .filter(|(_, amt)| *amt > 0.0)
+            .map(|(cat, amt)| {
+                let average = amt / window_months;
+                let base = if floor > 0.0 && average < floor {
+                    average + (floor - average) * (average / floor)
+                } else {
+                    average
+                };
+                let raw_limit = round_cents(base * (1.0 + buffer));
+                BudgetSuggestion {
+                    category: cat.clone(),
+                    suggested_limit: round_to_nice(raw_limit, round_step),
+                    raw_limit,
+                    reason: reason.clone(),
+                }
+            })
+            .collect();
+
+        // Encourage common buckets if user does not have any data yet.
+        if suggestions.is_empty() {
+            suggestions = vec![
+                BudgetSuggestion {
+                    category: "Housing".into(),
+                    suggested_limit: 0.0,
+                    raw_limit: 0.0,
+                    reason: "Add your rent/mortgage so you can track it monthly".into(),
+                },
+                BudgetSuggestion {
+                    category: "Food".into(),
+                    suggested_limit: 0.0,
+                    raw_limit: 0.0,
+                    reason: "Groceries, coffee, restaurants".into(),
+                },
+                BudgetSuggestion {
+                    category: "Savings".into(),
+                    suggested_limit: 0.0,
+                    raw_limit: 0.0,
+                    reason: "Pay yourself first".into(),
+                },
+            ];
+        }
+
+        suggestions.sort_by(|a, b| {
+            b.suggested_limit
+                .partial_cmp(&a.suggested_limit)
                 .unwrap_or(Ordering::Equal)
         });
         suggestions
     }
 }
 
+/// Whether a posting brings money in or sends it out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxKind {
+    Income,
+    /// Spending, including linked refunds that reduce it.
+    Expense,
+}
+
+/// A transaction (or a monthly share of one) as reports count it; see `Ledger::postings`.
+#[derive(Debug, Clone, Copy)]
+pub struct Posting<'a> {
+    pub tx: &'a Transaction,
+    pub date: NaiveDate,
+    pub category: &'a str,
+    /// Signed like `Transaction::amount`: positive is money out.
+    pub amount: f64,
+    pub kind: TxKind,
+}
+
+/// The one filtering path shared by overviews, charts and reports, started with
+/// `Ledger::query`. Date and category filters see refunds as their original purchase.
+pub struct LedgerQuery<'a> {
+    ledger: &'a Ledger,
+    month: Option<NaiveDate>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    category: Option<&'a str>,
+    kind: Option<TxKind>,
+    /// Search typed on the Transactions tab, matched against the transaction's own fields.
+    search: SearchQuery,
+    excluded: &'a [String],
+}
+
+impl<'a> LedgerQuery<'a> {
+    /// Only the calendar month containing `month`.
+    pub fn month(mut self, month: NaiveDate) -> Self {
+        self.month = Some(month);
+        self
+    }
+
+    /// Only postings on or after `date`.
+    pub fn since(mut self, date: NaiveDate) -> Self {
+        self.since = Some(date);
+        self
+    }
+
+    /// Only postings dated within `range`.
+    pub fn range(mut self, range: DateRange) -> Self {
+        self.since = Some(range.start);
+        self.until = Some(range.end);
+        self
+    }
+
+    /// Only `category` and its subcategories.
+    pub fn category(mut self, category: &'a str) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    pub fn kind(mut self, kind: TxKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Search as typed on the Transactions tab (see `parse_search_query`); empty matches
+    /// everything.
+    pub fn text(mut self, query: &str) -> Self {
+        self.search = parse_search_query(query);
+        self
+    }
+
+    /// Leaves out `categories` (compared case-insensitively).
+    pub fn excluding(mut self, categories: &'a [String]) -> Self {
+        self.excluded = categories;
+        self
+    }
+
+    /// Matching postings, newest transaction first.
+    pub fn iter(&self) -> impl Iterator<Item = Posting<'a>> + '_ {
+        self.ledger
+            .transactions
+            .iter()
+            .filter(|tx| self.search.matches(tx))
+            .flat_map(|tx| self.ledger.postings(tx))
+            .filter(|posting| self.matches(posting))
+    }
+
+    /// Signed total of the matching postings.
+    pub fn sum(&self) -> f64 {
+        round_cents(self.iter().map(|p| p.amount).sum())
+    }
+
+    fn matches(&self, posting: &Posting) -> bool {
+        self.month.is_none_or(|m| same_month(posting.date, m))
+            && self.since.is_none_or(|d| posting.date >= d)
+            && self.until.is_none_or(|d| posting.date <= d)
+            && self
+                .category
+                .is_none_or(|c| in_category(posting.category, c))
+            && self.kind.is_none_or(|k| posting.kind == k)
+            && !is_excluded(posting.category, self.excluded)
+    }
+}
+
+/// A parsed search: words matched against description or category, plus conditions on the
+/// size of the amount and optionally one category.
+#[derive(Debug, Clone, Default)]
+struct SearchQuery {
+    /// Lowercased; empty matches everything.
+    text: String,
+    amount: Vec<(AmountOp, f64)>,
+    /// Only this category and its subcategories, compared ignoring case.
+    category: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AmountOp {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+}
+
+impl SearchQuery {
+    fn matches(&self, tx: &Transaction) -> bool {
+        let size = tx.amount.abs();
+        (self.text.is_empty() || tx.matches_text(&self.text))
+            && self
+                .category
+                .as_ref()
+                .is_none_or(|c| in_category_list(&tx.category, std::slice::from_ref(c)))
+            && self.amount.iter().all(|&(op, value)| match op {
+                AmountOp::Greater => size > value,
+                AmountOp::GreaterOrEqual => size >= value,
+                AmountOp::Less => size < value,
+                AmountOp::LessOrEqual => size <= value,
+                AmountOp::Equal => (size - value).abs() < 0.005,
+            })
+    }
+}
+
+/// Splits a search into text, amount and category conditions. Amount conditions compare the
+/// size of the amount (income included) and may be prefixed with `amount`: `>100`, `>= 100`,
+/// `<20`, `<=20`, `=4.50`, or a range `20..50` (inclusive). `category:food` (or
+/// `category:"eating out"` for names with spaces) keeps only that category and its
+/// subcategories, unlike a plain word, which also matches descriptions. Everything else is
+/// text.
+fn parse_search_query(input: &str) -> SearchQuery {
+    let tokens: Vec<String> = input.split_whitespace().map(str::to_lowercase).collect();
+    let mut query = SearchQuery::default();
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if let Some(value) = tokens[i].strip_prefix("category:") {
+            let mut name = value.to_string();
+            if name.starts_with('"') {
+                while !(name.len() > 1 && name.ends_with('"')) && i + 1 < tokens.len() {
+                    i += 1;
+                    name.push(' ');
+                    name.push_str(&tokens[i]);
+                }
+            }
+            let name = name.trim_matches('"');
+            if !name.is_empty() {
+                query.category = Some(name.to_string());
+            }
+            i += 1;
+            continue;
+        }
+        // An optional `amount` keyword, then the condition with or without inner spaces.
+        let start = if tokens[i] == "amount" && i + 1 < tokens.len() {
+            i + 1
+        } else {
+            i
+        };
+        let first = tokens[start]
+            .strip_prefix("amount")
+            .unwrap_or(&tokens[start]);
+        if let Some(conditions) = parse_amount_condition(first) {
+            query.amount.extend(conditions);
+            i = start + 1;
+            continue;
+        }
+        if matches!(first, ">" | ">=" | "<" | "<=" | "=")
+            && let Some(next) = tokens.get(start + 1)
+            && let Some(conditions) = parse_amount_condition(&format!("{first}{next}"))
+        {
+            query.amount.extend(conditions);
+            i = start + 2;
+            continue;
+        }
+        words.push(tokens[i].clone());
+        i += 1;
+    }
+    query.text = words.join(" ");
+    query
+}
+
+/// `>100`, `<=20`, `=4.50` or `20..50`; `None` when `text` is not an amount condition.
+fn parse_amount_condition(text: &str) -> Option<Vec<(AmountOp, f64)>> {
+    let number = |s: &str| crate::input::parse_money(s).ok().map(f64::abs);
+    if let Some((low, high)) = text.split_once("..") {
+        return Some(vec![
+            (AmountOp::GreaterOrEqual, number(low)?),
+            (AmountOp::LessOrEqual, number(high)?),
+        ]);
+    }
+    let (op, rest) = if let Some(rest) = text.strip_prefix(">=") {
+        (AmountOp::GreaterOrEqual, rest)
+    } else if let Some(rest) = text.strip_prefix("<=") {
+        (AmountOp::LessOrEqual, rest)
+    } else if let Some(rest) = text.strip_prefix('>') {
+        (AmountOp::Greater, rest)
+    } else if let Some(rest) = text.strip_prefix('<') {
+        (AmountOp::Less, rest)
+    } else if let Some(rest) = text.strip_prefix('=') {
+        (AmountOp::Equal, rest)
+    } else {
+        return None;
+    };
+    Some(vec![(op, number(rest)?)])
+}
+
+/// A transaction that has been parsed from user input but not yet added to the ledger.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewTransaction {
+    pub description: String,
+    pub amount: f64,
+    pub category: String,
+    pub date: NaiveDate,
+    /// Becomes the transaction's note; see `Transaction::note`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+#[derive(Debug, Clone)]
+struct MonthAggregate {
+    overview: Overview,
+    by_category: Vec<(String, f64)>,
+    transaction_count: usize,
+}
+
+/// An inclusive span of days that reports cover, e.g. a calendar month or the last 30 days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl DateRange {
+    /// The calendar month containing `date`.
+    pub fn month(date: NaiveDate) -> Self {
+        let start = date.with_day(1).unwrap_or(date);
+        let end = start
+            .checked_add_months(Months::new(1))
+            .and_then(|next| next.pred_opt())
+            .unwrap_or(start);
+        Self { start, end }
+    }
+
+    /// Exactly one calendar month.
+    pub fn is_month(&self) -> bool {
+        *self == DateRange::month(self.start)
+    }
+
+    /// The span to compare against: the previous calendar month for a whole month, otherwise
+    /// as many days ending the day before `start`.
+    pub fn previous(&self) -> Option<Self> {
+        if self.is_month() {
+            return self
+                .start
+                .checked_sub_months(Months::new(1))
+                .map(DateRange::month);
+        }
+        let end = self.start.pred_opt()?;
+        let start = end.checked_sub_signed(self.end - self.start)?;
+        Some(Self { start, end })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Overview {
     pub total_income: f64,
@@ -258,9 +2333,654 @@ pub struct Overview {
     pub net: f64,
 }
 
+/// Which outflows `Ledger::safe_to_spend` subtracts from income.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SafeToSpendBasis {
+    /// Budget limits (or actual spend once over a limit) plus unbudgeted spending.
+    #[default]
+    Budgets,
+    /// Only what has actually been spent so far.
+    Spending,
+}
+
+#[derive(Debug, Clone)]
+pub struct SafeToSpend {
+    pub amount: f64,
+    /// The inputs that produced `amount`, for display next to the figure.
+    pub reason: String,
+}
+
+/// Result of `Ledger::reconcile`.
+#[derive(Debug, Clone)]
+pub struct Reconciliation<'a> {
+    pub ledger_balance: f64,
+    /// Statement balance minus `ledger_balance`.
+    pub difference: f64,
+    /// What remains of `difference` after allowing for the uncleared transactions; zero
+    /// when they account for all of it.
+    pub unexplained: f64,
+    pub uncleared: Vec<&'a Transaction>,
+}
+
+/// Returned by `Ledger::balance_warning` when the month is running low.
+#[derive(Debug, Clone)]
+pub struct BalanceWarning {
+    /// The figure that fell below the threshold.
+    pub amount: f64,
+    /// `amount` is the month-end projection rather than the net so far.
+    pub projected: bool,
+}
+
+/// Result of `Ledger::stats`. The dates are `None` for an empty ledger.
+#[derive(Debug, Clone)]
+pub struct LedgerStats {
+    pub tx_count: usize,
+    pub category_count: usize,
+    pub earliest: Option<NaiveDate>,
+    pub latest: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MonthSummary {
+    /// First day of the summarized month.
+    pub month: NaiveDate,
+    pub income: f64,
+    pub spending: f64,
+    pub net: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cadence {
+    Weekly,
+    Biweekly,
+    Monthly,
+    Irregular,
+}
+
+impl Cadence {
+    fn from_average_gap(days: f64) -> Self {
+        match days {
+            d if (5.0..=9.0).contains(&d) => Cadence::Weekly,
+            d if (12.0..=16.0).contains(&d) => Cadence::Biweekly,
+            d if (26.0..=35.0).contains(&d) => Cadence::Monthly,
+            _ => Cadence::Irregular,
+        }
+    }
+
+    /// When a charge last seen on `date` is expected next; `None` for irregular charges.
+    pub fn next_after(self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Cadence::Weekly => date.checked_add_days(Days::new(7)),
+            Cadence::Biweekly => date.checked_add_days(Days::new(14)),
+            Cadence::Monthly => date.checked_add_months(Months::new(1)),
+            Cadence::Irregular => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Cadence::Weekly => "weekly",
+            Cadence::Biweekly => "every 2 weeks",
+            Cadence::Monthly => "monthly",
+            Cadence::Irregular => "irregular",
+        }
+    }
+}
+
+/// A charge that repeats across months, as found by `Ledger::detect_recurring`.
+#[derive(Debug, Clone)]
+pub struct RecurringCandidate {
+    /// Description of the most recent occurrence.
+    pub description: String,
+    pub category: String,
+    pub average_amount: f64,
+    /// Number of distinct months the charge appeared in.
+    pub months: usize,
+    pub cadence: Cadence,
+    pub last_date: NaiveDate,
+}
+
+/// What `Ledger::normalize_categories` changed.
+#[derive(Debug, Clone, Default)]
+pub struct CategoryCleanup {
+    /// Categories rewritten, across transactions, templates and budgets.
+    pub changed: usize,
+    /// Categories whose budgets collided after normalizing and were merged into one.
+    pub merged_budgets: Vec<String>,
+}
+
+/// A month's spending by how easily it could change, as returned by
+/// `Ledger::fixed_vs_discretionary`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpendingSplit {
+    pub fixed: f64,
+    pub discretionary: f64,
+    /// Spending in categories listed as neither.
+    pub unclassified: f64,
+}
+
+impl SpendingSplit {
+    pub fn total(&self) -> f64 {
+        self.fixed + self.discretionary + self.unclassified
+    }
+}
+
+/// One category's spending in two months, as returned by `Ledger::compare_months`.
+#[derive(Debug, Clone)]
+pub struct CategoryComparison {
+    pub category: String,
+    pub a: f64,
+    pub b: f64,
+    /// `b - a`: positive when the second month spent more.
+    pub difference: f64,
+}
+
+/// One month of a what-if run, as returned by `Ledger::simulate_budgets`.
+#[derive(Debug, Clone)]
+pub struct MonthBudgetResult {
+    /// First day of the month.
+    pub month: NaiveDate,
+    /// In the order the budgets were proposed.
+    pub budgets: Vec<SimulatedBudget>,
+}
+
+/// A proposed budget against one month's actual spending.
+#[derive(Debug, Clone)]
+pub struct SimulatedBudget {
+    pub category: String,
+    pub limit: f64,
+    pub spent: f64,
+}
+
+impl SimulatedBudget {
+    pub fn met(&self) -> bool {
+        self.spent <= self.limit
+    }
+}
+
+/// A payment expected soon, as listed by `Ledger::upcoming_bills`.
+#[derive(Debug, Clone)]
+pub struct UpcomingBill {
+    pub description: String,
+    pub amount: f64,
+    pub due: NaiveDate,
+    /// Projected from a recurring charge rather than entered with a future date.
+    pub recurring: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct BudgetSuggestion {
     pub category: String,
+    /// `raw_limit` rounded up to a nice number; what applying the suggestion uses.
     pub suggested_limit: f64,
+    /// The average plus buffer before rounding.
+    pub raw_limit: f64,
     pub reason: String,
 }
+
+/// First day of the fiscal year containing `date`, for a year beginning in `start_month`.
+pub fn fiscal_year_start(date: NaiveDate, start_month: u32) -> NaiveDate {
+    let start_month = start_month.clamp(1, 12);
+    let year = if date.month() >= start_month {
+        date.year()
+    } else {
+        date.year() - 1
+    };
+    NaiveDate::from_ymd_opt(year, start_month, 1).unwrap_or(date)
+}
+
+/// Print-friendly styling for `Ledger::monthly_report_html`.
+const REPORT_CSS: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:1.5em}\
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left}\
+td.num{text-align:right}\
+svg text{font-size:12px}";
+
+/// Horizontal bars, one per category, scaled to the largest amount.
+fn spending_bars_svg(spending: &[(String, f64)]) -> String {
+    const ROW: usize = 22;
+    const LABEL: f64 = 140.0;
+    const BAR: f64 = 360.0;
+    let max = spending
+        .iter()
+        .map(|(_, amount)| *amount)
+        .fold(0.0, f64::max);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        LABEL + BAR + 80.0,
+        spending.len() * ROW
+    );
+    for (i, (category, amount)) in spending.iter().enumerate() {
+        let y = i * ROW;
+        let width = if max > 0.0 {
+            (amount.max(0.0) / max * BAR).round()
+        } else {
+            0.0
+        };
+        svg.push_str(&format!(
+            "<text x=\"0\" y=\"{}\">{}</text>\
+             <rect x=\"{LABEL}\" y=\"{}\" width=\"{width}\" height=\"16\" fill=\"#4a90d9\"/>\
+             <text x=\"{}\" y=\"{}\">{amount:.2}</text>\n",
+            y + 15,
+            html_escape(category),
+            y + 2,
+            LABEL + width + 6.0,
+            y + 15
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Amounts are kept to whole cents so sums compare exactly and never print float noise.
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Rounds `value` up to the next multiple of `step`, e.g. 321.47 to 330 with a step of 10.
+/// A step of zero or less leaves the value alone.
+fn round_to_nice(value: f64, step: f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    round_cents((value / step).ceil() * step)
+}
+
+/// Trims and collapses runs of whitespace; with `title_case`, also capitalizes each word and
+/// lowercases the rest.
+pub fn normalize_category(category: &str, title_case: bool) -> String {
+    let words = category.split_whitespace().map(|word| {
+        if !title_case {
+            return word.to_string();
+        }
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) => first
+                .to_uppercase()
+                .chain(chars.flat_map(char::to_lowercase))
+                .collect(),
+            None => String::new(),
+        }
+    });
+    words.collect::<Vec<_>>().join(" ")
+}
+
+/// Lowercases and keeps only letters so "NETFLIX.COM 1234" and "Netflix.com 5678" group.
+fn normalize_description(description: &str) -> String {
+    description
+        .chars()
+        .map(|c| {
+            if c.is_alphabetic() {
+                c.to_ascii_lowercase()
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Same sign and within 10% of each other.
+fn amounts_similar(a: f64, b: f64) -> bool {
+    a.signum() == b.signum() && (a - b).abs() <= a.abs().max(b.abs()) * 0.1
+}
+
+/// A parent category's total and its subcategories' spending; see `Ledger::category_rollup`.
+pub type CategoryRollup = (String, f64, Vec<(String, f64)>);
+
+/// What splits a category into parent and subcategory, as in "Food/Groceries" or
+/// "Food::Restaurants".
+const CATEGORY_SEPARATORS: [&str; 2] = ["::", "/"];
+
+/// The part of `category` before its first separator, trimmed; the whole category when it
+/// has none.
+pub fn category_parent(category: &str) -> &str {
+    split_category(category).map_or(category, |(parent, _)| parent)
+}
+
+/// Parent and subcategory, both trimmed, or `None` for a flat category.
+fn split_category(category: &str) -> Option<(&str, &str)> {
+    let (at, sep) = CATEGORY_SEPARATORS
+        .iter()
+        .filter_map(|sep| category.find(sep).map(|at| (at, sep)))
+        .min()?;
+    Some((category[..at].trim(), category[at + sep.len()..].trim()))
+}
+
+/// Whether spending on `category` counts toward `budget_category`: the same category or one
+/// of its subcategories.
+fn in_category(category: &str, budget_category: &str) -> bool {
+    category == budget_category || category_parent(category) == budget_category
+}
+
+/// Whether `category` or its parent is in `list`, ignoring case.
+fn in_category_list(category: &str, list: &[String]) -> bool {
+    let parent = category_parent(category);
+    list.iter()
+        .any(|c| c.eq_ignore_ascii_case(category) || c.eq_ignore_ascii_case(parent))
+}
+
+fn is_excluded(category: &str, excluded: &[String]) -> bool {
+    excluded.iter().any(|c| c.eq_ignore_ascii_case(category))
+}
+
+fn same_month(a: NaiveDate, b: NaiveDate) -> bool {
+    a.year() == b.year() && a.month() == b.month()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn normalize_category_trims_and_collapses_whitespace() {
+        assert_eq!(normalize_category("  Eating   Out ", false), "Eating Out");
+        assert_eq!(normalize_category("\tfood\n", false), "food");
+        assert_eq!(normalize_category("   ", false), "");
+    }
+
+    #[test]
+    fn normalize_category_title_cases_only_when_asked() {
+        assert_eq!(normalize_category("eATING out", true), "Eating Out");
+        assert_eq!(normalize_category("eATING out", false), "eATING out");
+    }
+
+    #[test]
+    fn normalize_categories_merges_colliding_budgets() {
+        let mut ledger = Ledger::empty();
+        ledger.add_or_update_budget("Food", 500.0, "groceries");
+        ledger.add_or_update_budget("food ", 200.0, "takeaway");
+        ledger.add_or_update_budget("Rent", 1200.0, "");
+        ledger.add_transaction("Shop", -20.0, " food", date(2024, 3, 2));
+
+        let cleanup = ledger.normalize_categories(true);
+
+        assert_eq!(cleanup.changed, 2);
+        assert_eq!(cleanup.merged_budgets, vec!["Food".to_string()]);
+        assert_eq!(ledger.budgets.len(), 2);
+        let food = &ledger.budgets[0];
+        assert_eq!(food.category, "Food");
+        assert_eq!(food.monthly_limit, 700.0);
+        assert_eq!(food.note, "groceries; takeaway");
+        assert_eq!(ledger.transactions[0].category, "Food");
+    }
+
+    #[test]
+    fn low_spend_category_is_not_inflated_by_the_floor() {
+        let today = Local::now().naive_local().date();
+        let mut ledger = Ledger::empty();
+        for days_ago in [10, 40, 70] {
+            ledger.add_transaction("Stamps", 8.0, "Postage", today - Duration::days(days_ago));
+        }
+
+        let suggestion = |floor: f64| {
+            ledger
+                .suggested_budgets(floor, 90, 0.1, 0.0)
+                .into_iter()
+                .find(|s| s.category == "Postage")
+                .unwrap()
+        };
+
+        // $8 a month plus the 10% buffer, with no floor.
+        assert_eq!(suggestion(0.0).suggested_limit, 8.8);
+        // A $50 floor only nudges it up in proportion, nowhere near the old $55.
+        let floored = suggestion(50.0).suggested_limit;
+        assert!(floored > 8.8 && floored < 20.0, "got {floored}");
+    }
+
+    #[test]
+    fn forecast_averages_the_last_complete_months() {
+        let mut ledger = Ledger::empty();
+        ledger.add_or_update_budget("Food", 500.0, "");
+        // Only the three months before June count; February and June itself are ignored.
+        for (month, amount) in [(2, 900.0), (3, 300.0), (4, 400.0), (5, 500.0), (6, 50.0)] {
+            ledger.add_transaction("Groceries", amount, "Food", date(2024, month, 10));
+        }
+        assert_eq!(ledger.forecast_category("Food", date(2024, 6, 15)), 400.0);
+        // July had no activity at all, so it is skipped rather than counted as zero.
+        assert_eq!(ledger.forecast_category("Food", date(2024, 8, 1)), 275.0);
+    }
+
+    #[test]
+    fn forecast_falls_back_to_the_budget_without_enough_history() {
+        let mut ledger = Ledger::empty();
+        ledger.add_or_update_budget("Food", 500.0, "");
+        ledger.add_transaction("Groceries", 320.0, "Food", date(2024, 5, 10));
+        assert_eq!(ledger.forecast_category("Food", date(2024, 6, 15)), 500.0);
+        assert_eq!(ledger.forecast_category("Travel", date(2024, 6, 15)), 0.0);
+    }
+
+    #[test]
+    fn month_totals_stay_cheap_with_50k_transactions() {
+        let newest = date(2024, 12, 31);
+        let mut ledger = Ledger::empty();
+        for i in 0..50_000i64 {
+            let category = ["Food", "Rent", "Transport", "Fun"][(i % 4) as usize];
+            ledger.add_transaction("Item", 1.25, category, newest - Duration::days(i / 35));
+        }
+        assert!(
+            ledger
+                .transactions
+                .windows(2)
+                .all(|w| w[0].date >= w[1].date)
+        );
+
+        let month = date(2024, 6, 1);
+        let first = ledger.month_overview(month);
+        assert!(ledger.month_cache.borrow().contains_key(&(2024, 6)));
+
+        // Every later frame reads the cached month instead of rescanning 50k transactions.
+        for _ in 0..10 {
+            assert_eq!(
+                ledger.month_overview(month).total_outgoing,
+                first.total_outgoing
+            );
+            ledger.category_spending_for_month(month);
+        }
+        assert_eq!(ledger.month_cache.borrow().len(), 1);
+
+        // A change drops the cache so the next frame sees it.
+        ledger.add_transaction("Late", 10.0, "Food", date(2024, 6, 15));
+        assert!(ledger.month_cache.borrow().is_empty());
+        assert_eq!(
+            ledger.month_overview(month).total_outgoing,
+            round_cents(first.total_outgoing + 10.0)
+        );
+    }
+
+    #[test]
+    fn savings_transfer_does_not_lower_the_cashflow_net() {
+        let today = Local::now().naive_local().date();
+        let mut ledger = Ledger::empty();
+        ledger.add_transaction("Salary", -3000.0, "Salary", today);
+        ledger.add_transaction("Groceries", 400.0, "Food", today);
+        ledger.add_transaction("To savings", 1000.0, "Savings", today);
+
+        let net = |excluded: &[String]| ledger.spending_last_n_months(1, excluded)[0].1;
+        assert_eq!(net(&[]), 1600.0);
+        assert_eq!(net(&["Savings".to_string()]), 2600.0);
+    }
+
+    #[test]
+    fn many_small_amounts_add_up_to_the_cent() {
+        // 0.1 + 0.2 style drift: 10,000 × 0.10 summed as floats is not exactly 1000.
+        let drifted: f64 = std::iter::repeat_n(0.1, 10_000).sum();
+        assert_ne!(drifted, 1000.0);
+
+        let mut ledger = Ledger::empty();
+        let day = date(2024, 3, 15);
+        for i in 0..10_000 {
+            let amount = [0.1, 0.2, 0.07][i % 3];
+            ledger.add_transaction("Coffee", amount, "Food", day);
+        }
+        // 3,334 × 0.10 + 3,333 × 0.20 + 3,333 × 0.07
+        let expected = 1233.31;
+        assert_eq!(ledger.month_overview(day).total_outgoing, expected);
+        assert_eq!(ledger.budget_spent("Food", day), expected);
+        assert_eq!(ledger.balance_as_of(day, None), -expected);
+        assert_eq!(round_cents(0.1 + 0.2), 0.3);
+    }
+
+    #[test]
+    fn monthly_report_html_lists_totals_budgets_and_transactions() {
+        let mut ledger = Ledger::empty();
+        ledger.add_or_update_budget("Food", 400.0, "");
+        ledger.add_transaction("Salary", -2000.0, "Income", date(2024, 3, 1));
+        ledger.add_transaction("Fish & Chips <takeaway>", 100.0, "Food", date(2024, 3, 5));
+        ledger.add_transaction("Last month", 999.0, "Food", date(2024, 2, 28));
+
+        let html = ledger.monthly_report_html(2024, 3);
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Report for 2024-03</h1>"));
+        assert!(html.contains("<tr><th>Income</th><td class=\"num\">2000.00</td></tr>"));
+        assert!(html.contains("<tr><th>Spending</th><td class=\"num\">100.00</td></tr>"));
+        assert!(html.contains("<svg"));
+        assert!(html.contains(
+            "<tr><td>Food</td><td class=\"num\">100.00</td>\
+             <td class=\"num\">400.00</td><td class=\"num\">25%</td></tr>"
+        ));
+        assert!(html.contains("<td>Fish &amp; Chips &lt;takeaway&gt;</td>"));
+        assert!(!html.contains("Last month"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+
+    #[test]
+    fn monthly_report_html_handles_empty_and_invalid_months() {
+        let ledger = Ledger::empty();
+        let html = ledger.monthly_report_html(2024, 3);
+        assert!(html.contains("<p>No spending this month.</p>"));
+        assert!(!html.contains("<svg"));
+        assert!(ledger.monthly_report_html(2024, 13).is_empty());
+    }
+
+    #[test]
+    fn logging_streak_counts_consecutive_days_up_to_today() {
+        let mut ledger = Ledger::empty();
+        for day in [1, 3, 4, 5] {
+            ledger.add_transaction("Coffee", 3.0, "Food", date(2024, 3, day));
+        }
+        // A second entry on the same day doesn't count twice, and future entries are ignored.
+        ledger.add_transaction("Lunch", 9.0, "Food", date(2024, 3, 5));
+        ledger.add_transaction("Planned", 50.0, "Food", date(2024, 3, 9));
+
+        assert_eq!(ledger.logging_streak(date(2024, 3, 5)), 3);
+        assert_eq!(ledger.logging_streak(date(2024, 3, 1)), 1);
+    }
+
+    #[test]
+    fn logging_streak_keeps_yesterdays_run_until_today_is_logged() {
+        let mut ledger = Ledger::empty();
+        for day in [3, 4, 5] {
+            ledger.add_transaction("Coffee", 3.0, "Food", date(2024, 3, day));
+        }
+        // Nothing logged on the 6th yet: the run through the 5th still stands.
+        assert_eq!(ledger.logging_streak(date(2024, 3, 6)), 3);
+        // A whole day missed breaks it.
+        assert_eq!(ledger.logging_streak(date(2024, 3, 7)), 0);
+
+        ledger.add_transaction("Bus", 2.5, "Transport", date(2024, 3, 6));
+        assert_eq!(ledger.logging_streak(date(2024, 3, 6)), 4);
+    }
+
+    #[test]
+    fn logging_streak_is_zero_for_an_empty_ledger() {
+        let ledger = Ledger::empty();
+        assert_eq!(ledger.logging_streak(date(2024, 3, 6)), 0);
+        assert_eq!(ledger.logging_streak(NaiveDate::MIN), 0);
+    }
+
+    #[test]
+    fn category_search_ignores_descriptions_and_keeps_subcategories() {
+        let mut ledger = Ledger::empty();
+        ledger.add_transaction("Groceries", 40.0, "Food", date(2024, 3, 1));
+        ledger.add_transaction("Bakery", 6.0, "Food/Treats", date(2024, 3, 2));
+        ledger.add_transaction("Food truck festival ticket", 25.0, "Fun", date(2024, 3, 3));
+        ledger.add_transaction("Pizza", 18.0, "Eating Out", date(2024, 3, 4));
+        let descriptions = |query: &str| -> Vec<String> {
+            let mut found: Vec<String> = ledger
+                .filter_transactions(query)
+                .iter()
+                .map(|t| t.description.clone())
+                .collect();
+            found.sort();
+            found
+        };
+
+        assert_eq!(descriptions("food").len(), 3);
+        assert_eq!(descriptions("category:FOOD"), ["Bakery", "Groceries"]);
+        assert_eq!(descriptions("category:food >10"), ["Groceries"]);
+        assert_eq!(descriptions("category:\"eating out\""), ["Pizza"]);
+        assert!(descriptions("category:eating").is_empty());
+    }
+
+    #[test]
+    fn rows_merged_from_an_archive_refuse_flag_changes() {
+        let mut old = Ledger::empty();
+        old.add_transaction("Old purchase", 30.0, "Fun", date(2022, 5, 1));
+        old.add_transaction("Old refund", -10.0, "Fun", date(2022, 5, 9));
+        let mut ledger = Ledger::empty();
+        ledger.next_tx_id = 10;
+        let current = ledger.add_transaction("Purchase", 40.0, "Fun", date(2024, 5, 1));
+        assert_eq!(ledger.merge_history(old.transactions), 2);
+        let archived = ledger
+            .transactions
+            .iter()
+            .find(|t| t.description == "Old refund")
+            .map(|t| t.id)
+            .unwrap();
+
+        assert_eq!(ledger.toggle_cleared(archived), None);
+        assert_eq!(ledger.toggle_business(archived), None);
+        assert!(!ledger.set_amortization(archived, Some(3)));
+        assert!(ledger.link_refund(archived, current).is_err());
+        let row = ledger
+            .transactions
+            .iter()
+            .find(|t| t.id == archived)
+            .unwrap();
+        assert!(!row.cleared && !row.business && row.refund_of.is_none());
+        assert_eq!(row.amortize_months, None);
+
+        assert_eq!(ledger.toggle_cleared(current), Some(true));
+        assert_eq!(ledger.toggle_business(current), Some(true));
+    }
+
+    #[test]
+    fn balance_as_of_follows_the_account() {
+        let mut ledger = Ledger::empty();
+        let checking = ledger.add_account("Checking", "").unwrap();
+        let card = ledger.add_account("Card", "").unwrap();
+        ledger.set_opening_balance(Some(checking), 1000.0, date(2024, 1, 1));
+        let pay = ledger.add_transaction("Pay", -500.0, "Income", date(2024, 1, 10));
+        ledger.set_transaction_account(pay, Some(checking));
+        let dinner = ledger.add_transaction("Dinner", 80.0, "Food", date(2024, 1, 12));
+        ledger.set_transaction_account(dinner, Some(card));
+        let later = ledger.add_transaction("Rent", 700.0, "Rent", date(2024, 2, 1));
+        ledger.set_transaction_account(later, Some(checking));
+        ledger.add_transaction("Cash", 20.0, "Food", date(2024, 1, 15));
+
+        let jan = date(2024, 1, 31);
+        assert_eq!(ledger.balance_as_of(jan, Some(checking)), 1500.0);
+        assert_eq!(ledger.balance_as_of(jan, Some(card)), -80.0);
+        assert_eq!(ledger.balance_as_of(jan, None), 1400.0);
+        assert_eq!(
+            ledger.balance_as_of(date(2024, 2, 1), Some(checking)),
+            800.0
+        );
+    }
+}
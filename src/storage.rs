@@ -1,42 +1,1029 @@
-use crate::models::Ledger;
-use anyhow::{Context, Result};
+use crate::config::{Config, ImportProfile, UiState};
+use crate::error::{CentshError, Result};
+use crate::input::{self, PasteFailure, PastedBlock};
+use crate::models::{Ledger, NewTransaction, Transaction};
+use chrono::{Datelike, Local, NaiveDate};
 use directories::ProjectDirs;
-use std::fs;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Environment variable naming a ledger file to use instead of the OS data directory.
+pub const LEDGER_ENV: &str = "CENTSH_LEDGER";
+
+/// Directory (relative to the working directory) used when the OS reports no data directory.
+const FALLBACK_DIR: &str = "centsh-data";
+
+/// Saving is refused when the ledger's filesystem has less free space than this, or than the
+/// file about to be written.
+const MIN_FREE_BYTES: u64 = 1024 * 1024;
+
+/// Which transactions an export includes. The scope also names the exported file.
+pub struct ExportScope {
+    pub period: ExportPeriod,
+    /// Search text as used on the Transactions tab; empty matches everything.
+    pub query: String,
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    /// One flat JSON object per line, for jq and data pipelines.
+    Jsonl,
+}
+
+impl ExportFormat {
+    /// Parses `csv` or `jsonl` (case-insensitive); empty means CSV.
+    pub fn parse(input: &str) -> Result<Self> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "" | "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "ndjson" => Ok(ExportFormat::Jsonl),
+            _ => Err(CentshError::Validation(
+                "Format must be csv or jsonl".into(),
+            )),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportPeriod {
+    All,
+    Year(i32),
+    Month(i32, u32),
+}
+
+impl ExportPeriod {
+    /// Parses `YYYY`, `YYYY-MM`, or an empty string for everything.
+    pub fn parse(input: &str) -> Result<Self> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(ExportPeriod::All);
+        }
+        let invalid = || CentshError::Validation("Period must be YYYY or YYYY-MM".into());
+        match input.split_once('-') {
+            None => Ok(ExportPeriod::Year(input.parse().map_err(|_| invalid())?)),
+            Some((year, month)) => {
+                let year = year.parse().map_err(|_| invalid())?;
+                let month = month.parse().map_err(|_| invalid())?;
+                if !(1..=12).contains(&month) {
+                    return Err(invalid());
+                }
+                Ok(ExportPeriod::Month(year, month))
+            }
+        }
+    }
+
+    fn contains(&self, date: NaiveDate) -> bool {
+        match *self {
+            ExportPeriod::All => true,
+            ExportPeriod::Year(year) => date.year() == year,
+            ExportPeriod::Month(year, month) => date.year() == year && date.month() == month,
+        }
+    }
+
+    fn label(&self) -> Option<String> {
+        match *self {
+            ExportPeriod::All => None,
+            ExportPeriod::Year(year) => Some(year.to_string()),
+            ExportPeriod::Month(year, month) => Some(format!("{year}-{month:02}")),
+        }
+    }
+}
+
+impl ExportScope {
+    /// `transactions[-period][-query].<ext>`, e.g. `transactions-2024-Food.csv`.
+    fn file_name(&self) -> String {
+        let mut name = String::from("transactions");
+        if let Some(period) = self.period.label() {
+            name.push('-');
+            name.push_str(&period);
+        }
+        let query: String = self
+            .query
+            .trim()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '-' })
+            .collect();
+        if !query.is_empty() {
+            name.push('-');
+            name.push_str(&query);
+        }
+        name.push('.');
+        name.push_str(self.format.extension());
+        name
+    }
+
+    fn is_whole_ledger(&self) -> bool {
+        self.period == ExportPeriod::All && self.query.trim().is_empty()
+    }
+}
+
+/// Where the ledger path came from, reported to the user on launch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataSource {
+    Env,
+    DataDir,
+    Fallback,
+}
+
+impl std::fmt::Display for DataSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataSource::Env => write!(f, "from {LEDGER_ENV}"),
+            DataSource::DataDir => write!(f, "OS data directory"),
+            DataSource::Fallback => write!(f, "no data directory found, using ./{FALLBACK_DIR}"),
+        }
+    }
+}
 
 pub struct Storage {
     path: PathBuf,
+    /// Named ledger in use (`--profile`), or `None` for the default one.
+    profile: Option<String>,
+    config_path: PathBuf,
+    ui_state_path: PathBuf,
+    actions_log_path: PathBuf,
+    source: DataSource,
+    /// False when the data directory could not be created or written to at startup. The app
+    /// still runs, but nothing is saved.
+    writable: bool,
+    /// Modification time of the ledger file when it was last loaded or saved, to notice
+    /// changes made by something else.
+    in_sync_at: Cell<Option<SystemTime>>,
 }
 
 impl Storage {
-    pub fn new() -> Result<Self> {
-        let dirs = ProjectDirs::from("com", "centsh", "centsh")
-            .context("unable to locate a config directory")?;
-        let data_dir = dirs.data_dir();
-        fs::create_dir_all(data_dir).context("failed to create data directory")?;
+    /// Opens the default ledger, or with `profile` its own `ledger-<profile>.json` (and
+    /// archive folder and audit log) beside it. The config and view settings are shared.
+    pub fn new(profile: Option<&str>) -> Result<Self> {
+        let profile = profile_name(profile)?;
+        let env = std::env::var_os(LEDGER_ENV)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+        let data_dir =
+            ProjectDirs::from("com", "centsh", "centsh").map(|dirs| dirs.data_dir().to_path_buf());
+        let (mut path, source) = resolve_ledger_path(env, data_dir);
+        let actions_log = match &profile {
+            Some(name) => {
+                path = profile_path(&path, name);
+                format!("actions-{name}.log")
+            }
+            None => "actions.log".to_string(),
+        };
+        let dir = path
+            .parent()
+            .filter(|d| !d.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let writable = fs::create_dir_all(dir).is_ok() && dir_is_writable(dir);
         Ok(Self {
-            path: data_dir.join("ledger.json"),
+            config_path: path.with_file_name("config.json"),
+            ui_state_path: path.with_file_name("ui_state.json"),
+            actions_log_path: path.with_file_name(actions_log),
+            path,
+            profile,
+            source,
+            writable,
+            in_sync_at: Cell::new(None),
         })
     }
 
+    /// A default-profile ledger at `path`, bypassing `CENTSH_LEDGER` and the data directory.
+    #[cfg(test)]
+    pub fn at(path: PathBuf) -> Self {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let writable = fs::create_dir_all(dir).is_ok() && dir_is_writable(dir);
+        Self {
+            config_path: path.with_file_name("config.json"),
+            ui_state_path: path.with_file_name("ui_state.json"),
+            actions_log_path: path.with_file_name("actions.log"),
+            path,
+            profile: None,
+            source: DataSource::Env,
+            writable,
+            in_sync_at: Cell::new(None),
+        }
+    }
+
+    /// Whether changes can be saved; see `Storage::save`.
+    pub fn writable(&self) -> bool {
+        self.writable
+    }
+
+    pub fn source(&self) -> DataSource {
+        self.source
+    }
+
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
 
+    /// False on first run, before anything has been saved.
+    pub fn ledger_exists(&self) -> bool {
+        self.path.exists()
+    }
+
     pub fn load(&self) -> Result<Ledger> {
-        if !self.path.exists() {
+        if !self.ledger_exists() {
             return Ok(Ledger::default());
         }
 
+        // Noted before reading, so a change landing mid-read is still noticed later.
+        self.mark_in_sync();
         let content =
-            fs::read_to_string(&self.path).with_context(|| format!("reading {:?}", self.path))?;
-        let data =
-            serde_json::from_str::<Ledger>(&content).context("parsing ledger data failed")?;
+            fs::read_to_string(&self.path).map_err(CentshError::io("reading", &self.path))?;
+        let mut data = serde_json::from_str::<Ledger>(&content)
+            .map_err(CentshError::json("parsing ledger data"))?;
+        data.sort_transactions();
+        data.round_amounts();
         Ok(data)
     }
 
+    /// Where an export or report named `file_name` goes: next to the ledger, with the
+    /// profile added to the name (`report-2024-03-business.html`) so profiles sharing a
+    /// folder don't overwrite each other's files.
+    fn export_path(&self, file_name: &str) -> PathBuf {
+        let path = self.path.with_file_name(file_name);
+        match &self.profile {
+            Some(name) => profile_path(&path, name),
+            None => path,
+        }
+    }
+
+    /// Folder next to the ledger holding one `ledger-YYYY.json` per archived year.
+    fn archive_dir(&self) -> PathBuf {
+        match &self.profile {
+            Some(name) => self.path.with_file_name(format!("archive-{name}")),
+            None => self.path.with_file_name("archive"),
+        }
+    }
+
+    /// Moves a finished year's transactions out of the ledger into
+    /// `archive/ledger-YYYY.json` and saves the smaller ledger. Archiving a year again adds
+    /// to its file. The archive is written before anything leaves the ledger, so a failed
+    /// write loses nothing.
+    pub fn archive_year(&self, ledger: &mut Ledger, year: i32) -> Result<PathBuf> {
+        if year >= Local::now().year() {
+            return Err(CentshError::Validation(format!(
+                "{year} isn't over yet; only past years can be archived"
+            )));
+        }
+        if !self.writable {
+            return Err(CentshError::NotSaved(format!(
+                "data dir not writable — can't archive; set {LEDGER_ENV} to a writable file"
+            )));
+        }
+        let mut archived: Vec<Transaction> = ledger
+            .transactions
+            .iter()
+            .filter(|t| t.date.year() == year)
+            .cloned()
+            .collect();
+        if archived.is_empty() {
+            return Err(CentshError::NotFound(format!("No transactions in {year}")));
+        }
+        let dir = self.archive_dir();
+        fs::create_dir_all(&dir).map_err(CentshError::io("creating", &dir))?;
+        let path = dir.join(format!("ledger-{year}.json"));
+        if path.exists() {
+            let mut earlier = read_archive(&path)?;
+            earlier.retain(|old| archived.iter().all(|t| t.id != old.id));
+            archived.extend(earlier);
+        }
+        archived.sort_by_key(|t| t.date);
+        let json = serde_json::to_string_pretty(&archived)
+            .map_err(CentshError::json("serializing archive"))?;
+        write_atomic(&path, &json)?;
+        ledger.take_year(year);
+        self.save(ledger)?;
+        Ok(path)
+    }
+
+    /// Every transaction in the yearly archive files, for merging back with
+    /// `Ledger::merge_history`. No archive folder means no history.
+    pub fn load_history(&self) -> Result<Vec<Transaction>> {
+        let dir = self.archive_dir();
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut history = Vec::new();
+        for entry in fs::read_dir(&dir).map_err(CentshError::io("reading", &dir))? {
+            let path = entry.map_err(CentshError::io("reading", &dir))?.path();
+            let is_archive = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("ledger-") && n.ends_with(".json"));
+            if is_archive {
+                history.extend(read_archive(&path)?);
+            }
+        }
+        Ok(history)
+    }
+
+    pub fn load_config(&self) -> Result<Config> {
+        if !self.config_path.exists() {
+            return Ok(Config::default());
+        }
+
+        let content = fs::read_to_string(&self.config_path)
+            .map_err(CentshError::io("reading", &self.config_path))?;
+        serde_json::from_str::<Config>(&content).map_err(CentshError::json("parsing config"))
+    }
+
+    /// Writes `config.json`, e.g. after the setup wizard picked a currency. Like the ledger,
+    /// it goes through a temp file so a crash can't leave half a config.
+    pub fn save_config(&self, config: &Config) -> Result<()> {
+        if !self.writable {
+            return Ok(());
+        }
+        let json = serde_json::to_string_pretty(config)
+            .map_err(CentshError::json("serializing config"))?;
+        write_atomic(&self.config_path, &json)
+    }
+
+    /// Reads the remembered view toggles. A missing or unreadable file is not worth stopping
+    /// startup for, so it falls back to the defaults.
+    pub fn load_ui_state(&self) -> UiState {
+        fs::read_to_string(&self.ui_state_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_ui_state(&self, state: &UiState) -> Result<()> {
+        if !self.writable {
+            return Ok(());
+        }
+        let json = serde_json::to_string_pretty(state)
+            .map_err(CentshError::json("serializing UI state"))?;
+        fs::write(&self.ui_state_path, json)
+            .map_err(CentshError::io("writing", &self.ui_state_path))
+    }
+
+    /// Appends one JSON line describing a mutation to `actions.log`. The log is for humans
+    /// auditing their data; the app never reads it back.
+    pub fn append_action(&self, action: &str, details: serde_json::Value) -> Result<()> {
+        let entry = serde_json::json!({
+            "timestamp": Local::now().to_rfc3339(),
+            "action": action,
+            "details": details,
+        });
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.actions_log_path)
+            .map_err(CentshError::io("opening", &self.actions_log_path))?;
+        writeln!(file, "{entry}").map_err(CentshError::io("writing", &self.actions_log_path))
+    }
+
+    /// Writes the transactions in `scope` next to the ledger, oldest first, and returns the
+    /// file path and row count. Opening balances are left out: they are neither income nor
+    /// spending, and would read back as income.
+    pub fn export_filtered(
+        &self,
+        ledger: &Ledger,
+        scope: &ExportScope,
+    ) -> Result<(PathBuf, usize)> {
+        let rows: Vec<&Transaction> = ledger
+            .filter_transactions(&scope.query)
+            .into_iter()
+            .rev()
+            .filter(|t| scope.period.contains(t.date) && !t.opening_balance)
+            .collect();
+        let path = self.export_path(&scope.file_name());
+        match scope.format {
+            ExportFormat::Csv if scope.is_whole_ledger() => self.export_csv(ledger, &path)?,
+            ExportFormat::Csv => fs::write(&path, transactions_csv(&rows))
+                .map_err(CentshError::io("writing", &path))?,
+            ExportFormat::Jsonl if scope.is_whole_ledger() => self.export_jsonl(ledger, &path)?,
+            ExportFormat::Jsonl => fs::write(&path, transactions_jsonl(&rows))
+                .map_err(CentshError::io("writing", &path))?,
+        }
+        Ok((path, rows.len()))
+    }
+
+    /// Writes every transaction in `ledger` to `path` as CSV, oldest first, with a
+    /// `date,description,amount,category` header and amounts as stored (positive is money
+    /// out), so importing it with the `centsh` profile gives the same rows back. Only those
+    /// four columns are kept; opening balances are left out.
+    pub fn export_csv(&self, ledger: &Ledger, path: &Path) -> Result<()> {
+        let rows: Vec<&Transaction> = ledger
+            .transactions
+            .iter()
+            .rev()
+            .filter(|t| !t.opening_balance)
+            .collect();
+        fs::write(path, transactions_csv(&rows)).map_err(CentshError::io("writing", path))
+    }
+
+    /// Writes every transaction in `ledger` to `path` as JSON Lines, oldest first: one flat
+    /// object per line with `id`, `date` (`YYYY-MM-DD`), `description`, `amount` (as stored,
+    /// positive is money out) and `category`. Opening balances are left out, as for CSV.
+    pub fn export_jsonl(&self, ledger: &Ledger, path: &Path) -> Result<()> {
+        let rows: Vec<&Transaction> = ledger
+            .transactions
+            .iter()
+            .rev()
+            .filter(|t| !t.opening_balance)
+            .collect();
+        fs::write(path, transactions_jsonl(&rows)).map_err(CentshError::io("writing", path))
+    }
+
+    /// Writes a bank-style register for one account next to the ledger, oldest first: the
+    /// account's opening balance (zero when none was set), then date, description, amount
+    /// (positive is money in), running balance and whether the transaction is cleared.
+    /// Returns the path and transaction count.
+    pub fn export_register(&self, ledger: &Ledger, account_id: u64) -> Result<(PathBuf, usize)> {
+        let account = ledger
+            .account(account_id)
+            .ok_or_else(|| CentshError::NotFound(format!("No account with id {account_id}")))?;
+        let mut rows = ledger.running_balances(Some(account_id));
+        let mut out = String::from("date,description,amount,balance,cleared\n");
+        // `running_balances` puts the opening balance first, so every later balance has it.
+        let opening = rows
+            .first()
+            .is_some_and(|(tx, _)| tx.opening_balance)
+            .then(|| rows.remove(0));
+        let (opening_date, opening_amount) = match opening {
+            Some((tx, _)) => (Some(tx.date), -tx.amount),
+            None => (rows.first().map(|(tx, _)| tx.date), 0.0),
+        };
+        out.push_str(&format!(
+            "{},Opening balance,{opening_amount:.2},{opening_amount:.2},\n",
+            opening_date.map(|d| d.to_string()).unwrap_or_default()
+        ));
+        for (tx, balance) in &rows {
+            out.push_str(&format!(
+                "{},{},{:.2},{balance:.2},{}\n",
+                tx.date,
+                csv_field(&tx.description),
+                -tx.amount,
+                if tx.cleared { "yes" } else { "no" }
+            ));
+        }
+        let slug: String = account
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let path = self.export_path(&format!("register-{slug}.csv"));
+        fs::write(&path, out).map_err(CentshError::io("writing", &path))?;
+        Ok((path, rows.len()))
+    }
+
+    /// Writes the business-flagged transactions in `period` next to the ledger for tax time,
+    /// grouped by category with a subtotal after each group and a grand total at the end.
+    /// Returns the path and transaction count.
+    pub fn export_csv_business_only(
+        &self,
+        ledger: &Ledger,
+        period: ExportPeriod,
+    ) -> Result<(PathBuf, usize)> {
+        let mut by_category: BTreeMap<&str, Vec<&Transaction>> = BTreeMap::new();
+        for tx in ledger.transactions.iter().rev() {
+            if tx.business && period.contains(tx.date) {
+                by_category.entry(&tx.category).or_default().push(tx);
+            }
+        }
+        let mut out = String::from("category,date,description,amount\n");
+        let (mut total, mut count) = (0.0, 0);
+        for (category, rows) in &by_category {
+            let category = csv_field(category);
+            for tx in rows {
+                out.push_str(&format!(
+                    "{category},{},{},{:.2}\n",
+                    tx.date,
+                    csv_field(&tx.description),
+                    tx.amount
+                ));
+            }
+            let subtotal: f64 = rows.iter().map(|t| t.amount).sum();
+            out.push_str(&format!("{category},,Subtotal,{subtotal:.2}\n"));
+            total += subtotal;
+            count += rows.len();
+        }
+        out.push_str(&format!(",,Total,{total:.2}\n"));
+        let name = match period.label() {
+            Some(label) => format!("business-expenses-{label}.csv"),
+            None => "business-expenses.csv".to_string(),
+        };
+        let path = self.export_path(&name);
+        fs::write(&path, out).map_err(CentshError::io("writing", &path))?;
+        Ok((path, count))
+    }
+
+    /// Appends the transactions in the CSV at `path` to `ledger`, in the app's own export
+    /// layout (`date,description,amount,category`, header optional) with amounts as stored,
+    /// positive for money out; `invert` flips every sign. Returns how many were added and the
+    /// lines that were skipped; see `import_csv_with_profile`.
+    pub fn import_csv(
+        &self,
+        path: &Path,
+        ledger: &mut Ledger,
+        invert: bool,
+    ) -> Result<(usize, Vec<PasteFailure>)> {
+        let profile = ImportProfile {
+            name: "centsh".to_string(),
+            category_column: Some("category".to_string()),
+            negative_is_spending: invert,
+            ..ImportProfile::default()
+        };
+        self.import_csv_with_profile(path, ledger, &profile)
+    }
+
+    /// Appends a bank's CSV export at `path` to `ledger` using `profile`'s column mapping and
+    /// returns how many transactions were added. Rows that don't parse are returned as
+    /// failures with their line number instead of stopping the rest; only an unreadable file
+    /// or missing columns fail the whole import, before anything is added.
+    pub fn import_csv_with_profile(
+        &self,
+        path: &Path,
+        ledger: &mut Ledger,
+        profile: &ImportProfile,
+    ) -> Result<(usize, Vec<PasteFailure>)> {
+        let content = fs::read_to_string(path).map_err(CentshError::io("reading", path))?;
+        let block = parse_csv_with_profile(&content, profile)?;
+        let count = block.parsed.len();
+        for tx in block.parsed {
+            ledger.add_transaction(tx.description, tx.amount, tx.category, tx.date);
+        }
+        Ok((count, block.failed))
+    }
+
+    /// Writes a generated report named `file_name` next to the ledger and returns its path.
+    pub fn write_report(&self, file_name: &str, contents: &str) -> Result<PathBuf> {
+        let path = self.export_path(file_name);
+        fs::write(&path, contents).map_err(CentshError::io("writing", &path))?;
+        Ok(path)
+    }
+
+    /// Writes the ledger, or explains how to fix a read-only data directory.
     pub fn save(&self, ledger: &Ledger) -> Result<()> {
-        let json = serde_json::to_string_pretty(ledger).context("serializing data failed")?;
-        fs::write(&self.path, json).with_context(|| format!("writing {:?}", self.path))
+        if !self.writable {
+            return Err(CentshError::NotSaved(format!(
+                "data dir not writable — changes won't persist; set {LEDGER_ENV} to a writable file"
+            )));
+        }
+        let lean = ledger.without_history();
+        let json = serde_json::to_string_pretty(lean.as_ref().unwrap_or(ledger))
+            .map_err(CentshError::json("serializing data"))?;
+        if let Some(free) = free_space(&self.path) {
+            let needed = MIN_FREE_BYTES.max(json.len() as u64);
+            if free < needed {
+                return Err(CentshError::NotSaved(format!(
+                    "disk almost full ({} KB free) — not saved to avoid a partial write; free up space and save again",
+                    free / 1024
+                )));
+            }
+        }
+        write_atomic(&self.path, &json)?;
+        self.mark_in_sync();
+        Ok(())
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Whether the ledger file was modified by anything else since this instance last loaded
+    /// or saved it.
+    pub fn file_changed_since_load(&self) -> bool {
+        let current = self.modified();
+        current.is_some() && current != self.in_sync_at.get()
+    }
+
+    /// Treats the file as it is now as the one loaded, e.g. after choosing to keep the
+    /// in-memory version over an outside change.
+    pub fn mark_in_sync(&self) {
+        self.in_sync_at.set(self.modified());
+    }
+}
+
+/// Writes `contents` to `<path>.tmp` beside `path`, flushes it to disk and renames it over
+/// `path`, so a crash mid-write leaves the old file whole rather than a truncated one.
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let written = fs::File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .map_err(CentshError::io("writing", &tmp))
+        .and_then(|()| fs::rename(&tmp, path).map_err(CentshError::io("replacing", path)));
+    if written.is_err() {
+        fs::remove_file(&tmp).ok();
+    }
+    written
+}
+
+fn read_archive(path: &Path) -> Result<Vec<Transaction>> {
+    let content = fs::read_to_string(path).map_err(CentshError::io("reading", path))?;
+    serde_json::from_str(&content).map_err(CentshError::json("parsing archive"))
+}
+
+/// Free bytes available to us on the filesystem holding `path`, or `None` where that can't
+/// be asked (non-Unix platforms, or the query failed), in which case saving goes ahead.
+#[cfg(unix)]
+fn free_space(path: &Path) -> Option<u64> {
+    let dir = path
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let stats = rustix::fs::statvfs(dir).ok()?;
+    Some(stats.f_bavail.saturating_mul(stats.f_frsize))
+}
+
+#[cfg(not(unix))]
+fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Creates and removes a scratch file, since permission bits alone miss read-only mounts.
+fn dir_is_writable(dir: &Path) -> bool {
+    let probe = dir.join(".centsh-write-test");
+    let created = fs::write(&probe, b"").is_ok();
+    if created {
+        fs::remove_file(&probe).ok();
+    }
+    created
+}
+
+/// Picks the ledger file: an explicit `CENTSH_LEDGER` path wins, then the OS data directory,
+/// then `./centsh-data/` so the app still launches without a home directory.
+fn resolve_ledger_path(env: Option<PathBuf>, data_dir: Option<PathBuf>) -> (PathBuf, DataSource) {
+    if let Some(path) = env {
+        return (path, DataSource::Env);
+    }
+    match data_dir {
+        Some(dir) => (dir.join("ledger.json"), DataSource::DataDir),
+        None => (
+            PathBuf::from(FALLBACK_DIR).join("ledger.json"),
+            DataSource::Fallback,
+        ),
+    }
+}
+
+/// Checks a profile name, which becomes part of file names. Blank or `default` means the
+/// default ledger.
+fn profile_name(profile: Option<&str>) -> Result<Option<String>> {
+    let Some(name) = profile
+        .map(str::trim)
+        .filter(|n| !n.is_empty() && *n != "default")
+    else {
+        return Ok(None);
+    };
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(CentshError::Validation(format!(
+            "profile {name:?} may only use letters, digits, - and _"
+        )));
+    }
+    Ok(Some(name.to_string()))
+}
+
+/// `ledger.json` becomes `ledger-<profile>.json` in the same folder (likewise for a file
+/// named by `CENTSH_LEDGER`).
+fn profile_path(path: &Path, profile: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map_or("ledger".into(), |s| s.to_string_lossy());
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{profile}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{profile}"),
+    };
+    path.with_file_name(name)
+}
+
+fn parse_csv_with_profile(content: &str, profile: &ImportProfile) -> Result<PastedBlock> {
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .peekable();
+    let (_, first) = lines
+        .peek()
+        .copied()
+        .ok_or_else(|| CentshError::Parse("the file is empty".into()))?;
+    let first: Vec<String> = input::split_csv_line(first.trim_start_matches('\u{feff}'))
+        .into_iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+    // No header when the first row already starts with a date: read the columns in the
+    // app's own order, date, description, amount, category.
+    let headerless = first
+        .first()
+        .is_some_and(|f| NaiveDate::parse_from_str(f, &profile.date_format).is_ok());
+    let header = if headerless {
+        ["date", "description", "amount", "category"]
+            .map(String::from)
+            .to_vec()
+    } else {
+        lines.next();
+        first
+    };
+    let centsh_order = ImportProfile {
+        name: profile.name.clone(),
+        date_format: profile.date_format.clone(),
+        category_column: Some("category".to_string()),
+        negative_is_spending: profile.negative_is_spending,
+        ..ImportProfile::default()
+    };
+    let profile = if headerless { &centsh_order } else { profile };
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                CentshError::Parse(format!("no {name:?} column (found {})", header.join(", ")))
+            })
+    };
+    let date = column(&profile.date_column)?;
+    let description = column(&profile.description_column)?;
+    let category = profile.category_column.as_deref().map(column).transpose()?;
+    let amount = match (
+        &profile.amount_column,
+        &profile.debit_column,
+        &profile.credit_column,
+    ) {
+        (Some(amount), _, _) => AmountColumns::Signed(column(amount)?),
+        (None, Some(debit), Some(credit)) => {
+            AmountColumns::DebitCredit(column(debit)?, column(credit)?)
+        }
+        _ => {
+            return Err(CentshError::Validation(format!(
+                "profile {:?} needs an amount column or both debit and credit columns",
+                profile.name
+            )));
+        }
+    };
+
+    let mut block = PastedBlock {
+        parsed: Vec::new(),
+        failed: Vec::new(),
+    };
+    for (index, line) in lines {
+        let fields = input::split_csv_line(line);
+        let field = |i: usize| fields.get(i).map(|f| f.trim()).unwrap_or_default();
+        // Row errors are plain messages; the line number is added below.
+        let row = || -> Result<NewTransaction, String> {
+            let parsed_date = NaiveDate::parse_from_str(field(date), &profile.date_format)
+                .map_err(|err| {
+                    format!(
+                        "invalid date {:?} (expected {}): {err}",
+                        field(date),
+                        profile.date_format
+                    )
+                })?;
+            if field(description).is_empty() {
+                return Err("description is empty".into());
+            }
+            let money = |text: &str| input::parse_money(text).map_err(|err| format!("{err:#}"));
+            let value = match amount {
+                AmountColumns::Signed(i) => {
+                    let value = money(field(i))?;
+                    if profile.negative_is_spending {
+                        -value
+                    } else {
+                        value
+                    }
+                }
+                AmountColumns::DebitCredit(debit, credit) => {
+                    let side = |i: usize| match field(i) {
+                        "" => Ok(0.0),
+                        text => money(text).map(f64::abs),
+                    };
+                    side(debit)? - side(credit)?
+                }
+            };
+            Ok(NewTransaction {
+                description: field(description).to_string(),
+                amount: value,
+                category: input::category_or_default(category.map(field).unwrap_or_default()),
+                date: parsed_date,
+                note: String::new(),
+            })
+        };
+        match row() {
+            Ok(tx) => block.parsed.push(tx),
+            Err(error) => block.failed.push(PasteFailure {
+                line: index + 1,
+                text: line.trim().to_string(),
+                error,
+            }),
+        }
+    }
+    Ok(block)
+}
+
+/// Where a profile's amount comes from, as header positions.
+#[derive(Clone, Copy)]
+enum AmountColumns {
+    Signed(usize),
+    DebitCredit(usize, usize),
+}
+
+/// One object per line with the stable fields `id`, `date` (ISO `YYYY-MM-DD`),
+/// `description`, `amount` (positive is money out) and `category`.
+fn transactions_jsonl(rows: &[&Transaction]) -> String {
+    let mut out = String::new();
+    for tx in rows {
+        let line = serde_json::json!({
+            "id": tx.id,
+            "date": tx.date.to_string(),
+            "description": tx.description,
+            "amount": tx.amount,
+            "category": tx.category,
+        });
+        out.push_str(&line.to_string());
+        out.push('\n');
+    }
+    out
+}
+
+/// CSV with a header row, in the column order the paste parser expects.
+fn transactions_csv(rows: &[&Transaction]) -> String {
+    let mut out = String::from("date,description,amount,category\n");
+    for tx in rows {
+        out.push_str(&format!(
+            "{},{},{},{}\n",
+            tx.date,
+            csv_field(&tx.description),
+            tx.amount,
+            csv_field(&tx.category)
+        ));
+    }
+    out
+}
+
+/// Quotes a field containing commas or quotes. Line breaks become spaces, since the importer
+/// reads one row per line.
+fn csv_field(value: &str) -> String {
+    let value = value.replace(['\r', '\n'], " ");
+    if value.contains([',', '"']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ledger_path_falls_back_to_the_working_directory() {
+        let (path, source) = resolve_ledger_path(None, None);
+        assert_eq!(path, PathBuf::from(FALLBACK_DIR).join("ledger.json"));
+        assert_eq!(source, DataSource::Fallback);
+    }
+
+    #[test]
+    fn ledger_path_prefers_the_env_then_the_data_dir() {
+        let env = PathBuf::from("/tmp/mine.json");
+        let data_dir = PathBuf::from("/data/centsh");
+        assert_eq!(
+            resolve_ledger_path(Some(env.clone()), Some(data_dir.clone())),
+            (env, DataSource::Env)
+        );
+        assert_eq!(
+            resolve_ledger_path(None, Some(data_dir.clone())),
+            (data_dir.join("ledger.json"), DataSource::DataDir)
+        );
+    }
+
+    #[test]
+    fn save_refuses_a_read_only_data_dir() {
+        let path = std::env::temp_dir().join("centsh-read-only-test.json");
+        let mut storage = Storage::at(path.clone());
+        storage.writable = false;
+        let err = storage.save(&Ledger::empty()).unwrap_err();
+        assert!(matches!(err, CentshError::NotSaved(_)), "got {err}");
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn save_reports_a_path_it_cannot_write() {
+        // A file standing where the parent directory should be can't be written through,
+        // even as root.
+        let blocker = std::env::temp_dir().join(format!("centsh-blocker-{}", std::process::id()));
+        fs::write(&blocker, b"").unwrap();
+        let mut storage = Storage::at(blocker.join("ledger.json"));
+        // Past the startup check, so the write itself has to fail.
+        storage.writable = true;
+        let result = storage.save(&Ledger::empty());
+        fs::remove_file(&blocker).unwrap();
+        assert!(
+            matches!(result, Err(CentshError::Io { .. })),
+            "got {result:?}"
+        );
+    }
+
+    #[test]
+    fn export_jsonl_writes_one_flat_object_per_line_oldest_first() {
+        let mut ledger = Ledger::empty();
+        ledger.set_opening_balance(None, 500.0, NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        ledger.add_transaction(
+            "Pay",
+            -1000.0,
+            "Income",
+            NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+        );
+        ledger.add_transaction(
+            "Tea \"loose\"",
+            4.5,
+            "Food",
+            NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(),
+        );
+        let path = std::env::temp_dir().join(format!("centsh-export-{}.jsonl", std::process::id()));
+
+        Storage::at(std::env::temp_dir().join("ledger.json"))
+            .export_jsonl(&ledger, &path)
+            .unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        let mut keys: Vec<&str> = lines[0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["amount", "category", "date", "description", "id"]);
+        assert_eq!(lines[0]["date"], "2024-01-02");
+        assert_eq!(lines[0]["description"], "Tea \"loose\"");
+        assert_eq!(lines[0]["amount"], 4.5);
+        assert_eq!(lines[0]["category"], "Food");
+        assert_eq!(lines[1]["date"], "2024-01-31");
+        assert_eq!(lines[1]["amount"], -1000.0);
+    }
+
+    #[test]
+    fn import_csv_appends_rows_and_reports_bad_lines() {
+        let dir = std::env::temp_dir().join(format!("centsh-import-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bank.csv");
+        fs::write(
+            &path,
+            "date,description,amount,category\n\
+             2024-03-01,\"Dinner, with friends\",42.50,Food\n\
+             2024-03-02,Broken row,lots,Food\n\
+             2024-03-03,Salary,-1000,Income\n",
+        )
+        .unwrap();
+        let storage = Storage::at(dir.join("ledger.json"));
+
+        let mut ledger = Ledger::empty();
+        let (count, failed) = storage.import_csv(&path, &mut ledger, false).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].line, 3);
+        assert!(
+            failed[0].error.contains("not an amount"),
+            "{}",
+            failed[0].error
+        );
+        let dinner = ledger
+            .transactions
+            .iter()
+            .find(|t| t.description == "Dinner, with friends")
+            .unwrap();
+        assert_eq!((dinner.amount, dinner.category.as_str()), (42.5, "Food"));
+        assert!(ledger.transactions.iter().any(|t| t.amount == -1000.0));
+
+        let mut inverted = Ledger::empty();
+        storage.import_csv(&path, &mut inverted, true).unwrap();
+        assert!(inverted.transactions.iter().any(|t| t.amount == -42.5));
+
+        fs::remove_dir_all(&dir).ok();
     }
 }